@@ -0,0 +1,179 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use ecs::{
+    Entity, EntityId, EntityShapeBuilder, Query, ResMut, RunSystemPhase, Service, System, ECS,
+};
+
+const ENTITIES: usize = 10_000;
+
+struct A(f32);
+struct B(f32);
+struct C(f32);
+struct D(f32);
+struct Tag;
+
+struct Counter(u64);
+
+fn full_entity(i: usize) -> Entity {
+    let value = i as f32;
+    Entity::new()
+        .with(A(value))
+        .with(B(value))
+        .with(C(value))
+        .with(D(value))
+}
+
+fn world() -> (ECS, Vec<EntityId>) {
+    let mut ecs = ECS::new();
+    let ids = (0..ENTITIES)
+        .map(|i| ecs.add_entity(full_entity(i)))
+        .collect();
+    (ecs, ids)
+}
+
+fn spawn(c: &mut Criterion) {
+    c.bench_function("spawn", |b| {
+        b.iter_batched(
+            ECS::new,
+            |mut ecs| {
+                for i in 0..ENTITIES {
+                    ecs.add_entity(full_entity(i));
+                }
+                ecs
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn despawn(c: &mut Criterion) {
+    c.bench_function("despawn", |b| {
+        b.iter_batched(
+            world,
+            |(mut ecs, ids)| {
+                for id in ids {
+                    ecs.despawn(id);
+                }
+                ecs
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn add_remove_component(c: &mut Criterion) {
+    c.bench_function("add_component", |b| {
+        b.iter_batched(
+            world,
+            |(mut ecs, ids)| {
+                for id in ids {
+                    ecs.insert_component(id, Tag);
+                }
+                ecs
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    c.bench_function("remove_component", |b| {
+        b.iter_batched(
+            || {
+                let (mut ecs, ids) = world();
+                for id in ids.iter() {
+                    ecs.insert_component(*id, Tag);
+                }
+                (ecs, ids)
+            },
+            |(mut ecs, ids)| {
+                for id in ids {
+                    ecs.remove_component::<Tag>(id);
+                }
+                ecs
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn iterate(c: &mut Criterion) {
+    let (mut ecs, _) = world();
+
+    c.bench_function("iterate_1", |b| {
+        b.iter(|| {
+            for mut a in ecs.query::<&mut A>() {
+                a.0 += 1.0;
+            }
+        })
+    });
+    c.bench_function("iterate_2", |b| {
+        b.iter(|| {
+            for (mut a, b) in ecs.query::<(&mut A, &B)>() {
+                a.0 += b.0;
+            }
+        })
+    });
+    c.bench_function("iterate_3", |b| {
+        b.iter(|| {
+            for (mut a, b, c) in ecs.query::<(&mut A, &B, &C)>() {
+                a.0 += b.0 * c.0;
+            }
+        })
+    });
+    c.bench_function("iterate_4", |b| {
+        b.iter(|| {
+            for (mut a, b, c, d) in ecs.query::<(&mut A, &B, &C, &D)>() {
+                a.0 += b.0 * c.0 - d.0;
+            }
+        })
+    });
+
+    // Same work through `run_systems`, per entity and as a service
+    ecs.add_system(System::at_tick(
+        EntityShapeBuilder::new().with::<A>().with::<B>().build(),
+        |entity, _, _| {
+            let b = entity.get::<B>().unwrap().0;
+            entity.get_mut::<A>().unwrap().0 += b;
+        },
+    ));
+    c.bench_function("run_systems_entity", |b| {
+        b.iter(|| ecs.run_systems(RunSystemPhase::Tick))
+    });
+
+    let (mut ecs, _) = world();
+    ecs.add_before_service(Service::at_tick(|mut query: Query<(&mut A, &B)>| {
+        for (mut a, b) in &mut query {
+            a.0 += b.0;
+        }
+    }));
+    c.bench_function("run_systems_query", |b| {
+        b.iter(|| ecs.run_systems(RunSystemPhase::Tick))
+    });
+}
+
+fn resources(c: &mut Criterion) {
+    let mut ecs = ECS::new();
+    ecs.resources.set(Counter(0));
+
+    c.bench_function("resource_get", |b| {
+        b.iter(|| black_box(ecs.resources.get::<Counter>().unwrap().0))
+    });
+    c.bench_function("resource_get_mut", |b| {
+        b.iter(|| ecs.resources.get_mut::<Counter>().unwrap().0 += 1)
+    });
+
+    ecs.add_before_service(Service::at_tick(|mut counter: ResMut<Counter>| {
+        counter.0 += 1;
+    }));
+    c.bench_function("resource_service", |b| {
+        b.iter(|| ecs.run_systems(RunSystemPhase::Tick))
+    });
+}
+
+criterion_group!(
+    benches,
+    spawn,
+    despawn,
+    add_remove_component,
+    iterate,
+    resources
+);
+criterion_main!(benches);
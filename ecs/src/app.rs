@@ -0,0 +1,407 @@
+use super::{RunSystemPhase, ScheduleError, ECS};
+use std::any::{type_name, TypeId};
+use std::collections::HashSet;
+use std::fmt;
+use std::mem;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PluginId {
+    type_id: TypeId,
+    name: &'static str,
+}
+
+impl PluginId {
+    pub fn of<P: Plugin>() -> Self {
+        Self {
+            type_id: TypeId::of::<P>(),
+            name: type_name::<P>(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+// A feature of the game: the resources, systems and services it registers. Configuration goes in
+// the fields of the implementing struct
+pub trait Plugin: 'static {
+    fn build(&self, app: &mut App);
+
+    // Plugins that have to be built before this one, `App::build` fails when one is missing
+    fn dependencies(&self) -> Vec<PluginId> {
+        Vec::new()
+    }
+}
+
+// A set of plugins added together, like everything a game mode needs
+pub trait PluginGroup {
+    fn build(self, group: &mut PluginGroupBuilder);
+}
+
+#[derive(Default)]
+pub struct PluginGroupBuilder {
+    plugins: Vec<PendingPlugin>,
+}
+
+impl PluginGroupBuilder {
+    pub fn add<P: Plugin>(&mut self, plugin: P) -> &mut Self {
+        self.plugins.push(PendingPlugin::new(plugin));
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum AppError {
+    DuplicatePlugin {
+        plugin: &'static str,
+    },
+    MissingDependency {
+        plugin: &'static str,
+        dependency: &'static str,
+    },
+    Cycle {
+        plugins: Vec<&'static str>,
+    },
+    Schedule(ScheduleError),
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AppError::DuplicatePlugin { plugin } => {
+                write!(f, "Plugin `{}` was added more than once", plugin)
+            }
+            AppError::MissingDependency { plugin, dependency } => write!(
+                f,
+                "Plugin `{}` depends on plugin `{}`, which was never added",
+                plugin, dependency
+            ),
+            AppError::Cycle { plugins } => {
+                write!(f, "Plugins depend on each other: {}", plugins.join(", "))
+            }
+            AppError::Schedule(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+struct PendingPlugin {
+    id: PluginId,
+    plugin: Box<dyn Plugin>,
+}
+
+impl PendingPlugin {
+    fn new<P: Plugin>(plugin: P) -> Self {
+        Self {
+            id: PluginId::of::<P>(),
+            plugin: Box::new(plugin),
+        }
+    }
+}
+
+type Extract = Box<dyn FnMut(&mut ECS, &mut ECS)>;
+
+// World next to the main one with its own entities, resources and schedule, like a world per
+// level, a render-extraction world or a headless server world
+pub struct SubApp {
+    pub ecs: ECS,
+    extract: Option<Extract>,
+}
+
+impl SubApp {
+    pub fn new(ecs: ECS) -> Self {
+        Self { ecs, extract: None }
+    }
+
+    // Runs ahead of the sub app schedule with the main world first, to copy or move over what
+    // the sub app works on
+    pub fn with_extract<F>(mut self, extract: F) -> Self
+    where
+        F: FnMut(&mut ECS, &mut ECS) + 'static,
+    {
+        self.extract = Some(Box::new(extract));
+        self
+    }
+}
+
+pub struct App {
+    pub ecs: ECS,
+    sub_apps: Vec<(&'static str, SubApp)>,
+    pending: Vec<PendingPlugin>,
+    added: HashSet<TypeId>,
+    built: HashSet<TypeId>,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl App {
+    pub fn new() -> Self {
+        Self::from_ecs(ECS::new())
+    }
+
+    pub fn from_ecs(ecs: ECS) -> Self {
+        Self {
+            ecs,
+            sub_apps: Vec::new(),
+            pending: Vec::new(),
+            added: HashSet::new(),
+            built: HashSet::new(),
+        }
+    }
+
+    // Queued until `build`, plugins may add more plugins from their own `build`
+    pub fn add_plugin<P: Plugin>(&mut self, plugin: P) -> &mut Self {
+        self.pending.push(PendingPlugin::new(plugin));
+        self
+    }
+
+    pub fn add_plugins<G: PluginGroup>(&mut self, group: G) -> &mut Self {
+        let mut builder = PluginGroupBuilder::default();
+        group.build(&mut builder);
+        self.pending.append(&mut builder.plugins);
+        self
+    }
+
+    // Replaces the sub app with the same label
+    pub fn add_sub_app(&mut self, label: &'static str, sub_app: SubApp) -> &mut Self {
+        self.sub_apps.retain(|(other, _)| *other != label);
+        self.sub_apps.push((label, sub_app));
+        self
+    }
+
+    pub fn sub_app(&self, label: &str) -> Option<&ECS> {
+        self.sub_apps
+            .iter()
+            .find(|(other, _)| *other == label)
+            .map(|(_, sub_app)| &sub_app.ecs)
+    }
+
+    pub fn sub_app_mut(&mut self, label: &str) -> Option<&mut ECS> {
+        self.sub_apps
+            .iter_mut()
+            .find(|(other, _)| *other == label)
+            .map(|(_, sub_app)| &mut sub_app.ecs)
+    }
+
+    pub fn remove_sub_app(&mut self, label: &str) -> Option<SubApp> {
+        let index = self
+            .sub_apps
+            .iter()
+            .position(|(other, _)| *other == label)?;
+        Some(self.sub_apps.remove(index).1)
+    }
+
+    // Extracts from the main world into every sub app and runs `phase` on it, in the order the
+    // sub apps were added. The main world schedule is left to the caller
+    pub fn run_sub_apps(&mut self, phase: RunSystemPhase) {
+        for (_, sub_app) in self.sub_apps.iter_mut() {
+            if let Some(extract) = sub_app.extract.as_mut() {
+                extract(&mut self.ecs, &mut sub_app.ecs);
+            }
+            sub_app.ecs.run_systems(phase.clone());
+        }
+    }
+
+    pub fn is_added<P: Plugin>(&self) -> bool {
+        self.added.contains(&TypeId::of::<P>())
+            || self
+                .pending
+                .iter()
+                .any(|pending| pending.id.type_id == TypeId::of::<P>())
+    }
+
+    // Builds the queued plugins, each after its dependencies and in the order they were added
+    // otherwise, then sorts the schedules of the main world and the sub apps
+    pub fn build(&mut self) -> Result<(), AppError> {
+        while !self.pending.is_empty() {
+            let mut pending = mem::take(&mut self.pending);
+
+            for plugin in pending.iter() {
+                if !self.added.insert(plugin.id.type_id) {
+                    return Err(AppError::DuplicatePlugin {
+                        plugin: plugin.id.name,
+                    });
+                }
+            }
+
+            for plugin in pending.iter() {
+                for dependency in plugin.plugin.dependencies() {
+                    if !self.added.contains(&dependency.type_id) {
+                        return Err(AppError::MissingDependency {
+                            plugin: plugin.id.name,
+                            dependency: dependency.name,
+                        });
+                    }
+                }
+            }
+
+            while !pending.is_empty() {
+                let built = &self.built;
+                let ready = pending.iter().position(|plugin| {
+                    plugin
+                        .plugin
+                        .dependencies()
+                        .iter()
+                        .all(|dependency| built.contains(&dependency.type_id))
+                });
+
+                let plugin = match ready {
+                    Some(index) => pending.remove(index),
+                    None => {
+                        return Err(AppError::Cycle {
+                            plugins: pending.iter().map(|plugin| plugin.id.name).collect(),
+                        })
+                    }
+                };
+
+                plugin.plugin.build(self);
+                self.built.insert(plugin.id.type_id);
+            }
+        }
+
+        self.ecs.build_schedule().map_err(AppError::Schedule)?;
+        for (_, sub_app) in self.sub_apps.iter_mut() {
+            sub_app.ecs.build_schedule().map_err(AppError::Schedule)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EntityShapeBuilder, Service, System};
+
+    struct Log(Vec<&'static str>);
+
+    fn logging_app() -> App {
+        let mut app = App::new();
+        app.ecs.resources.set(Log(Vec::new()));
+        app
+    }
+
+    fn log(app: &mut App, name: &'static str) {
+        app.ecs.resources.get_mut::<Log>().unwrap().0.push(name);
+    }
+
+    fn built(app: &App) -> Vec<&'static str> {
+        app.ecs.resources.get::<Log>().unwrap().0.clone()
+    }
+
+    struct Base;
+    struct Dependent;
+    struct Outer;
+    struct Looping;
+    struct Looped;
+
+    impl Plugin for Base {
+        fn build(&self, app: &mut App) {
+            log(app, "base");
+        }
+    }
+
+    impl Plugin for Dependent {
+        fn build(&self, app: &mut App) {
+            log(app, "dependent");
+        }
+
+        fn dependencies(&self) -> Vec<PluginId> {
+            vec![PluginId::of::<Base>()]
+        }
+    }
+
+    impl Plugin for Outer {
+        fn build(&self, app: &mut App) {
+            log(app, "outer");
+            app.add_plugin(Dependent);
+        }
+    }
+
+    impl Plugin for Looping {
+        fn build(&self, _app: &mut App) {}
+
+        fn dependencies(&self) -> Vec<PluginId> {
+            vec![PluginId::of::<Looped>()]
+        }
+    }
+
+    impl Plugin for Looped {
+        fn build(&self, _app: &mut App) {}
+
+        fn dependencies(&self) -> Vec<PluginId> {
+            vec![PluginId::of::<Looping>()]
+        }
+    }
+
+    struct Group;
+
+    impl PluginGroup for Group {
+        fn build(self, group: &mut PluginGroupBuilder) {
+            group.add(Dependent).add(Base);
+        }
+    }
+
+    #[test]
+    fn dependencies_are_built_first() {
+        let mut app = logging_app();
+        app.add_plugins(Group);
+        assert!(app.is_added::<Base>());
+        app.build().unwrap();
+
+        assert_eq!(built(&app), ["base", "dependent"]);
+    }
+
+    #[test]
+    fn plugins_added_while_building_are_built_too() {
+        let mut app = logging_app();
+        app.add_plugin(Outer).add_plugin(Base);
+        app.build().unwrap();
+
+        assert_eq!(built(&app), ["outer", "base", "dependent"]);
+        assert!(app.is_added::<Dependent>());
+    }
+
+    #[test]
+    fn broken_plugin_sets_are_errors() {
+        let mut app = logging_app();
+        app.add_plugin(Dependent);
+        let result = app.build();
+        assert!(matches!(result, Err(AppError::MissingDependency { .. })));
+
+        let mut app = logging_app();
+        app.add_plugin(Base).add_plugin(Base);
+        assert!(matches!(app.build(), Err(AppError::DuplicatePlugin { .. })));
+
+        let mut app = logging_app();
+        app.add_plugin(Base).build().unwrap();
+        app.add_plugin(Base);
+        assert!(matches!(app.build(), Err(AppError::DuplicatePlugin { .. })));
+
+        let mut app = logging_app();
+        app.add_plugin(Looping).add_plugin(Looped);
+        match app.build() {
+            Err(AppError::Cycle { plugins }) => assert_eq!(plugins.len(), 2),
+            result => panic!("Expected a cycle, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn schedule_errors_fail_the_build() {
+        let mut app = logging_app();
+        let service = Service::at_tick(|| {}).after("missing");
+        app.ecs.add_before_service(service);
+        assert!(matches!(app.build(), Err(AppError::Schedule(_))));
+
+        let mut app = logging_app();
+        let shape = EntityShapeBuilder::new().build();
+        let system = System::at_tick(shape, |_, _, _| {}).before("missing");
+        app.ecs.add_system(system);
+        assert!(matches!(app.build(), Err(AppError::Schedule(_))));
+    }
+}
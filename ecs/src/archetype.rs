@@ -0,0 +1,340 @@
+use super::entity::EntityId;
+use super::reflect::TypeRegistry;
+use super::shape::EntityShape;
+use std::any::{type_name, Any, TypeId};
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+
+// Type erased `Vec<T>`, one per component type in a table
+pub(crate) trait Column: Any {
+    fn swap_remove(&mut self, row: usize);
+    fn move_row(&mut self, row: usize, target: &mut dyn Column);
+    fn new_empty(&self) -> Box<dyn Column>;
+    fn row(&self, row: usize) -> &dyn Any;
+    fn row_mut(&mut self, row: usize) -> &mut dyn Any;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+// Values of a column sit in a cell so the services of a parallel batch can take pointers to
+// different columns of one table from a shared `&Archetype`. Writing through them is sound as
+// long as the `SystemAccess` of the batch holds no conflict
+pub(crate) type ColumnCell<T> = UnsafeCell<Vec<T>>;
+
+pub(crate) fn new_column<T: 'static>(values: Vec<T>) -> Box<dyn Column> {
+    Box::new(ColumnCell::new(values))
+}
+
+impl<T: 'static> Column for ColumnCell<T> {
+    fn swap_remove(&mut self, row: usize) {
+        self.get_mut().swap_remove(row);
+    }
+
+    fn move_row(&mut self, row: usize, target: &mut dyn Column) {
+        let value = self.get_mut().swap_remove(row);
+        target
+            .as_any_mut()
+            .downcast_mut::<ColumnCell<T>>()
+            .expect("Column type mismatch")
+            .get_mut()
+            .push(value);
+    }
+
+    fn new_empty(&self) -> Box<dyn Column> {
+        new_column(Vec::<T>::new())
+    }
+
+    fn row(&self, row: usize) -> &dyn Any {
+        // Only queries write without `&mut`, and never while the table is borrowed like this
+        unsafe { &(&*self.get())[row] }
+    }
+
+    fn row_mut(&mut self, row: usize) -> &mut dyn Any {
+        &mut self.get_mut()[row]
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntityLocation {
+    pub archetype: usize,
+    pub row: usize,
+}
+
+pub struct Archetype {
+    pub(crate) shape: Vec<TypeId>,
+    pub(crate) columns: HashMap<TypeId, Box<dyn Column>>,
+    // Tick of the insertion and of the last mutable access, per component and row
+    pub(crate) added: HashMap<TypeId, ColumnCell<u32>>,
+    pub(crate) changed: HashMap<TypeId, ColumnCell<u32>>,
+    pub(crate) entities: Vec<EntityId>,
+}
+
+impl Archetype {
+    pub(crate) fn new(shape: Vec<TypeId>) -> Self {
+        let added = shape
+            .iter()
+            .map(|type_id| (*type_id, ColumnCell::default()))
+            .collect();
+        let changed = shape
+            .iter()
+            .map(|type_id| (*type_id, ColumnCell::default()))
+            .collect();
+
+        Self {
+            shape,
+            columns: HashMap::new(),
+            added,
+            changed,
+            entities: Vec::new(),
+        }
+    }
+
+    pub fn shape(&self) -> &[TypeId] {
+        &self.shape
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn has(&self, type_id: &TypeId) -> bool {
+        self.columns.contains_key(type_id)
+    }
+
+    pub fn matches(&self, shape: &EntityShape) -> bool {
+        shape.include.iter().all(|type_id| self.has(type_id))
+            && !shape.exclude.iter().any(|type_id| self.has(type_id))
+    }
+
+    pub fn entities(&self) -> &[EntityId] {
+        &self.entities
+    }
+
+    // Returns the entity that was moved into `row` to fill the gap, if any
+    pub(crate) fn swap_remove(&mut self, row: usize) -> Option<EntityId> {
+        for column in self.columns.values_mut() {
+            column.swap_remove(row);
+        }
+        for ticks in self.added.values_mut().chain(self.changed.values_mut()) {
+            ticks.get_mut().swap_remove(row);
+        }
+        self.entities.swap_remove(row);
+        self.entities.get(row).cloned()
+    }
+
+    // Like `swap_remove`, but the components of `row` are handed back as one-row columns
+    pub(crate) fn take_row(
+        &mut self,
+        row: usize,
+    ) -> (HashMap<TypeId, Box<dyn Column>>, Option<EntityId>) {
+        let mut components = HashMap::new();
+        for (type_id, column) in self.columns.iter_mut() {
+            let mut target = column.new_empty();
+            column.move_row(row, target.as_mut());
+            components.insert(*type_id, target);
+        }
+        for ticks in self.added.values_mut().chain(self.changed.values_mut()) {
+            ticks.get_mut().swap_remove(row);
+        }
+        self.entities.swap_remove(row);
+        (components, self.entities.get(row).cloned())
+    }
+
+    // Ticks of a component pushed along with its value, as (added, changed)
+    pub(crate) fn push_ticks(&mut self, type_id: &TypeId, (added, changed): (u32, u32)) {
+        self.added.get_mut(type_id).unwrap().get_mut().push(added);
+        self.changed
+            .get_mut(type_id)
+            .unwrap()
+            .get_mut()
+            .push(changed);
+    }
+
+    pub(crate) fn swap_remove_ticks(&mut self, type_id: &TypeId, row: usize) -> (u32, u32) {
+        (
+            self.added
+                .get_mut(type_id)
+                .unwrap()
+                .get_mut()
+                .swap_remove(row),
+            self.changed
+                .get_mut(type_id)
+                .unwrap()
+                .get_mut()
+                .swap_remove(row),
+        )
+    }
+
+    fn tick(ticks: &HashMap<TypeId, ColumnCell<u32>>, type_id: &TypeId, row: usize) -> Option<u32> {
+        ticks
+            .get(type_id)
+            .map(|ticks| unsafe { (&*ticks.get())[row] })
+    }
+
+    pub(crate) fn set_changed(&mut self, type_id: &TypeId, row: usize, tick: u32) {
+        if let Some(ticks) = self.changed.get_mut(type_id) {
+            ticks.get_mut()[row] = tick;
+        }
+    }
+
+    pub(crate) fn column<T: 'static>(&self) -> Option<&Vec<T>> {
+        self.column_ptr::<T>().map(|column| unsafe { &*column })
+    }
+
+    pub(crate) fn column_mut<T: 'static>(&mut self) -> Option<&mut Vec<T>> {
+        self.columns
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|column| column.as_any_mut().downcast_mut::<ColumnCell<T>>())
+            .map(ColumnCell::get_mut)
+    }
+
+    // Taken without borrowing the table mutably, for queries running next to each other
+    pub(crate) fn column_ptr<T: 'static>(&self) -> Option<*mut Vec<T>> {
+        self.columns
+            .get(&TypeId::of::<T>())
+            .and_then(|column| column.as_any().downcast_ref::<ColumnCell<T>>())
+            .map(ColumnCell::get)
+    }
+}
+
+// Sorted component set, the key of an archetype table
+pub(crate) fn sorted_shape<'a, I: Iterator<Item = &'a TypeId>>(types: I) -> Vec<TypeId> {
+    let mut shape: Vec<TypeId> = types.cloned().collect();
+    shape.sort();
+    shape.dedup();
+    shape
+}
+
+pub struct EntityRef<'a> {
+    pub(crate) archetype: &'a Archetype,
+    pub(crate) row: usize,
+    pub(crate) types: &'a TypeRegistry,
+}
+
+impl<'a> EntityRef<'a> {
+    pub fn id(&self) -> EntityId {
+        self.archetype.entities[self.row]
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&'a T> {
+        self.archetype
+            .column::<T>()
+            .and_then(|column| column.get(self.row))
+    }
+
+    pub fn has<T: 'static>(&self) -> bool {
+        self.archetype.has(&TypeId::of::<T>())
+    }
+}
+
+pub struct EntityMut<'a> {
+    pub(crate) archetype: &'a mut Archetype,
+    pub(crate) row: usize,
+    pub(crate) change_tick: u32,
+    pub(crate) last_change_tick: u32,
+}
+
+impl<'a> EntityMut<'a> {
+    pub fn id(&self) -> EntityId {
+        self.archetype.entities[self.row]
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.archetype
+            .column::<T>()
+            .and_then(|column| column.get(self.row))
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        let row = self.row;
+        self.archetype
+            .set_changed(&TypeId::of::<T>(), row, self.change_tick);
+        self.archetype
+            .column_mut::<T>()
+            .and_then(|column| column.get_mut(row))
+    }
+
+    pub fn get_many_mut<'b, T: ComponentSet<'b>>(&'b mut self) -> Option<T::Refs> {
+        T::fetch(self.archetype, self.row, self.change_tick)
+    }
+
+    pub fn has<T: 'static>(&self) -> bool {
+        self.archetype.has(&TypeId::of::<T>())
+    }
+
+    // Whether `T` was inserted since the running system last ran
+    pub fn is_added<T: 'static>(&self) -> bool {
+        match Archetype::tick(&self.archetype.added, &TypeId::of::<T>(), self.row) {
+            Some(tick) => tick > self.last_change_tick,
+            None => false,
+        }
+    }
+
+    // Whether `T` was inserted or mutably accessed since the running system last ran
+    pub fn is_changed<T: 'static>(&self) -> bool {
+        match Archetype::tick(&self.archetype.changed, &TypeId::of::<T>(), self.row) {
+            Some(tick) => tick > self.last_change_tick,
+            None => false,
+        }
+    }
+}
+
+pub trait ComponentSet<'a> {
+    type Refs;
+
+    fn fetch(archetype: &'a mut Archetype, row: usize, change_tick: u32) -> Option<Self::Refs>;
+}
+
+fn assert_disjoint(type_ids: &[TypeId], names: &[&str]) {
+    for (i, type_id) in type_ids.iter().enumerate() {
+        if type_ids[..i].contains(type_id) {
+            panic!(
+                "Component `{}` is borrowed mutably more than once in {:?}",
+                names[i], names
+            );
+        }
+    }
+}
+
+macro_rules! impl_component_set {
+    ($($name:ident),+) => {
+        impl<'a, $($name: 'static),+> ComponentSet<'a> for ($($name,)+) {
+            type Refs = ($(&'a mut $name,)+);
+
+            #[allow(non_snake_case)]
+            fn fetch(archetype: &'a mut Archetype, row: usize, change_tick: u32) -> Option<Self::Refs> {
+                assert_disjoint(
+                    &[$(TypeId::of::<$name>()),+],
+                    &[$(type_name::<$name>()),+],
+                );
+
+                $(
+                    let $name: *mut $name = archetype.column_mut::<$name>()?.get_mut(row)?;
+                    archetype.set_changed(&TypeId::of::<$name>(), row, change_tick);
+                )+
+
+                // Every pointer lives in a different column, checked above
+                unsafe { Some(($(&mut *$name,)+)) }
+            }
+        }
+    };
+}
+
+impl_component_set!(A);
+impl_component_set!(A, B);
+impl_component_set!(A, B, C);
+impl_component_set!(A, B, C, D);
+impl_component_set!(A, B, C, D, E);
+impl_component_set!(A, B, C, D, E, F);
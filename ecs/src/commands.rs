@@ -0,0 +1,142 @@
+use super::{Entity, EntityId, ECS};
+
+type Command = Box<dyn FnOnce(&mut ECS)>;
+
+// Structural changes recorded while systems run, applied by the ECS once the
+// phase is over. Always present in the ResourceRegistry. Commands aren't `Send`,
+// services take them as `NonSendMut<Commands>`.
+pub struct Commands {
+    queue: Vec<Command>,
+}
+
+impl Default for Commands {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Commands {
+    pub fn new() -> Self {
+        Self { queue: Vec::new() }
+    }
+
+    pub fn spawn(&mut self, entity: Entity) {
+        self.queue.push(Box::new(move |ecs: &mut ECS| {
+            ecs.add_entity(entity);
+        }));
+    }
+
+    pub fn despawn(&mut self, id: EntityId) {
+        self.queue.push(Box::new(move |ecs: &mut ECS| {
+            ecs.despawn(id);
+        }));
+    }
+
+    pub fn despawn_recursive(&mut self, id: EntityId) {
+        self.queue.push(Box::new(move |ecs: &mut ECS| {
+            ecs.despawn_recursive(id);
+        }));
+    }
+
+    pub fn set_parent(&mut self, child: EntityId, parent: EntityId) {
+        self.queue.push(Box::new(move |ecs: &mut ECS| {
+            ecs.set_parent(child, parent);
+        }));
+    }
+
+    pub fn remove_parent(&mut self, child: EntityId) {
+        self.queue.push(Box::new(move |ecs: &mut ECS| {
+            ecs.remove_parent(child);
+        }));
+    }
+
+    pub fn insert<T: 'static>(&mut self, id: EntityId, value: T) {
+        self.queue.push(Box::new(move |ecs: &mut ECS| {
+            ecs.insert_component(id, value);
+        }));
+    }
+
+    pub fn remove<T: 'static>(&mut self, id: EntityId) {
+        self.queue.push(Box::new(move |ecs: &mut ECS| {
+            ecs.remove_component::<T>(id);
+        }));
+    }
+
+    pub fn add<F: FnOnce(&mut ECS) + 'static>(&mut self, command: F) {
+        self.queue.push(Box::new(command));
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub(crate) fn take(&mut self) -> Vec<Command> {
+        std::mem::take(&mut self.queue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EntityShapeBuilder, RunSystemPhase, System};
+
+    struct A;
+    struct B;
+
+    #[test]
+    fn commands_wait_for_the_end_of_the_phase() {
+        let mut ecs = ECS::new();
+        for _ in 0..3 {
+            ecs.add_entity(Entity::new().with(A));
+        }
+
+        let shape = EntityShapeBuilder::new().with::<A>().build();
+        ecs.add_system(System::at_tick(shape, |entity, resources, _| {
+            let mut commands = resources.get_mut::<Commands>().unwrap();
+            commands.despawn(entity.id());
+            commands.spawn(Entity::new().with(B));
+        }));
+        ecs.run_systems(RunSystemPhase::Tick);
+
+        assert_eq!(ecs.query::<&A>().count(), 0);
+        assert_eq!(ecs.query::<&B>().count(), 3);
+        assert!(ecs.resources.get::<Commands>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn commands_apply_in_order() {
+        let mut ecs = ECS::new();
+        let id = ecs.add_entity(Entity::new().with(A));
+
+        let mut commands = ecs.resources.get_mut::<Commands>().unwrap();
+        commands.insert(id, B);
+        commands.remove::<B>(id);
+        commands.remove::<A>(id);
+        commands.insert(id, A);
+        drop(commands);
+        ecs.apply_commands();
+
+        let entity = ecs.get(id).unwrap();
+        assert!(entity.has::<A>());
+        assert!(!entity.has::<B>());
+    }
+
+    #[test]
+    fn commands_queued_by_commands_apply_too() {
+        let mut ecs = ECS::new();
+        ecs.resources
+            .get_mut::<Commands>()
+            .unwrap()
+            .add(|ecs: &mut ECS| {
+                let id = ecs.add_entity(Entity::new().with(A));
+                ecs.resources.get_mut::<Commands>().unwrap().insert(id, B);
+            });
+        ecs.apply_commands();
+
+        assert_eq!(ecs.query::<(&A, &B)>().count(), 1);
+    }
+}
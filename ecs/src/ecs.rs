@@ -1,13 +1,37 @@
+use super::archetype::{
+    new_column, sorted_shape, Archetype, Column, EntityLocation, EntityMut, EntityRef,
+};
+use super::events::{update_events, Subscription};
+use super::hooks::{ComponentHooks, HookKind};
+use super::reflect::TypeRegistry;
+use super::scene::SceneRegistry;
+use super::schedule::is_ordered;
+use super::system::RunCondition;
+use super::worlds::CloneComponent;
 use super::{
-    Entity, EntityShape, ResourceRegistry, RunSystemPhase, Service, ServicePhase, System,
-    SystemRunner,
+    Bounds, Commands, Entity, EntityId, EntityShape, Events, QueryFilter, QueryIter,
+    ResourceRegistry, RunSystemPhase, ScheduleError, Service, ServicePhase, System, SystemContext,
+    SystemRunner, Transform, WorldQuery,
 };
 use generational_arena::Arena;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
 use std::fmt;
 
 pub struct ECS {
-    pub(crate) entities: Arena<Entity>,
+    pub(crate) entities: Arena<EntityLocation>,
+    pub(crate) archetypes: Vec<Archetype>,
+    archetype_index: HashMap<Vec<TypeId>, usize>,
     systems: SystemRunner,
+    hooks: ComponentHooks,
+    event_updaters: Vec<fn(&ResourceRegistry)>,
+    pub(crate) state_updaters: Vec<fn(&mut ECS)>,
+    pub(crate) state_services: HashMap<TypeId, Box<dyn Any>>,
+    pub(crate) scene_types: SceneRegistry,
+    pub(crate) types: TypeRegistry,
+    pub(crate) cloners: HashMap<TypeId, CloneComponent>,
+    pub(crate) change_tick: u32,
+    last_change_tick: u32,
     pub resources: ResourceRegistry,
 }
 
@@ -17,117 +41,808 @@ impl fmt::Debug for ECS {
     }
 }
 
+// Conditions come first, so a skipped subscriber still sees its events once they hold again
+fn should_run(
+    conditions: &[RunCondition],
+    subscription: &mut Option<Subscription>,
+    phase: &RunSystemPhase,
+    resources: &ResourceRegistry,
+) -> bool {
+    if !conditions.iter().all(|condition| condition(resources)) {
+        return false;
+    }
+
+    match (phase, subscription) {
+        (RunSystemPhase::Update, Some(subscription)) => subscription.poll(resources),
+        _ => true,
+    }
+}
+
+// Consecutive services that don't conflict and aren't ordered against each other run together
+// on the thread pool, the ones pinned to the main thread always run alone. Every service runs at
+// its own tick
+pub(crate) fn run_services(
+    services: &mut [Service],
+    context: &SystemContext,
+    change_tick: &mut u32,
+) {
+    let resources = context.resources();
+    let mut running = Vec::new();
+    for service in services.iter_mut() {
+        let (conditions, subscription) = (&service.conditions, &mut service.subscription);
+        if should_run(conditions, subscription, context.phase(), resources) {
+            running.push(service);
+        }
+    }
+    let mut services = running;
+
+    let mut start = 0;
+    while start < services.len() {
+        let mut end = start + 1;
+        while end < services.len() && can_batch(&services[start..end], services[end]) {
+            end += 1;
+        }
+
+        let batch = &mut services[start..end];
+        let contexts: Vec<SystemContext> = batch
+            .iter_mut()
+            .map(|service| {
+                *change_tick += 1;
+                let context = context.with_ticks(*change_tick, service.last_run);
+                service.last_run = *change_tick;
+                context
+            })
+            .collect();
+
+        match batch {
+            [service] => service.calls.run(&contexts[0]),
+            batch => rayon::scope(|scope| {
+                for (service, context) in batch.iter_mut().zip(&contexts) {
+                    let calls = &mut service.calls;
+                    scope.spawn(move |_| calls.run(context));
+                }
+            }),
+        }
+
+        start = end;
+    }
+}
+
+fn can_batch(batch: &[&mut Service], service: &Service) -> bool {
+    batch.iter().all(|other| {
+        other.access.is_compatible(&service.access) && !is_ordered(&other.order, &service.order)
+    })
+}
+
+fn pair_mut<T>(items: &mut [T], a: usize, b: usize) -> (&mut T, &mut T) {
+    assert_ne!(a, b);
+    if a < b {
+        let (left, right) = items.split_at_mut(b);
+        (&mut left[a], &mut right[0])
+    } else {
+        let (left, right) = items.split_at_mut(a);
+        (&mut right[0], &mut left[b])
+    }
+}
+
+fn run_entity_systems(
+    systems: &mut [System],
+    archetypes: &mut [Archetype],
+    resources: &mut ResourceRegistry,
+    phase: &RunSystemPhase,
+    change_tick: &mut u32,
+) {
+    for system in systems.iter_mut() {
+        if !should_run(
+            &system.conditions,
+            &mut system.subscription,
+            phase,
+            resources,
+        ) {
+            continue;
+        }
+
+        *change_tick += 1;
+        let last_change_tick = system.last_run;
+        system.last_run = *change_tick;
+        resources.change_tick = *change_tick;
+        resources.last_change_tick = last_change_tick;
+
+        for archetype in archetypes.iter_mut() {
+            if archetype.is_empty() || !archetype.matches(&system.query) {
+                continue;
+            }
+
+            for row in 0..archetype.len() {
+                let mut entity = EntityMut {
+                    archetype,
+                    row,
+                    change_tick: *change_tick,
+                    last_change_tick,
+                };
+                (system.calls)(&mut entity, resources, phase);
+            }
+        }
+    }
+}
+
 impl ECS {
     pub fn new() -> Self {
-        Self {
+        let mut resources = ResourceRegistry::new();
+        resources.set(Commands::new());
+
+        let mut ecs = Self {
             entities: Arena::new(),
+            archetypes: Vec::new(),
+            archetype_index: HashMap::new(),
             systems: SystemRunner::new(),
-            resources: ResourceRegistry::new(),
+            hooks: ComponentHooks::new(),
+            event_updaters: Vec::new(),
+            state_updaters: Vec::new(),
+            state_services: HashMap::new(),
+            scene_types: SceneRegistry::new(),
+            types: TypeRegistry::new(),
+            cloners: HashMap::new(),
+            change_tick: 1,
+            last_change_tick: 0,
+            resources,
+        };
+        ecs.register_component::<Transform>("Transform");
+        ecs.register_type::<Transform>();
+        ecs.register_clone::<Transform>();
+        ecs.register_component::<Bounds>("Bounds");
+        ecs.register_type::<Bounds>();
+        ecs.register_clone::<Bounds>();
+        ecs
+    }
+
+    fn add_archetype(
+        &mut self,
+        shape: Vec<TypeId>,
+        columns: HashMap<TypeId, Box<dyn Column>>,
+    ) -> usize {
+        let mut archetype = Archetype::new(shape.clone());
+        archetype.columns = columns;
+
+        let index = self.archetypes.len();
+        self.archetypes.push(archetype);
+        self.archetype_index.insert(shape, index);
+        index
+    }
+
+    // Table for `shape`, created with empty copies of the `source` table columns plus `extra`
+    fn archetype_from(
+        &mut self,
+        shape: Vec<TypeId>,
+        source: usize,
+        extra: Option<(TypeId, Box<dyn Column>)>,
+    ) -> usize {
+        if let Some(index) = self.archetype_index.get(&shape) {
+            return *index;
+        }
+
+        let mut columns: HashMap<TypeId, Box<dyn Column>> = self.archetypes[source]
+            .columns
+            .iter()
+            .filter(|(type_id, _)| shape.contains(type_id))
+            .map(|(type_id, column)| (*type_id, column.new_empty()))
+            .collect();
+
+        if let Some((type_id, column)) = extra {
+            columns.insert(type_id, column);
+        }
+
+        self.add_archetype(shape, columns)
+    }
+
+    pub fn add_entity(&mut self, entity: Entity) -> EntityId {
+        let mut entity = entity;
+        let shape = sorted_shape(entity.components.keys());
+        let archetype_index = match self.archetype_index.get(&shape) {
+            Some(index) => *index,
+            None => {
+                let columns = entity
+                    .components
+                    .iter()
+                    .map(|(type_id, column)| (*type_id, column.new_empty()))
+                    .collect();
+                self.add_archetype(shape, columns)
+            }
+        };
+        let archetype = &mut self.archetypes[archetype_index];
+
+        let row = archetype.len();
+        for (type_id, column) in entity.components.iter_mut() {
+            let target = archetype.columns.get_mut(type_id).unwrap();
+            column.move_row(0, target.as_mut());
+            archetype.push_ticks(type_id, (self.change_tick, self.change_tick));
+        }
+
+        let id = EntityId(self.entities.insert(EntityLocation {
+            archetype: archetype_index,
+            row,
+        }));
+        archetype.entities.push(id);
+
+        self.run_hooks(HookKind::Add, id, archetype_index, row);
+        id
+    }
+
+    // Runs the `kind` hooks of every component of the entity at `row`
+    pub(crate) fn run_hooks(&mut self, kind: HookKind, id: EntityId, archetype: usize, row: usize) {
+        for (type_id, column) in self.archetypes[archetype].columns.iter_mut() {
+            self.hooks
+                .run(kind, type_id, id, column.row_mut(row), &mut self.resources);
+        }
+    }
+
+    pub fn despawn(&mut self, id: EntityId) -> bool {
+        self.detach(id);
+
+        let location = match self.entities.remove(id.0) {
+            Some(location) => location,
+            None => return false,
+        };
+
+        self.run_hooks(HookKind::Despawn, id, location.archetype, location.row);
+        self.run_hooks(HookKind::Remove, id, location.archetype, location.row);
+
+        let archetype = &mut self.archetypes[location.archetype];
+        if let Some(moved) = archetype.swap_remove(location.row) {
+            if let Some(moved_location) = self.entities.get_mut(moved.0) {
+                moved_location.row = location.row;
+            }
+        }
+
+        true
+    }
+
+    // Moves every component but `skip` from the entity's current table to the end of `target`
+    fn move_entity(&mut self, id: EntityId, target: usize, skip: Option<TypeId>) {
+        let location = *self.entities.get(id.0).unwrap();
+        let (source, destination) = pair_mut(&mut self.archetypes, location.archetype, target);
+
+        for (type_id, column) in source.columns.iter_mut() {
+            if Some(*type_id) == skip {
+                continue;
+            }
+            let target_column = destination.columns.get_mut(type_id).unwrap();
+            column.move_row(location.row, target_column.as_mut());
+
+            let added = source
+                .added
+                .get_mut(type_id)
+                .unwrap()
+                .get_mut()
+                .swap_remove(location.row);
+            let changed = source
+                .changed
+                .get_mut(type_id)
+                .unwrap()
+                .get_mut()
+                .swap_remove(location.row);
+            destination.push_ticks(type_id, (added, changed));
+        }
+
+        source.entities.swap_remove(location.row);
+        let moved = source.entities.get(location.row).cloned();
+
+        let row = destination.len();
+        destination.entities.push(id);
+
+        if let Some(moved) = moved {
+            if let Some(moved_location) = self.entities.get_mut(moved.0) {
+                moved_location.row = location.row;
+            }
+        }
+        if let Some(entity_location) = self.entities.get_mut(id.0) {
+            *entity_location = EntityLocation {
+                archetype: target,
+                row,
+            };
+        }
+    }
+
+    pub fn insert_component<T: 'static>(&mut self, id: EntityId, value: T) -> bool {
+        let location = match self.entities.get(id.0) {
+            Some(location) => *location,
+            None => return false,
+        };
+
+        let type_id = TypeId::of::<T>();
+        let archetype = &mut self.archetypes[location.archetype];
+
+        if let Some(column) = archetype.column_mut::<T>() {
+            let previous = &mut column[location.row];
+            self.hooks.run(
+                HookKind::Remove,
+                &type_id,
+                id,
+                previous,
+                &mut self.resources,
+            );
+
+            column[location.row] = value;
+            let value = &mut column[location.row];
+            self.hooks
+                .run(HookKind::Add, &type_id, id, value, &mut self.resources);
+
+            archetype.set_changed(&type_id, location.row, self.change_tick);
+            return true;
         }
+
+        let shape = sorted_shape(archetype.shape.iter().chain(std::iter::once(&type_id)));
+        let target = self.archetype_from(
+            shape,
+            location.archetype,
+            Some((type_id, new_column(Vec::<T>::new()))),
+        );
+
+        self.move_entity(id, target, None);
+
+        let archetype = &mut self.archetypes[target];
+        archetype.column_mut::<T>().unwrap().push(value);
+        archetype.push_ticks(&type_id, (self.change_tick, self.change_tick));
+
+        let row = archetype.len() - 1;
+        let value = &mut archetype.column_mut::<T>().unwrap()[row];
+        self.hooks
+            .run(HookKind::Add, &type_id, id, value, &mut self.resources);
+        true
+    }
+
+    pub fn remove_component<T: 'static>(&mut self, id: EntityId) -> Option<T> {
+        let location = *self.entities.get(id.0)?;
+
+        let type_id = TypeId::of::<T>();
+        let archetype = &mut self.archetypes[location.archetype];
+        if !archetype.has(&type_id) {
+            return None;
+        }
+
+        let mut value = archetype
+            .column_mut::<T>()
+            .unwrap()
+            .swap_remove(location.row);
+        archetype.swap_remove_ticks(&type_id, location.row);
+
+        let shape: Vec<TypeId> = archetype
+            .shape
+            .iter()
+            .filter(|other| **other != type_id)
+            .cloned()
+            .collect();
+        let target = self.archetype_from(shape, location.archetype, None);
+
+        self.move_entity(id, target, Some(type_id));
+
+        self.hooks.run(
+            HookKind::Remove,
+            &type_id,
+            id,
+            &mut value,
+            &mut self.resources,
+        );
+        Some(value)
+    }
+
+    // Runs once `T` is on an entity, when spawned or through `insert_component`
+    pub fn on_add<T, F>(&mut self, hook: F)
+    where
+        T: 'static,
+        F: FnMut(EntityId, &mut T, &mut ResourceRegistry) + 'static,
+    {
+        self.hooks.add(HookKind::Add, hook);
+    }
+
+    // Runs as `T` leaves an entity: removed, replaced by `insert_component` or despawned
+    pub fn on_remove<T, F>(&mut self, hook: F)
+    where
+        T: 'static,
+        F: FnMut(EntityId, &mut T, &mut ResourceRegistry) + 'static,
+    {
+        self.hooks.add(HookKind::Remove, hook);
+    }
+
+    // Runs for each component of a despawned entity, ahead of its `on_remove` hooks
+    pub fn on_despawn<T, F>(&mut self, hook: F)
+    where
+        T: 'static,
+        F: FnMut(EntityId, &mut T, &mut ResourceRegistry) + 'static,
+    {
+        self.hooks.add(HookKind::Despawn, hook);
+    }
+
+    // Runs for each component of an entity handed out by `take_entity`, which keeps its
+    // components alive and so runs no remove hooks. For state kept about the entity elsewhere
+    pub fn on_take<T, F>(&mut self, hook: F)
+    where
+        T: 'static,
+        F: FnMut(EntityId, &mut T, &mut ResourceRegistry) + 'static,
+    {
+        self.hooks.add(HookKind::Take, hook);
+    }
+
+    pub fn is_alive(&self, id: EntityId) -> bool {
+        self.entities.contains(id.0)
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<EntityRef<'_>> {
+        let location = self.entities.get(id.0)?;
+
+        Some(EntityRef {
+            archetype: &self.archetypes[location.archetype],
+            row: location.row,
+            types: &self.types,
+        })
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<EntityMut<'_>> {
+        let location = self.entities.get(id.0)?;
+
+        Some(EntityMut {
+            archetype: &mut self.archetypes[location.archetype],
+            row: location.row,
+            change_tick: self.change_tick,
+            last_change_tick: self.last_change_tick,
+        })
     }
 
-    pub fn add_entity(&mut self, entity: Entity) {
-        self.entities.insert(entity);
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
     }
 
     pub fn add_system(&mut self, system: System) {
+        if let Some(subscription) = &system.subscription {
+            subscription.register(self);
+        }
         self.systems.add(system);
     }
 
-    pub fn add_before_service(&mut self, system: Service) {
+    // Panics when the service asks for a resource that was not inserted yet
+    pub(crate) fn init_service(&mut self, service: &mut Service) {
+        if let Some(subscription) = &service.subscription {
+            subscription.register(self);
+        }
+        if let Err(error) = service.calls.init(&mut service.access, &self.resources) {
+            panic!("{}", error);
+        }
+    }
+
+    pub fn add_before_service(&mut self, mut system: Service) {
+        self.init_service(&mut system);
         self.systems.add_service(system, ServicePhase::Before);
     }
 
-    pub fn add_after_service(&mut self, system: Service) {
+    pub fn add_after_service(&mut self, mut system: Service) {
+        self.init_service(&mut system);
         self.systems.add_service(system, ServicePhase::After);
     }
 
+    pub fn archetypes(&self) -> &[Archetype] {
+        &self.archetypes
+    }
+
+    // Sorts the systems by their labels, `run_systems` does it too when systems were added since
+    pub fn build_schedule(&mut self) -> Result<(), ScheduleError> {
+        self.systems.sort()
+    }
+
     pub fn run_systems(&mut self, phase: RunSystemPhase) {
-        match phase.clone() {
-            RunSystemPhase::Update(type_id) => {
-                for service in self.systems.before_update.iter_mut() {
-                    if let Some(update_key) = service.update_key {
-                        if update_key == type_id {
-                            (service.calls)(&mut self.resources, &phase);
-                        }
-                    }
-                }
-                for (_, entity) in self.entities.iter_mut() {
-                    for system in self.systems.update.iter_mut() {
-                        if let Some(update_key) = system.update_key {
-                            if update_key == type_id {
-                                if entity.components.raw_contains_all(&system.query.0) {
-                                    (system.calls)(entity, &mut self.resources, &phase);
-                                }
-                            }
-                        }
-                    }
-                }
-                for service in self.systems.after_update.iter_mut() {
-                    if let Some(update_key) = service.update_key {
-                        if update_key == type_id {
-                            (service.calls)(&mut self.resources, &phase);
-                        }
-                    }
-                }
+        if self.systems.dirty {
+            if let Err(error) = self.systems.sort() {
+                panic!("{}", error);
             }
-            RunSystemPhase::Event(_event) => {
-                for system in self.systems.before_event.iter_mut() {
-                    (system.calls)(&mut self.resources, &phase);
-                }
-                for (_, entity) in self.entities.iter_mut() {
-                    for system in self.systems.event.iter_mut() {
-                        if entity.components.raw_contains_all(&system.query.0) {
-                            (system.calls)(entity, &mut self.resources, &phase);
-                        }
-                    }
-                }
-                for system in self.systems.after_event.iter_mut() {
-                    (system.calls)(&mut self.resources, &phase);
-                }
+        }
+
+        let (before, systems, after) = match phase {
+            RunSystemPhase::Update => (
+                &mut self.systems.before_update,
+                &mut self.systems.update,
+                &mut self.systems.after_update,
+            ),
+            RunSystemPhase::Event(_) => (
+                &mut self.systems.before_event,
+                &mut self.systems.event,
+                &mut self.systems.after_event,
+            ),
+            RunSystemPhase::Tick => (
+                &mut self.systems.before_tick,
+                &mut self.systems.tick,
+                &mut self.systems.after_tick,
+            ),
+            RunSystemPhase::Render => (
+                &mut self.systems.before_render,
+                &mut self.systems.render,
+                &mut self.systems.after_render,
+            ),
+        };
+
+        let context = SystemContext::new(&mut self.archetypes, &mut self.resources, &phase);
+        run_services(before, &context, &mut self.change_tick);
+
+        run_entity_systems(
+            systems,
+            &mut self.archetypes,
+            &mut self.resources,
+            &phase,
+            &mut self.change_tick,
+        );
+
+        let context = SystemContext::new(&mut self.archetypes, &mut self.resources, &phase);
+        run_services(after, &context, &mut self.change_tick);
+
+        // Back to the ticks of the ECS itself, for commands and code outside of systems
+        self.change_tick += 1;
+        self.resources.change_tick = self.change_tick;
+        self.resources.last_change_tick = self.last_change_tick;
+
+        self.apply_commands();
+    }
+
+    // Sync point, runs after every phase
+    pub fn apply_commands(&mut self) {
+        loop {
+            let queue = match self.resources.get_mut::<Commands>() {
+                Some(mut commands) if !commands.is_empty() => commands.take(),
+                _ => return,
+            };
+
+            for command in queue {
+                command(self);
             }
-            RunSystemPhase::Tick => {
-                for system in self.systems.before_tick.iter_mut() {
-                    (system.calls)(&mut self.resources, &phase);
-                }
-                for (_, entity) in self.entities.iter_mut() {
-                    for system in self.systems.tick.iter_mut() {
-                        if entity.components.raw_contains_all(&system.query.0) {
-                            (system.calls)(entity, &mut self.resources, &phase);
-                        }
-                    }
-                }
-                for system in self.systems.after_tick.iter_mut() {
-                    (system.calls)(&mut self.resources, &phase);
-                }
+        }
+    }
+
+    // Components mutated after this call are reported by `Changed<T>` in queries made on the ECS
+    // directly, usually called once per frame. Systems track their own last run instead
+    pub fn clear_trackers(&mut self) {
+        self.last_change_tick = self.change_tick;
+        self.change_tick += 1;
+        self.resources.change_tick = self.change_tick;
+        self.resources.last_change_tick = self.last_change_tick;
+    }
+
+    // Inserts the `Events<T>` resource once, its buffers are swapped by `update_events`
+    pub fn add_event<T: Send + Sync + 'static>(&mut self) {
+        if self.resources.contains::<Events<T>>() {
+            return;
+        }
+        self.resources.set(Events::<T>::new());
+        self.event_updaters.push(update_events::<T>);
+    }
+
+    // Ends the lifetime of the events sent two calls ago, usually called once per frame
+    pub fn update_events(&mut self) {
+        for update in &self.event_updaters {
+            update(&self.resources);
+        }
+    }
+
+    pub fn query<Q: WorldQuery>(&mut self) -> QueryIter<'_, Q, ()> {
+        self.query_filtered::<Q, ()>()
+    }
+
+    pub fn query_filtered<Q: WorldQuery, F: QueryFilter>(&mut self) -> QueryIter<'_, Q, F> {
+        QueryIter::new(
+            &mut self.archetypes,
+            self.change_tick,
+            self.last_change_tick,
+        )
+    }
+
+    pub fn query_exact<F>(&mut self, shape: &EntityShape, mut calls: F)
+    where
+        F: FnMut(&mut EntityMut),
+    {
+        let (change_tick, last_change_tick) = (self.change_tick, self.last_change_tick);
+
+        for archetype in self.archetypes.iter_mut() {
+            if !archetype.matches(shape) {
+                continue;
             }
-            RunSystemPhase::Render => {
-                for system in self.systems.before_render.iter_mut() {
-                    (system.calls)(&mut self.resources, &phase);
-                }
-                for (_, entity) in self.entities.iter_mut() {
-                    for system in self.systems.render.iter_mut() {
-                        if entity.components.raw_contains_all(&system.query.0) {
-                            (system.calls)(entity, &mut self.resources, &phase);
-                        }
-                    }
-                }
-                for system in self.systems.after_render.iter_mut() {
-                    (system.calls)(&mut self.resources, &phase);
-                }
+
+            for row in 0..archetype.len() {
+                calls(&mut EntityMut {
+                    archetype,
+                    row,
+                    change_tick,
+                    last_change_tick,
+                });
             }
         }
     }
+}
 
-    pub fn query_exact(&mut self, shape: &EntityShape) -> Vec<&mut Entity> {
-        let mut matching: Vec<&mut Entity> = Vec::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Query, ResMut};
 
-        for (_, entity) in self.entities.iter_mut() {
-            if entity.components.raw_contains_all(&shape.0) {
-                matching.push(entity);
-            }
+    struct A(u64);
+    struct B(u64);
+    struct C;
+    struct Total(u64);
+
+    fn double_a(mut query: Query<&mut A>) {
+        for mut a in query.iter() {
+            a.0 *= 2;
+        }
+    }
+
+    fn double_b(mut query: Query<&mut B>) {
+        for mut b in query.iter() {
+            b.0 *= 2;
+        }
+    }
+
+    fn increment_a(mut query: Query<&mut A>) {
+        for mut a in query.iter() {
+            a.0 += 1;
+        }
+    }
+
+    fn sum(mut query: Query<(&A, &B)>, mut total: ResMut<Total>) {
+        total.0 = query.iter().map(|(a, b)| a.0 + b.0).sum();
+    }
+
+    #[test]
+    fn services_run_in_batches_of_compatible_access() {
+        let mut ecs = ECS::new();
+        ecs.resources.set(Total(0));
+        for i in 0..1000 {
+            ecs.add_entity(Entity::new().with(A(i)).with(B(i)));
+        }
+
+        // `double_a` and `double_b` share a batch, the others each wait for the previous one
+        ecs.add_before_service(Service::at_tick(increment_a));
+        ecs.add_before_service(Service::at_tick(double_a));
+        ecs.add_before_service(Service::at_tick(double_b));
+        ecs.add_before_service(Service::at_tick(sum));
+        ecs.run_systems(RunSystemPhase::Tick);
+
+        let expected: u64 = (0..1000).map(|i| (i + 1) * 2 + i * 2).sum();
+        assert_eq!(ecs.resources.get::<Total>().unwrap().0, expected);
+    }
+
+    #[test]
+    fn batching_follows_access_and_order() {
+        let mut resources = ResourceRegistry::new();
+        resources.set(Total(0));
+        let mut services = [
+            Service::at_tick(double_a),
+            Service::at_tick(double_b).label("double_b"),
+            Service::at_tick(increment_a),
+            Service::at_tick(sum),
+            Service::at_tick(double_a).after("double_b"),
+        ];
+        for service in services.iter_mut() {
+            service.calls.init(&mut service.access, &resources).unwrap();
+        }
+
+        let (first, rest) = services.split_first_mut().unwrap();
+        let batch = [first];
+        assert!(can_batch(&batch, &rest[0]));
+        assert!(!can_batch(&batch, &rest[1]));
+        assert!(!can_batch(&batch, &rest[2]));
+
+        let (last, rest) = rest.split_last_mut().unwrap();
+        assert!(last.access.is_compatible(&rest[0].access));
+        assert!(!can_batch(&[last], &rest[0]));
+    }
+
+    fn location(ecs: &ECS, id: EntityId) -> EntityLocation {
+        *ecs.entities.get(id.0).unwrap()
+    }
+
+    fn a_of(ecs: &ECS, id: EntityId) -> Option<u64> {
+        ecs.get(id)
+            .and_then(|entity| entity.get::<A>().map(|a| a.0))
+    }
+
+    #[test]
+    fn entities_of_one_shape_share_a_table() {
+        let mut ecs = ECS::new();
+        let first = ecs.add_entity(Entity::new().with(A(1)).with(B(1)));
+        let second = ecs.add_entity(Entity::new().with(B(2)).with(A(2)));
+        ecs.add_entity(Entity::new().with(A(3)));
+
+        let first_location = location(&ecs, first);
+        assert_eq!(location(&ecs, second).archetype, first_location.archetype);
+        assert_eq!(ecs.archetypes[first_location.archetype].len(), 2);
+        assert_eq!(ecs.archetypes.len(), 2);
+    }
+
+    #[test]
+    fn inserting_and_removing_moves_between_tables() {
+        let mut ecs = ECS::new();
+        let ids: Vec<EntityId> = (0..3)
+            .map(|i| ecs.add_entity(Entity::new().with(A(i))))
+            .collect();
+
+        // The last entity fills the row left by the first one
+        assert!(ecs.insert_component(ids[0], B(10)));
+        let entity = ecs.get(ids[0]).unwrap();
+        assert_eq!(entity.get::<B>().unwrap().0, 10);
+        assert_eq!(a_of(&ecs, ids[0]), Some(0));
+        assert_eq!(location(&ecs, ids[2]).row, 0);
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(a_of(&ecs, *id), Some(i as u64));
+        }
+
+        assert_eq!(ecs.remove_component::<B>(ids[0]).map(|b| b.0), Some(10));
+        assert!(!ecs.get(ids[0]).unwrap().has::<B>());
+        assert!(ecs.remove_component::<B>(ids[0]).is_none());
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(a_of(&ecs, *id), Some(i as u64));
         }
+    }
+
+    #[test]
+    fn inserting_an_existing_component_replaces_it_in_place() {
+        let mut ecs = ECS::new();
+        let id = ecs.add_entity(Entity::new().with(A(1)).with(C));
+        let before = location(&ecs, id);
+
+        assert!(ecs.insert_component(id, A(2)));
+        assert_eq!(location(&ecs, id), before);
+        assert_eq!(a_of(&ecs, id), Some(2));
+    }
+
+    #[test]
+    fn despawn_fills_the_gap_and_kills_the_id() {
+        let mut ecs = ECS::new();
+        let ids: Vec<EntityId> = (0..3)
+            .map(|i| ecs.add_entity(Entity::new().with(A(i))))
+            .collect();
+
+        assert!(ecs.despawn(ids[0]));
+        assert!(!ecs.despawn(ids[0]));
+        assert!(!ecs.is_alive(ids[0]));
+        assert!(ecs.get(ids[0]).is_none());
+        assert!(!ecs.insert_component(ids[0], B(0)));
+        assert_eq!(ecs.len(), 2);
+
+        assert_eq!(location(&ecs, ids[2]).row, 0);
+        assert_eq!(a_of(&ecs, ids[1]), Some(1));
+        assert_eq!(a_of(&ecs, ids[2]), Some(2));
+    }
+
+    #[test]
+    fn reused_slots_get_new_ids() {
+        let mut ecs = ECS::new();
+        let old = ecs.add_entity(Entity::new().with(A(1)));
+        ecs.despawn(old);
+
+        let new = ecs.add_entity(Entity::new().with(A(2)));
+        assert_ne!(old, new);
+        assert!(!ecs.is_alive(old));
+        assert_eq!(a_of(&ecs, new), Some(2));
+    }
+
+    #[test]
+    fn many_components_borrow_mutably_at_once() {
+        let mut ecs = ECS::new();
+        let id = ecs.add_entity(Entity::new().with(A(1)).with(B(2)));
+
+        let mut entity = ecs.get_mut(id).unwrap();
+        let (a, b) = entity.get_many_mut::<(A, B)>().unwrap();
+        std::mem::swap(&mut a.0, &mut b.0);
+        assert!(entity.get_many_mut::<(A, C)>().is_none());
+
+        assert_eq!(a_of(&ecs, id), Some(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "borrowed mutably more than once")]
+    fn one_component_borrows_mutably_once() {
+        let mut ecs = ECS::new();
+        let id = ecs.add_entity(Entity::new().with(A(1)));
 
-        matching
+        ecs.get_mut(id).unwrap().get_many_mut::<(A, A)>();
     }
 }
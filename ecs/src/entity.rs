@@ -1,11 +1,20 @@
-use anymap::AnyMap;
+use super::archetype::{new_column, Column, ColumnCell, EntityLocation};
+use generational_arena::Index;
 use std::any::TypeId;
-use std::collections::HashSet;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntityId(pub(crate) Index<EntityLocation>);
 
-#[derive(Debug)]
 pub struct Entity {
-    pub(crate) components: AnyMap,
-    pub(crate) shape: HashSet<TypeId>,
+    pub(crate) components: HashMap<TypeId, Box<dyn Column>>,
+}
+
+impl fmt::Debug for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Entity: {} components>", self.components.len())
+    }
 }
 
 impl Default for Entity {
@@ -17,18 +26,17 @@ impl Default for Entity {
 impl Entity {
     pub fn new() -> Self {
         Self {
-            components: AnyMap::new(),
-            shape: HashSet::new(),
+            components: HashMap::new(),
         }
     }
 
     pub fn deregister_component<T: 'static>(&mut self) {
-        self.components.remove::<T>();
-        self.shape.remove(&TypeId::of::<T>());
+        self.components.remove(&TypeId::of::<T>());
     }
 
     fn register_component<T: 'static>(&mut self, value: T) {
-        self.components.insert::<T>(value);
+        self.components
+            .insert(TypeId::of::<T>(), new_column(vec![value]));
     }
 
     pub fn remove<T: 'static>(&mut self) {
@@ -36,24 +44,29 @@ impl Entity {
     }
 
     pub fn with<T: 'static>(mut self, value: T) -> Self {
-        self.shape.insert(TypeId::of::<T>());
         self.register_component(value);
         self
     }
 
     pub fn set<T: 'static>(&mut self, value: T) {
-        self.components.insert::<T>(value);
+        self.register_component(value);
     }
 
     pub fn get<T: 'static>(&self) -> Option<&T> {
-        self.components.get::<T>()
+        self.components
+            .get(&TypeId::of::<T>())
+            .and_then(|column| column.as_any().downcast_ref::<ColumnCell<T>>())
+            .and_then(|column| unsafe { (*column.get()).first() })
     }
 
-    pub fn get_mut<T: 'static>(&mut self) -> Option<&'static mut T> {
-        unsafe { std::mem::transmute(self.components.get_mut::<T>()) }
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.components
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|column| column.as_any_mut().downcast_mut::<ColumnCell<T>>())
+            .and_then(|column| column.get_mut().first_mut())
     }
 
     pub fn has<A: 'static>(&self) -> bool {
-        self.components.contains::<A>()
+        self.components.contains_key(&TypeId::of::<A>())
     }
 }
@@ -0,0 +1,214 @@
+use super::{Res, ResMut, ResourceRegistry, ECS};
+use std::mem;
+
+// Queue of events of one type, kept as a resource. Buffers are swapped by `ECS::update_events`,
+// so every event stays readable for two updates whichever side of its sender a reader runs on
+pub struct Events<T> {
+    previous: Vec<T>,
+    current: Vec<T>,
+    // Ids of the first event in `previous` and of the next one sent
+    start: usize,
+    count: usize,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Events<T> {
+    pub fn new() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            start: 0,
+            count: 0,
+        }
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+        self.count += 1;
+    }
+
+    // Drops the events of the update before the last one
+    pub fn update(&mut self) {
+        self.start += self.previous.len();
+        mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+    }
+
+    // Events sent since `cursor` that are still buffered, moving `cursor` past all of them
+    pub fn read<'a>(&'a self, cursor: &mut usize) -> impl Iterator<Item = &'a T> {
+        let skip = cursor.saturating_sub(self.start);
+        *cursor = self.count;
+        self.previous.iter().chain(self.current.iter()).skip(skip)
+    }
+
+    // Number of events ever sent, usable as the cursor of a reader that skips the buffered ones
+    pub fn event_count(&self) -> usize {
+        self.count
+    }
+
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+pub struct EventWriter<'a, T: 'static> {
+    pub(crate) events: ResMut<'a, Events<T>>,
+}
+
+impl<'a, T: 'static> EventWriter<'a, T> {
+    pub fn send(&mut self, event: T) {
+        self.events.send(event);
+    }
+}
+
+// Reads the events its service did not see yet, each service keeps its own cursor
+pub struct EventReader<'a, T: 'static> {
+    pub(crate) events: Res<'a, Events<T>>,
+    pub(crate) cursor: &'a mut usize,
+}
+
+impl<'a, T: 'static> EventReader<'a, T> {
+    pub fn iter(&mut self) -> impl Iterator<Item = &T> {
+        let events: &Events<T> = &self.events;
+        events.read(self.cursor)
+    }
+}
+
+pub(crate) fn update_events<T: 'static>(resources: &ResourceRegistry) {
+    if let Some(mut events) = resources.get_mut::<Events<T>>() {
+        events.update();
+    }
+}
+
+// Event type a `Service::at_update` or `System::at_update` waits for. It runs in the update phase
+// only when events of that type were sent since its last run
+pub(crate) struct Subscription {
+    register: fn(&mut ECS),
+    event_count: fn(&ResourceRegistry) -> usize,
+    cursor: usize,
+}
+
+impl Subscription {
+    pub(crate) fn new<T: Send + Sync + 'static>() -> Self {
+        Self {
+            register: ECS::add_event::<T>,
+            event_count: |resources| {
+                resources
+                    .get::<Events<T>>()
+                    .map(|events| events.event_count())
+                    .unwrap_or(0)
+            },
+            cursor: 0,
+        }
+    }
+
+    pub(crate) fn register(&self, ecs: &mut ECS) {
+        (self.register)(ecs)
+    }
+
+    pub(crate) fn poll(&mut self, resources: &ResourceRegistry) -> bool {
+        let count = (self.event_count)(resources);
+        let pending = count > self.cursor;
+        self.cursor = count;
+        pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RunSystemPhase, Service};
+
+    struct Hit(u32);
+    struct Seen(Vec<u32>);
+
+    fn read(events: &Events<u32>, cursor: &mut usize) -> Vec<u32> {
+        events.read(cursor).cloned().collect()
+    }
+
+    #[test]
+    fn events_live_for_two_updates() {
+        let mut events = Events::new();
+        events.send(1);
+        events.send(2);
+        events.update();
+        events.send(3);
+        assert_eq!(read(&events, &mut 0), [1, 2, 3]);
+
+        events.update();
+        assert_eq!(read(&events, &mut 0), [3]);
+        events.update();
+        assert!(events.is_empty());
+        assert_eq!(events.event_count(), 3);
+    }
+
+    #[test]
+    fn cursors_only_see_new_events() {
+        let mut events = Events::new();
+        let (mut early, mut late) = (0, 0);
+        events.send(1);
+        assert_eq!(read(&events, &mut early), [1]);
+        assert!(read(&events, &mut early).is_empty());
+
+        events.update();
+        events.send(2);
+        events.update();
+        events.send(3);
+        assert_eq!(read(&events, &mut early), [2, 3]);
+        // Events dropped before a reader got to them are skipped
+        assert_eq!(read(&events, &mut late), [2, 3]);
+    }
+
+    fn send_hits(mut hits: EventWriter<Hit>) {
+        hits.send(Hit(1));
+        hits.send(Hit(2));
+    }
+
+    fn record_hits(mut hits: EventReader<Hit>, mut seen: ResMut<Seen>) {
+        seen.0.extend(hits.iter().map(|hit| hit.0));
+    }
+
+    #[test]
+    fn readers_see_each_event_once() {
+        let mut ecs = ECS::new();
+        ecs.add_event::<Hit>();
+        ecs.resources.set(Seen(Vec::new()));
+        ecs.add_before_service(Service::at_tick(send_hits).label("send"));
+        ecs.add_after_service(Service::at_tick(record_hits).after("send"));
+
+        ecs.run_systems(RunSystemPhase::Tick);
+        ecs.update_events();
+        ecs.run_systems(RunSystemPhase::Render);
+        ecs.update_events();
+        ecs.run_systems(RunSystemPhase::Tick);
+
+        assert_eq!(ecs.resources.get::<Seen>().unwrap().0, [1, 2, 1, 2]);
+    }
+
+    #[test]
+    fn subscribers_run_when_events_were_sent() {
+        let mut ecs = ECS::new();
+        ecs.resources.set(Seen(Vec::new()));
+        let mut runs = 0;
+        ecs.add_before_service(Service::at_update(Hit(0), move |mut seen: ResMut<Seen>| {
+            runs += 1;
+            seen.0.push(runs);
+        }));
+
+        ecs.run_systems(RunSystemPhase::Update);
+        ecs.resources.get_mut::<Events<Hit>>().unwrap().send(Hit(7));
+        ecs.run_systems(RunSystemPhase::Update);
+        ecs.run_systems(RunSystemPhase::Update);
+
+        assert_eq!(ecs.resources.get::<Seen>().unwrap().0, [1]);
+    }
+}
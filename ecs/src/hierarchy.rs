@@ -0,0 +1,307 @@
+use super::{EntityId, Without, ECS};
+use math::{Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+use std::ops::Deref;
+
+// Entity this one is attached to, kept in sync with the parent's `Children` by the ECS
+pub struct Parent(pub(crate) EntityId);
+
+impl Parent {
+    pub fn get(&self) -> EntityId {
+        self.0
+    }
+}
+
+pub struct Children(pub(crate) Vec<EntityId>);
+
+impl Deref for Children {
+    type Target = [EntityId];
+
+    fn deref(&self) -> &[EntityId] {
+        &self.0
+    }
+}
+
+// Position, rotation and scale relative to the parent, or to the world for root entities
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Transform {
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transform {
+    pub fn new() -> Self {
+        Self {
+            position: Vec3::new(),
+            rotation: Quat::new(),
+            scale: Vec3::from_scalar(1.0),
+        }
+    }
+
+    pub fn from_position(position: Vec3) -> Self {
+        Self {
+            position,
+            ..Self::new()
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        let mut matrix = Mat4::new();
+        matrix.compose(&self.position, &self.rotation, &self.scale);
+        matrix
+    }
+}
+
+// World space matrix of an entity with a `Transform`, written by `ECS::propagate_transforms`
+pub struct GlobalTransform(pub Mat4);
+
+impl GlobalTransform {
+    pub fn matrix(&self) -> &Mat4 {
+        &self.0
+    }
+}
+
+impl ECS {
+    // Attaches `child` to `parent`, detaching it from its previous parent first. Fails when
+    // either entity is dead or when `parent` is `child` itself or one of its descendants
+    pub fn set_parent(&mut self, child: EntityId, parent: EntityId) -> bool {
+        if !self.is_alive(child) || !self.is_alive(parent) {
+            return false;
+        }
+
+        let mut ancestor = Some(parent);
+        while let Some(current) = ancestor {
+            if current == child {
+                return false;
+            }
+            ancestor = self
+                .get(current)
+                .and_then(|entity| entity.get::<Parent>())
+                .map(Parent::get);
+        }
+
+        self.remove_parent(child);
+        self.insert_component(child, Parent(parent));
+
+        let mut parent_entity = self.get_mut(parent).unwrap();
+        if let Some(children) = parent_entity.get_mut::<Children>() {
+            children.0.push(child);
+            return true;
+        }
+        self.insert_component(parent, Children(vec![child]));
+        true
+    }
+
+    // Turns `child` back into a root entity, returning its former parent
+    pub fn remove_parent(&mut self, child: EntityId) -> Option<EntityId> {
+        let parent = self.remove_component::<Parent>(child)?.0;
+
+        if let Some(mut parent_entity) = self.get_mut(parent) {
+            if let Some(children) = parent_entity.get_mut::<Children>() {
+                children.0.retain(|other| *other != child);
+            }
+        }
+        Some(parent)
+    }
+
+    // Keeps the hierarchy consistent while `id` is despawned, its children become roots
+    pub(crate) fn detach(&mut self, id: EntityId) {
+        self.remove_parent(id);
+
+        for child in self.children_of(id) {
+            self.remove_component::<Parent>(child);
+        }
+    }
+
+    // Despawns `id` along with all of its descendants
+    pub fn despawn_recursive(&mut self, id: EntityId) -> bool {
+        for child in self.children_of(id) {
+            self.despawn_recursive(child);
+        }
+        self.despawn(id)
+    }
+
+    // Writes the `GlobalTransform` of every entity with a `Transform`, parents first. Entities
+    // without a `Transform` hand their parent's matrix down as is
+    pub fn propagate_transforms(&mut self) {
+        let roots: Vec<EntityId> = self
+            .query_filtered::<(EntityId, Option<&Transform>, Option<&Children>), Without<Parent>>()
+            .filter(|(_, transform, children)| transform.is_some() || children.is_some())
+            .map(|(id, _, _)| id)
+            .collect();
+
+        for root in roots {
+            self.propagate(root, &Mat4::new());
+        }
+    }
+
+    fn propagate(&mut self, id: EntityId, parent: &Mat4) {
+        let mut entity = match self.get_mut(id) {
+            Some(entity) => entity,
+            None => return,
+        };
+
+        let global = match entity.get::<Transform>() {
+            Some(transform) => {
+                let mut global = Mat4::new();
+                global.multiply_matrices(parent, &transform.matrix());
+                global
+            }
+            None => Mat4 {
+                elements: parent.elements,
+            },
+        };
+
+        // Only written when it moved, so `Changed<GlobalTransform>` stays meaningful
+        let current = entity
+            .get::<GlobalTransform>()
+            .map(|current| current.0.elements);
+        let has_transform = entity.has::<Transform>();
+        match current {
+            Some(elements) if elements == global.elements => (),
+            Some(_) => entity.get_mut::<GlobalTransform>().unwrap().0.elements = global.elements,
+            None if has_transform => {
+                let elements = global.elements;
+                self.insert_component(id, GlobalTransform(Mat4 { elements }));
+            }
+            None => (),
+        }
+
+        for child in self.children_of(id) {
+            self.propagate(child, &global);
+        }
+    }
+
+    fn children_of(&self, id: EntityId) -> Vec<EntityId> {
+        self.get(id)
+            .and_then(|entity| entity.get::<Children>())
+            .map(|children| children.0.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Changed, Entity};
+
+    fn spawn(ecs: &mut ECS, x: f32, y: f32) -> EntityId {
+        let position = Vec3::from_components(x, y, 0.0);
+        ecs.add_entity(Entity::new().with(Transform::from_position(position)))
+    }
+
+    fn parent_of(ecs: &ECS, id: EntityId) -> Option<EntityId> {
+        ecs.get(id)?.get::<Parent>().map(Parent::get)
+    }
+
+    fn global_position(ecs: &ECS, id: EntityId) -> [f32; 3] {
+        let global = ecs.get(id).unwrap().get::<GlobalTransform>().unwrap();
+        let elements = &global.0.elements;
+        [elements[12], elements[13], elements[14]]
+    }
+
+    #[test]
+    fn parents_and_children_stay_in_sync() {
+        let mut ecs = ECS::new();
+        let first = ecs.add_entity(Entity::new());
+        let second = ecs.add_entity(Entity::new());
+        let child = ecs.add_entity(Entity::new());
+
+        assert!(ecs.set_parent(child, first));
+        assert_eq!(parent_of(&ecs, child), Some(first));
+        assert_eq!(ecs.children_of(first), [child]);
+
+        assert!(ecs.set_parent(child, second));
+        assert_eq!(parent_of(&ecs, child), Some(second));
+        assert!(ecs.children_of(first).is_empty());
+        assert_eq!(ecs.children_of(second), [child]);
+
+        assert_eq!(ecs.remove_parent(child), Some(second));
+        assert_eq!(ecs.remove_parent(child), None);
+        assert!(ecs.children_of(second).is_empty());
+    }
+
+    #[test]
+    fn cycles_and_dead_entities_are_refused() {
+        let mut ecs = ECS::new();
+        let root = ecs.add_entity(Entity::new());
+        let child = ecs.add_entity(Entity::new());
+        let grandchild = ecs.add_entity(Entity::new());
+        ecs.set_parent(child, root);
+        ecs.set_parent(grandchild, child);
+
+        assert!(!ecs.set_parent(root, root));
+        assert!(!ecs.set_parent(root, grandchild));
+        assert!(!ecs.set_parent(child, grandchild));
+        assert_eq!(parent_of(&ecs, root), None);
+        assert_eq!(parent_of(&ecs, child), Some(root));
+
+        let dead = ecs.add_entity(Entity::new());
+        ecs.despawn(dead);
+        assert!(!ecs.set_parent(dead, root));
+        assert!(!ecs.set_parent(root, dead));
+    }
+
+    #[test]
+    fn despawning_detaches_from_the_hierarchy() {
+        let mut ecs = ECS::new();
+        let root = ecs.add_entity(Entity::new());
+        let child = ecs.add_entity(Entity::new());
+        let grandchild = ecs.add_entity(Entity::new());
+        let sibling = ecs.add_entity(Entity::new());
+        ecs.set_parent(child, root);
+        ecs.set_parent(sibling, root);
+        ecs.set_parent(grandchild, child);
+
+        ecs.despawn(child);
+        assert_eq!(ecs.children_of(root), [sibling]);
+        assert_eq!(parent_of(&ecs, grandchild), None);
+
+        ecs.set_parent(grandchild, sibling);
+        assert!(ecs.despawn_recursive(root));
+        assert!(ecs.is_empty());
+    }
+
+    #[test]
+    fn global_transforms_compose_down_the_hierarchy() {
+        let mut ecs = ECS::new();
+        let root = spawn(&mut ecs, 1.0, 0.0);
+        let child = spawn(&mut ecs, 0.0, 2.0);
+        let grandchild = spawn(&mut ecs, 3.0, 0.0);
+        ecs.get_mut(root)
+            .unwrap()
+            .get_mut::<Transform>()
+            .unwrap()
+            .scale = Vec3::from_scalar(2.0);
+        ecs.set_parent(child, root);
+        ecs.set_parent(grandchild, child);
+
+        ecs.propagate_transforms();
+        assert_eq!(global_position(&ecs, root), [1.0, 0.0, 0.0]);
+        assert_eq!(global_position(&ecs, child), [1.0, 4.0, 0.0]);
+        assert_eq!(global_position(&ecs, grandchild), [7.0, 4.0, 0.0]);
+
+        ecs.clear_trackers();
+        ecs.get_mut(child)
+            .unwrap()
+            .get_mut::<Transform>()
+            .unwrap()
+            .position
+            .y = 1.0;
+        ecs.propagate_transforms();
+        assert_eq!(global_position(&ecs, grandchild), [7.0, 2.0, 0.0]);
+
+        let mut moved: Vec<EntityId> = ecs
+            .query_filtered::<EntityId, Changed<GlobalTransform>>()
+            .collect();
+        moved.sort_by_key(|id| *id != child);
+        assert_eq!(moved, [child, grandchild]);
+    }
+}
@@ -0,0 +1,144 @@
+use super::{EntityId, ResourceRegistry};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+type Hook = Box<dyn FnMut(EntityId, &mut dyn Any, &mut ResourceRegistry)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HookKind {
+    Add,
+    Remove,
+    Despawn,
+    Take,
+}
+
+#[derive(Default)]
+struct Hooks {
+    on_add: Vec<Hook>,
+    on_remove: Vec<Hook>,
+    on_despawn: Vec<Hook>,
+    on_take: Vec<Hook>,
+}
+
+impl Hooks {
+    fn of_kind(&mut self, kind: HookKind) -> &mut Vec<Hook> {
+        match kind {
+            HookKind::Add => &mut self.on_add,
+            HookKind::Remove => &mut self.on_remove,
+            HookKind::Despawn => &mut self.on_despawn,
+            HookKind::Take => &mut self.on_take,
+        }
+    }
+}
+
+// Callbacks registered per component type, ran by the ECS as components come and go
+#[derive(Default)]
+pub(crate) struct ComponentHooks {
+    hooks: HashMap<TypeId, Hooks>,
+}
+
+impl ComponentHooks {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn add<T, F>(&mut self, kind: HookKind, mut hook: F)
+    where
+        T: 'static,
+        F: FnMut(EntityId, &mut T, &mut ResourceRegistry) + 'static,
+    {
+        let hook: Hook = Box::new(move |id, value, resources| {
+            hook(id, value.downcast_mut::<T>().unwrap(), resources)
+        });
+
+        self.hooks
+            .entry(TypeId::of::<T>())
+            .or_default()
+            .of_kind(kind)
+            .push(hook);
+    }
+
+    pub(crate) fn run(
+        &mut self,
+        kind: HookKind,
+        type_id: &TypeId,
+        id: EntityId,
+        value: &mut dyn Any,
+        resources: &mut ResourceRegistry,
+    ) {
+        if let Some(hooks) = self.hooks.get_mut(type_id) {
+            for hook in hooks.of_kind(kind).iter_mut() {
+                hook(id, value, resources);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResourceRegistry;
+    use crate::{Entity, ECS};
+
+    struct A(u32);
+    struct B;
+    struct Log(Vec<String>);
+
+    fn world() -> ECS {
+        let mut ecs = ECS::new();
+        ecs.resources.set(Log(Vec::new()));
+        ecs.on_add(|_, a: &mut A, resources| log(resources, format!("add {}", a.0)));
+        ecs.on_remove(|_, a: &mut A, resources| log(resources, format!("remove {}", a.0)));
+        ecs.on_despawn(|_, a: &mut A, resources| log(resources, format!("despawn {}", a.0)));
+        ecs.on_take(|_, a: &mut A, resources| log(resources, format!("take {}", a.0)));
+        ecs
+    }
+
+    fn log(resources: &mut ResourceRegistry, line: String) {
+        resources.get_mut::<Log>().unwrap().0.push(line);
+    }
+
+    fn take_log(ecs: &mut ECS) -> Vec<String> {
+        std::mem::take(&mut ecs.resources.get_mut::<Log>().unwrap().0)
+    }
+
+    #[test]
+    fn hooks_follow_insertions_and_removals() {
+        let mut ecs = world();
+        let id = ecs.add_entity(Entity::new().with(B));
+        ecs.insert_component(id, A(1));
+        ecs.insert_component(id, A(2));
+        ecs.remove_component::<A>(id);
+        ecs.remove_component::<B>(id);
+        assert_eq!(
+            take_log(&mut ecs),
+            ["add 1", "remove 1", "add 2", "remove 2"]
+        );
+
+        ecs.add_entity(Entity::new().with(A(3)));
+        assert_eq!(take_log(&mut ecs), ["add 3"]);
+    }
+
+    #[test]
+    fn despawn_runs_despawn_hooks_then_remove_hooks() {
+        let mut ecs = world();
+        let id = ecs.add_entity(Entity::new().with(A(1)));
+        take_log(&mut ecs);
+
+        ecs.despawn(id);
+        ecs.despawn(id);
+        assert_eq!(take_log(&mut ecs), ["despawn 1", "remove 1"]);
+    }
+
+    #[test]
+    fn taken_entities_only_run_take_hooks() {
+        let mut ecs = world();
+        let id = ecs.add_entity(Entity::new().with(A(1)));
+        take_log(&mut ecs);
+
+        let entity = ecs.take_entity(id).unwrap();
+        assert_eq!(take_log(&mut ecs), ["take 1"]);
+
+        ecs.add_entity(entity);
+        assert_eq!(take_log(&mut ecs), ["add 1"]);
+    }
+}
@@ -1,13 +1,39 @@
 extern crate generational_arena;
 
+mod app;
+mod archetype;
+mod commands;
 mod ecs;
 mod entity;
+mod events;
+mod hierarchy;
+mod hooks;
+mod query;
+mod reflect;
 mod resource_registry;
+mod scene;
+mod schedule;
 mod shape;
+mod spatial;
+mod state;
 mod system;
+mod system_param;
+mod worlds;
 
 pub use crate::ecs::*;
+pub use app::*;
+pub use archetype::{Archetype, ComponentSet, EntityLocation, EntityMut, EntityRef};
+pub use commands::*;
 pub use entity::*;
+pub use events::*;
+pub use hierarchy::*;
+pub use query::*;
+pub use reflect::*;
 pub use resource_registry::*;
+pub use scene::*;
+pub use schedule::{ScheduleError, SystemOrder};
 pub use shape::*;
+pub use spatial::*;
+pub use state::*;
 pub use system::*;
+pub use system_param::*;
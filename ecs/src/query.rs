@@ -0,0 +1,701 @@
+use super::archetype::Archetype;
+use super::entity::EntityId;
+use std::any::{type_name, TypeId};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+// Component types read and written by a query
+#[derive(Debug, Default)]
+pub struct Access {
+    reads: Vec<TypeId>,
+    writes: Vec<TypeId>,
+}
+
+impl Access {
+    pub fn new() -> Self {
+        Self {
+            reads: Vec::new(),
+            writes: Vec::new(),
+        }
+    }
+
+    // Errors with the name of `T` when it is already written
+    pub fn read<T: 'static>(&mut self) -> Result<(), &'static str> {
+        let type_id = TypeId::of::<T>();
+        if self.writes.contains(&type_id) {
+            return Err(type_name::<T>());
+        }
+        self.reads.push(type_id);
+        Ok(())
+    }
+
+    // Errors with the name of `T` when it is already read or written
+    pub fn write<T: 'static>(&mut self) -> Result<(), &'static str> {
+        let type_id = TypeId::of::<T>();
+        if self.writes.contains(&type_id) || self.reads.contains(&type_id) {
+            return Err(type_name::<T>());
+        }
+        self.writes.push(type_id);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reads.is_empty() && self.writes.is_empty()
+    }
+
+    // Whether both can be held at once, only reads overlap
+    pub fn is_compatible(&self, other: &Access) -> bool {
+        let writes_read = |writes: &[TypeId], access: &Access| {
+            writes
+                .iter()
+                .any(|type_id| access.reads.contains(type_id) || access.writes.contains(type_id))
+        };
+        !writes_read(&self.writes, other) && !writes_read(&other.writes, self)
+    }
+
+    pub fn reads(&self) -> &[TypeId] {
+        &self.reads
+    }
+
+    pub fn writes(&self) -> &[TypeId] {
+        &self.writes
+    }
+}
+
+// Mutable component reference that marks the component as changed once written through
+pub struct Mut<'a, T: 'static> {
+    value: &'a mut T,
+    changed: &'a mut u32,
+    change_tick: u32,
+}
+
+impl<'a, T: 'static> Deref for Mut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<'a, T: 'static> DerefMut for Mut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        *self.changed = self.change_tick;
+        self.value
+    }
+}
+
+impl<'a, T: fmt::Debug + 'static> fmt::Debug for Mut<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+// What a query asks for, such as `&T`, `&mut T`, `Option<&T>`, `EntityId` or a tuple of them.
+// The lifetimes written in the query don't matter, items borrow from the query itself
+pub trait WorldQuery {
+    type Fetch: for<'a> Fetch<'a>;
+}
+
+pub struct Read<T>(PhantomData<T>);
+
+pub struct Write<T>(PhantomData<T>);
+
+impl<T: 'static> WorldQuery for &T {
+    type Fetch = Read<T>;
+}
+
+impl<T: 'static> WorldQuery for &mut T {
+    type Fetch = Write<T>;
+}
+
+impl<Q: WorldQuery> WorldQuery for Option<Q> {
+    type Fetch = Option<Q::Fetch>;
+}
+
+impl WorldQuery for EntityId {
+    type Fetch = EntityId;
+}
+
+/// Queries whose items may be fetched from a thread pool worker
+///
+/// # Safety
+/// `&T` requires `T: Sync` and `&mut T` requires `T: Send`
+pub unsafe trait SendQuery: WorldQuery {}
+
+unsafe impl<T: Sync + 'static> SendQuery for &T {}
+
+unsafe impl<T: Send + 'static> SendQuery for &mut T {}
+
+unsafe impl<Q: SendQuery> SendQuery for Option<Q> {}
+
+unsafe impl SendQuery for EntityId {}
+
+// Reads a `WorldQuery` out of every row of the matching archetypes
+pub trait Fetch<'a> {
+    type Item;
+    type Column: Copy;
+
+    fn access(access: &mut Access) -> Result<(), &'static str>;
+    fn matches(archetype: &Archetype) -> bool;
+
+    /// # Safety
+    /// `archetype` must match, outlive 'a and `access` must have been checked for conflicts
+    unsafe fn column(archetype: *mut Archetype, change_tick: u32) -> Self::Column;
+
+    /// # Safety
+    /// `row` must be in bounds and fetched at most once per query
+    unsafe fn get(column: Self::Column, row: usize) -> Self::Item;
+}
+
+impl<'a, T: 'static> Fetch<'a> for Read<T> {
+    type Item = &'a T;
+    type Column = *const T;
+
+    fn access(access: &mut Access) -> Result<(), &'static str> {
+        access.read::<T>()
+    }
+
+    fn matches(archetype: &Archetype) -> bool {
+        archetype.has(&TypeId::of::<T>())
+    }
+
+    unsafe fn column(archetype: *mut Archetype, _change_tick: u32) -> Self::Column {
+        (*(*archetype).column_ptr::<T>().unwrap()).as_ptr()
+    }
+
+    unsafe fn get(column: Self::Column, row: usize) -> Self::Item {
+        &*column.add(row)
+    }
+}
+
+impl<'a, T: 'static> Fetch<'a> for Write<T> {
+    type Item = Mut<'a, T>;
+    type Column = (*mut T, *mut u32, u32);
+
+    fn access(access: &mut Access) -> Result<(), &'static str> {
+        access.write::<T>()
+    }
+
+    fn matches(archetype: &Archetype) -> bool {
+        archetype.has(&TypeId::of::<T>())
+    }
+
+    // Other services of the batch may hold pointers into the same table, so it is only ever
+    // borrowed shared and the column of `T`, which nothing else accesses, is written through its
+    // cell
+    unsafe fn column(archetype: *mut Archetype, change_tick: u32) -> Self::Column {
+        let archetype = &*archetype;
+        let values = (*archetype.column_ptr::<T>().unwrap()).as_mut_ptr();
+        let ticks = (*archetype.changed[&TypeId::of::<T>()].get()).as_mut_ptr();
+        (values, ticks, change_tick)
+    }
+
+    unsafe fn get((values, ticks, change_tick): Self::Column, row: usize) -> Self::Item {
+        Mut {
+            value: &mut *values.add(row),
+            changed: &mut *ticks.add(row),
+            change_tick,
+        }
+    }
+}
+
+impl<'a, Q: Fetch<'a>> Fetch<'a> for Option<Q> {
+    type Item = Option<Q::Item>;
+    type Column = Option<Q::Column>;
+
+    fn access(access: &mut Access) -> Result<(), &'static str> {
+        Q::access(access)
+    }
+
+    fn matches(_archetype: &Archetype) -> bool {
+        true
+    }
+
+    unsafe fn column(archetype: *mut Archetype, change_tick: u32) -> Self::Column {
+        if Q::matches(&*archetype) {
+            Some(Q::column(archetype, change_tick))
+        } else {
+            None
+        }
+    }
+
+    unsafe fn get(column: Self::Column, row: usize) -> Self::Item {
+        column.map(|column| Q::get(column, row))
+    }
+}
+
+impl<'a> Fetch<'a> for EntityId {
+    type Item = EntityId;
+    type Column = *const EntityId;
+
+    fn access(_access: &mut Access) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn matches(_archetype: &Archetype) -> bool {
+        true
+    }
+
+    unsafe fn column(archetype: *mut Archetype, _change_tick: u32) -> Self::Column {
+        (*archetype).entities.as_ptr()
+    }
+
+    unsafe fn get(column: Self::Column, row: usize) -> Self::Item {
+        *column.add(row)
+    }
+}
+
+macro_rules! impl_fetch {
+    ($($name:ident),+) => {
+        impl<$($name: WorldQuery),+> WorldQuery for ($($name,)+) {
+            type Fetch = ($($name::Fetch,)+);
+        }
+
+        unsafe impl<$($name: SendQuery),+> SendQuery for ($($name,)+) {}
+
+        impl<'a, $($name: Fetch<'a>),+> Fetch<'a> for ($($name,)+) {
+            type Item = ($($name::Item,)+);
+            type Column = ($($name::Column,)+);
+
+            fn access(access: &mut Access) -> Result<(), &'static str> {
+                $($name::access(access)?;)+
+                Ok(())
+            }
+
+            fn matches(archetype: &Archetype) -> bool {
+                $($name::matches(archetype))&&+
+            }
+
+            unsafe fn column(archetype: *mut Archetype, change_tick: u32) -> Self::Column {
+                ($($name::column(archetype, change_tick),)+)
+            }
+
+            #[allow(non_snake_case)]
+            unsafe fn get(column: Self::Column, row: usize) -> Self::Item {
+                let ($($name,)+) = column;
+                ($($name::get($name, row),)+)
+            }
+        }
+    };
+}
+
+impl_fetch!(A);
+impl_fetch!(A, B);
+impl_fetch!(A, B, C);
+impl_fetch!(A, B, C, D);
+impl_fetch!(A, B, C, D, E);
+impl_fetch!(A, B, C, D, E, F);
+impl_fetch!(A, B, C, D, E, F, G);
+impl_fetch!(A, B, C, D, E, F, G, H);
+
+// Narrows a query down without fetching any data
+pub trait QueryFilter {
+    type Column: Copy;
+
+    // Filters only ever read. `query` is the access of the data fetched next to the filter,
+    // whose own writes it may read row by row
+    fn access(_access: &mut Access, _query: &Access) -> Result<(), &'static str> {
+        Ok(())
+    }
+    fn matches(archetype: &Archetype) -> bool;
+
+    /// # Safety
+    /// `archetype` must match and outlive the returned column
+    unsafe fn column(archetype: *const Archetype, last_change_tick: u32) -> Self::Column;
+
+    /// # Safety
+    /// `row` must be in bounds
+    unsafe fn filter(column: Self::Column, row: usize) -> bool;
+}
+
+pub struct With<T>(PhantomData<T>);
+
+pub struct Without<T>(PhantomData<T>);
+
+// Rows whose `T` was added or written to since the system running the query last ran, or since
+// the last `ECS::clear_trackers` for queries made on the ECS directly
+pub struct Changed<T>(PhantomData<T>);
+
+// Rows whose `T` was inserted since then, the entity being spawned or `T` added later on
+pub struct Added<T>(PhantomData<T>);
+
+impl QueryFilter for () {
+    type Column = ();
+
+    fn matches(_archetype: &Archetype) -> bool {
+        true
+    }
+
+    unsafe fn column(_archetype: *const Archetype, _last_change_tick: u32) {}
+
+    unsafe fn filter(_column: (), _row: usize) -> bool {
+        true
+    }
+}
+
+impl<T: 'static> QueryFilter for With<T> {
+    type Column = ();
+
+    fn matches(archetype: &Archetype) -> bool {
+        archetype.has(&TypeId::of::<T>())
+    }
+
+    unsafe fn column(_archetype: *const Archetype, _last_change_tick: u32) {}
+
+    unsafe fn filter(_column: (), _row: usize) -> bool {
+        true
+    }
+}
+
+impl<T: 'static> QueryFilter for Without<T> {
+    type Column = ();
+
+    fn matches(archetype: &Archetype) -> bool {
+        !archetype.has(&TypeId::of::<T>())
+    }
+
+    unsafe fn column(_archetype: *const Archetype, _last_change_tick: u32) {}
+
+    unsafe fn filter(_column: (), _row: usize) -> bool {
+        true
+    }
+}
+
+impl<T: 'static> QueryFilter for Changed<T> {
+    type Column = (*const u32, u32);
+
+    // The ticks of `T` are written along with it, so reading them is reading `T`
+    fn access(access: &mut Access, query: &Access) -> Result<(), &'static str> {
+        if query.writes.contains(&TypeId::of::<T>()) {
+            return Ok(());
+        }
+        access.read::<T>()
+    }
+
+    fn matches(archetype: &Archetype) -> bool {
+        archetype.has(&TypeId::of::<T>())
+    }
+
+    unsafe fn column(archetype: *const Archetype, last_change_tick: u32) -> Self::Column {
+        let ticks = &*(&*archetype).changed[&TypeId::of::<T>()].get();
+        (ticks.as_ptr(), last_change_tick)
+    }
+
+    unsafe fn filter((ticks, last_change_tick): Self::Column, row: usize) -> bool {
+        *ticks.add(row) > last_change_tick
+    }
+}
+
+impl<T: 'static> QueryFilter for Added<T> {
+    type Column = (*const u32, u32);
+
+    fn matches(archetype: &Archetype) -> bool {
+        archetype.has(&TypeId::of::<T>())
+    }
+
+    unsafe fn column(archetype: *const Archetype, last_change_tick: u32) -> Self::Column {
+        let ticks = &*(&*archetype).added[&TypeId::of::<T>()].get();
+        (ticks.as_ptr(), last_change_tick)
+    }
+
+    unsafe fn filter((ticks, last_change_tick): Self::Column, row: usize) -> bool {
+        *ticks.add(row) > last_change_tick
+    }
+}
+
+macro_rules! impl_query_filter {
+    ($($name:ident),+) => {
+        impl<$($name: QueryFilter),+> QueryFilter for ($($name,)+) {
+            type Column = ($($name::Column,)+);
+
+            fn access(access: &mut Access, query: &Access) -> Result<(), &'static str> {
+                $($name::access(access, query)?;)+
+                Ok(())
+            }
+
+            fn matches(archetype: &Archetype) -> bool {
+                $($name::matches(archetype))&&+
+            }
+
+            unsafe fn column(archetype: *const Archetype, last_change_tick: u32) -> Self::Column {
+                ($($name::column(archetype, last_change_tick),)+)
+            }
+
+            #[allow(non_snake_case)]
+            unsafe fn filter(column: Self::Column, row: usize) -> bool {
+                let ($($name,)+) = column;
+                $($name::filter($name, row))&&+
+            }
+        }
+    };
+}
+
+impl_query_filter!(A);
+impl_query_filter!(A, B);
+impl_query_filter!(A, B, C);
+impl_query_filter!(A, B, C, D);
+impl_query_filter!(A, B, C, D, E);
+impl_query_filter!(A, B, C, D, E, F);
+
+pub struct QueryIter<'a, Q: WorldQuery, F: QueryFilter> {
+    archetypes: *mut Archetype,
+    count: usize,
+    next: usize,
+    current: Option<(<Q::Fetch as Fetch<'a>>::Column, F::Column, usize)>,
+    row: usize,
+    change_tick: u32,
+    last_change_tick: u32,
+    _marker: PhantomData<&'a mut Archetype>,
+}
+
+impl<'a, Q: WorldQuery, F: QueryFilter> QueryIter<'a, Q, F> {
+    pub(crate) fn new(
+        archetypes: &'a mut [Archetype],
+        change_tick: u32,
+        last_change_tick: u32,
+    ) -> Self {
+        unsafe {
+            Self::from_raw(
+                archetypes.as_mut_ptr(),
+                archetypes.len(),
+                change_tick,
+                last_change_tick,
+            )
+        }
+    }
+
+    // Safety: the archetypes must outlive 'a and no other query may write what this one accesses
+    pub(crate) unsafe fn from_raw(
+        archetypes: *mut Archetype,
+        count: usize,
+        change_tick: u32,
+        last_change_tick: u32,
+    ) -> Self {
+        if let Err(component) = <Q::Fetch as Fetch<'a>>::access(&mut Access::new()) {
+            panic!(
+                "Component `{}` is borrowed mutably while borrowed elsewhere in the same query",
+                component
+            );
+        }
+
+        Self {
+            archetypes,
+            count,
+            next: 0,
+            current: None,
+            row: 0,
+            change_tick,
+            last_change_tick,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, Q: WorldQuery, F: QueryFilter> Iterator for QueryIter<'a, Q, F> {
+    type Item = <Q::Fetch as Fetch<'a>>::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((column, filter, len)) = self.current {
+                while self.row < len {
+                    let row = self.row;
+                    self.row += 1;
+
+                    // Rows are visited once each and the access was checked in `from_raw`
+                    unsafe {
+                        if F::filter(filter, row) {
+                            return Some(<Q::Fetch as Fetch<'a>>::get(column, row));
+                        }
+                    }
+                }
+            }
+
+            if self.next == self.count {
+                return None;
+            }
+
+            let archetype = unsafe { self.archetypes.add(self.next) };
+            self.next += 1;
+            self.current = None;
+
+            let (matches, len) = unsafe {
+                let archetype = &*archetype;
+                (
+                    !archetype.is_empty()
+                        && <Q::Fetch as Fetch<'a>>::matches(archetype)
+                        && F::matches(archetype),
+                    archetype.len(),
+                )
+            };
+            if !matches {
+                continue;
+            }
+
+            self.row = 0;
+            self.current = unsafe {
+                Some((
+                    <Q::Fetch as Fetch<'a>>::column(archetype, self.change_tick),
+                    F::column(archetype, self.last_change_tick),
+                    len,
+                ))
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Entity, Query, ResMut, RunSystemPhase, Service, ECS};
+
+    struct A(u32);
+    struct B(u32);
+    struct C;
+
+    fn world() -> (ECS, [EntityId; 3]) {
+        let mut ecs = ECS::new();
+        let ids = [
+            ecs.add_entity(Entity::new().with(A(1))),
+            ecs.add_entity(Entity::new().with(A(2)).with(B(20))),
+            ecs.add_entity(Entity::new().with(A(3)).with(C)),
+        ];
+        (ecs, ids)
+    }
+
+    fn sorted<I: Iterator<Item = u32>>(values: I) -> Vec<u32> {
+        let mut values: Vec<u32> = values.collect();
+        values.sort_unstable();
+        values
+    }
+
+    #[test]
+    fn queries_visit_every_matching_table() {
+        let (mut ecs, ids) = world();
+
+        assert_eq!(sorted(ecs.query::<&A>().map(|a| a.0)), [1, 2, 3]);
+        let sums = ecs.query::<(&A, &B)>().map(|(a, b)| a.0 + b.0);
+        assert_eq!(sorted(sums), [22]);
+
+        let (id, _) = ecs.query::<(EntityId, &B)>().next().unwrap();
+        assert_eq!(id, ids[1]);
+    }
+
+    #[test]
+    fn with_and_without_filter_tables() {
+        let (mut ecs, _) = world();
+
+        let with = ecs.query_filtered::<&A, With<C>>().map(|a| a.0);
+        assert_eq!(sorted(with), [3]);
+        let without = ecs
+            .query_filtered::<&A, (Without<B>, Without<C>)>()
+            .map(|a| a.0);
+        assert_eq!(sorted(without), [1]);
+        assert_eq!(ecs.query_filtered::<&A, (With<B>, With<C>)>().count(), 0);
+    }
+
+    #[test]
+    fn optional_components_are_none_when_missing() {
+        let (mut ecs, _) = world();
+
+        let pairs = ecs
+            .query::<(&A, Option<&B>)>()
+            .map(|(a, b)| a.0 + b.map_or(0, |b| b.0));
+        assert_eq!(sorted(pairs), [1, 3, 22]);
+    }
+
+    #[test]
+    fn writes_go_through_to_the_table() {
+        let (mut ecs, ids) = world();
+
+        for (mut a, b) in ecs.query::<(&mut A, Option<&mut B>)>() {
+            a.0 *= 10;
+            if let Some(mut b) = b {
+                b.0 += 1;
+            }
+        }
+
+        assert_eq!(sorted(ecs.query::<&A>().map(|a| a.0)), [10, 20, 30]);
+        assert_eq!(ecs.get(ids[1]).unwrap().get::<B>().unwrap().0, 21);
+    }
+
+    #[test]
+    #[should_panic(expected = "borrowed mutably while borrowed elsewhere")]
+    fn a_query_cannot_alias_a_written_component() {
+        let (mut ecs, _) = world();
+        ecs.query::<(&mut A, &A)>();
+    }
+
+    #[test]
+    fn only_reads_are_compatible() {
+        let mut reads = Access::new();
+        reads.read::<A>().unwrap();
+        let mut other_reads = Access::new();
+        other_reads.read::<A>().unwrap();
+        other_reads.write::<B>().unwrap();
+        let mut writes = Access::new();
+        writes.write::<A>().unwrap();
+
+        assert!(reads.is_compatible(&other_reads));
+        assert!(!reads.is_compatible(&writes));
+        assert!(!writes.is_compatible(&reads));
+        assert!(writes.read::<A>().is_err());
+        assert!(reads.write::<A>().is_err());
+    }
+
+    #[test]
+    fn changes_are_tracked_until_cleared() {
+        let (mut ecs, ids) = world();
+        assert_eq!(ecs.query_filtered::<&A, Added<A>>().count(), 3);
+        assert_eq!(ecs.query_filtered::<&A, Changed<A>>().count(), 3);
+
+        ecs.clear_trackers();
+        for a in ecs.query::<&mut A>() {
+            assert!(a.0 > 0);
+        }
+        assert_eq!(ecs.query_filtered::<&A, Changed<A>>().count(), 0);
+
+        ecs.get_mut(ids[0]).unwrap().get_mut::<A>().unwrap().0 = 5;
+        ecs.insert_component(ids[1], A(6));
+        ecs.insert_component(ids[2], B(7));
+
+        let changed = ecs.query_filtered::<&A, Changed<A>>().map(|a| a.0);
+        assert_eq!(sorted(changed), [5, 6]);
+        assert_eq!(ecs.query_filtered::<&A, Added<A>>().count(), 0);
+        let added = ecs.query_filtered::<&B, Added<B>>().map(|b| b.0);
+        assert_eq!(sorted(added), [7]);
+    }
+
+    #[test]
+    fn components_moved_between_tables_keep_their_ticks() {
+        let (mut ecs, ids) = world();
+        ecs.clear_trackers();
+
+        ecs.insert_component(ids[0], C);
+        ecs.remove_component::<B>(ids[1]);
+
+        assert_eq!(ecs.query_filtered::<&A, Changed<A>>().count(), 0);
+        assert_eq!(ecs.query_filtered::<&C, Added<C>>().count(), 1);
+    }
+
+    struct Seen(Vec<usize>);
+
+    fn count_changes(mut query: Query<EntityId, Changed<A>>, mut seen: ResMut<Seen>) {
+        let count = query.iter().count();
+        seen.0.push(count);
+    }
+
+    #[test]
+    fn services_see_the_changes_since_their_last_run() {
+        let (mut ecs, ids) = world();
+        ecs.resources.set(Seen(Vec::new()));
+        ecs.add_before_service(Service::at_tick(count_changes));
+
+        ecs.run_systems(RunSystemPhase::Tick);
+        ecs.run_systems(RunSystemPhase::Tick);
+        ecs.get_mut(ids[2]).unwrap().get_mut::<A>().unwrap().0 += 1;
+        ecs.run_systems(RunSystemPhase::Tick);
+
+        assert_eq!(ecs.resources.get::<Seen>().unwrap().0, [3, 0, 1]);
+    }
+}
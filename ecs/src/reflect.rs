@@ -0,0 +1,507 @@
+use super::archetype::EntityRef;
+use super::{Bounds, EntityId, Transform, ECS};
+use math::{Quat, Vec2, Vec3, Vec4};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+// Value of a primitive field, what inspectors show and scripts write
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldValue::Bool(value) => write!(f, "{}", value),
+            FieldValue::Int(value) => write!(f, "{}", value),
+            FieldValue::Float(value) => write!(f, "{}", value),
+            FieldValue::String(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldInfo {
+    pub name: &'static str,
+    pub type_name: &'static str,
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeInfo {
+    pub name: &'static str,
+    pub fields: Vec<FieldInfo>,
+}
+
+// Access to the fields of a type by name. Structs get it through `impl_reflect!`, primitives
+// expose their value instead of fields
+pub trait Reflect: Any {
+    fn type_info() -> TypeInfo
+    where
+        Self: Sized;
+
+    fn type_name(&self) -> &'static str;
+
+    fn field_names(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn field(&self, _name: &str) -> Option<&dyn Reflect> {
+        None
+    }
+
+    fn field_mut(&mut self, _name: &str) -> Option<&mut dyn Reflect> {
+        None
+    }
+
+    fn value(&self) -> Option<FieldValue> {
+        None
+    }
+
+    // Fails when the value doesn't fit the type
+    fn set_value(&mut self, _value: FieldValue) -> bool {
+        false
+    }
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl dyn Reflect {
+    pub fn downcast_ref<T: Reflect>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+
+    pub fn downcast_mut<T: Reflect>(&mut self) -> Option<&mut T> {
+        self.as_any_mut().downcast_mut()
+    }
+
+    // Nested field, like `position.x`
+    pub fn path(&self, path: &str) -> Option<&dyn Reflect> {
+        path.split('.')
+            .try_fold(self, |value, name| value.field(name))
+    }
+
+    pub fn path_mut(&mut self, path: &str) -> Option<&mut dyn Reflect> {
+        let mut value = self;
+        for name in path.split('.') {
+            value = value.field_mut(name)?;
+        }
+        Some(value)
+    }
+}
+
+impl fmt::Debug for dyn Reflect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(value) = self.value() {
+            return write!(f, "{}", value);
+        }
+
+        let mut fields = f.debug_struct(self.type_name());
+        for name in self.field_names() {
+            fields.field(name, &self.field(name).unwrap());
+        }
+        fields.finish()
+    }
+}
+
+// Implements `Reflect` for a struct from its fields and their types, which have to implement
+// `Reflect` themselves
+#[macro_export]
+macro_rules! impl_reflect {
+    ($type:ident { $($field:ident: $field_type:ty),* $(,)? }) => {
+        impl $crate::Reflect for $type {
+            fn type_info() -> $crate::TypeInfo {
+                $crate::TypeInfo {
+                    name: stringify!($type),
+                    fields: vec![$($crate::FieldInfo {
+                        name: stringify!($field),
+                        type_name: <$field_type as $crate::Reflect>::type_info().name,
+                    }),*],
+                }
+            }
+
+            fn type_name(&self) -> &'static str {
+                stringify!($type)
+            }
+
+            fn field_names(&self) -> &'static [&'static str] {
+                &[$(stringify!($field)),*]
+            }
+
+            fn field(&self, name: &str) -> Option<&dyn $crate::Reflect> {
+                match name {
+                    $(stringify!($field) => Some(&self.$field),)*
+                    _ => None,
+                }
+            }
+
+            fn field_mut(&mut self, name: &str) -> Option<&mut dyn $crate::Reflect> {
+                match name {
+                    $(stringify!($field) => Some(&mut self.$field),)*
+                    _ => None,
+                }
+            }
+
+            fn as_any(&self) -> &dyn std::any::Any {
+                self
+            }
+
+            fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+                self
+            }
+        }
+    };
+}
+
+macro_rules! impl_reflect_value {
+    ($type:ty, $variant:ident, $value:ident => $to_value:expr, $from_value:expr) => {
+        impl Reflect for $type {
+            fn type_info() -> TypeInfo {
+                TypeInfo {
+                    name: stringify!($type),
+                    fields: Vec::new(),
+                }
+            }
+
+            fn type_name(&self) -> &'static str {
+                stringify!($type)
+            }
+
+            fn value(&self) -> Option<FieldValue> {
+                let $value = *self;
+                $to_value.map(FieldValue::$variant)
+            }
+
+            fn set_value(&mut self, value: FieldValue) -> bool {
+                let converted = match value {
+                    FieldValue::$variant($value) => $from_value,
+                    _ => None,
+                };
+                match converted {
+                    Some(value) => {
+                        *self = value;
+                        true
+                    }
+                    None => false,
+                }
+            }
+
+            fn as_any(&self) -> &dyn Any {
+                self
+            }
+
+            fn as_any_mut(&mut self) -> &mut dyn Any {
+                self
+            }
+        }
+    };
+}
+
+// Integers past the range of the field are rejected instead of wrapping, and a `u64` too large
+// for an `i64` has no value
+macro_rules! impl_reflect_int {
+    ($($type:ty),*) => {
+        $(impl_reflect_value!(
+            $type,
+            Int,
+            value => i64::try_from(value).ok(),
+            <$type>::try_from(value).ok()
+        );)*
+    };
+}
+
+impl_reflect_value!(bool, Bool, value => Some(value), Some(value));
+impl_reflect_int!(i8, i16, i32, i64, u8, u16, u32, u64, usize);
+// Finite values too large for an `f32` are rejected rather than becoming infinite
+impl_reflect_value!(
+    f32,
+    Float,
+    value => Some(f64::from(value)),
+    Some(value as f32).filter(|float| float.is_finite() || !value.is_finite())
+);
+impl_reflect_value!(f64, Float, value => Some(value), Some(value));
+
+impl Reflect for String {
+    fn type_info() -> TypeInfo {
+        TypeInfo {
+            name: "String",
+            fields: Vec::new(),
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        "String"
+    }
+
+    fn value(&self) -> Option<FieldValue> {
+        Some(FieldValue::String(self.clone()))
+    }
+
+    fn set_value(&mut self, value: FieldValue) -> bool {
+        match value {
+            FieldValue::String(value) => *self = value,
+            _ => return false,
+        }
+        true
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl_reflect!(Vec2 { x: f32, y: f32 });
+impl_reflect!(Vec3 {
+    x: f32,
+    y: f32,
+    z: f32
+});
+impl_reflect!(Vec4 {
+    x: f32,
+    y: f32,
+    z: f32,
+    w: f32
+});
+impl_reflect!(Quat {
+    x: f32,
+    y: f32,
+    z: f32,
+    w: f32
+});
+impl_reflect!(Transform {
+    position: Vec3,
+    rotation: Quat,
+    scale: Vec3
+});
+impl_reflect!(Bounds { half_extents: Vec3 });
+
+struct RegisteredType {
+    info: TypeInfo,
+    reflect: fn(&dyn Any) -> &dyn Reflect,
+    reflect_mut: fn(&mut dyn Any) -> &mut dyn Reflect,
+}
+
+// Component types that tools can list and edit without knowing them at compile time
+#[derive(Default)]
+pub struct TypeRegistry {
+    types: HashMap<TypeId, RegisteredType>,
+    names: HashMap<&'static str, TypeId>,
+}
+
+impl TypeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<T: Reflect>(&mut self) {
+        let info = T::type_info();
+        self.names.insert(info.name, TypeId::of::<T>());
+        self.types.insert(
+            TypeId::of::<T>(),
+            RegisteredType {
+                info,
+                reflect: |value| value.downcast_ref::<T>().unwrap(),
+                reflect_mut: |value| value.downcast_mut::<T>().unwrap(),
+            },
+        );
+    }
+
+    pub fn get(&self, type_id: &TypeId) -> Option<&TypeInfo> {
+        self.types.get(type_id).map(|registered| &registered.info)
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&TypeInfo> {
+        self.names.get(name).and_then(|type_id| self.get(type_id))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TypeInfo> {
+        self.types.values().map(|registered| &registered.info)
+    }
+}
+
+// A registered component of an entity, seen through reflection
+pub struct ComponentView<'a> {
+    pub info: &'a TypeInfo,
+    pub value: &'a dyn Reflect,
+}
+
+impl<'a> ComponentView<'a> {
+    pub fn name(&self) -> &'static str {
+        self.info.name
+    }
+}
+
+impl<'a> fmt::Debug for ComponentView<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<'a> EntityRef<'a> {
+    // Registered components of the entity, the others are skipped
+    pub fn components(&self) -> impl Iterator<Item = ComponentView<'a>> + 'a {
+        let (types, row) = (self.types, self.row);
+        self.archetype
+            .columns
+            .iter()
+            .filter_map(move |(type_id, column)| {
+                let registered = types.types.get(type_id)?;
+                Some(ComponentView {
+                    info: &registered.info,
+                    value: (registered.reflect)(column.row(row)),
+                })
+            })
+    }
+
+    pub fn component(&self, name: &str) -> Option<ComponentView<'a>> {
+        self.components().find(|component| component.name() == name)
+    }
+}
+
+impl ECS {
+    pub fn register_type<T: Reflect>(&mut self) {
+        self.types.register::<T>();
+    }
+
+    pub fn type_registry(&self) -> &TypeRegistry {
+        &self.types
+    }
+
+    // Registered component of `id` by name, for scripts and inspectors. The component is marked
+    // as changed
+    pub fn reflect_mut(&mut self, id: EntityId, name: &str) -> Option<&mut dyn Reflect> {
+        let type_id = *self.types.names.get(name)?;
+        let registered = self.types.types.get(&type_id)?;
+        let location = self.entities.get(id.0)?;
+
+        let archetype = &mut self.archetypes[location.archetype];
+        if !archetype.has(&type_id) {
+            return None;
+        }
+        archetype.set_changed(&type_id, location.row, self.change_tick);
+
+        let column = archetype.columns.get_mut(&type_id)?;
+        Some((registered.reflect_mut)(column.row_mut(location.row)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Entity;
+
+    #[test]
+    fn integers_out_of_range_are_rejected() {
+        let mut value = 7u8;
+        assert!(!value.set_value(FieldValue::Int(300)));
+        assert!(!value.set_value(FieldValue::Int(-1)));
+        assert_eq!(value, 7);
+
+        let mut value = 0i16;
+        assert!(!value.set_value(FieldValue::Int(i64::from(i16::MAX) + 1)));
+        assert!(value.set_value(FieldValue::Int(i64::from(i16::MIN))));
+        assert_eq!(value, i16::MIN);
+
+        let mut value = 0u64;
+        assert!(!value.set_value(FieldValue::Int(-5)));
+        assert_eq!(value, 0);
+    }
+
+    #[test]
+    fn integers_round_trip() {
+        let mut value = 0u8;
+        assert!(value.set_value(FieldValue::Int(255)));
+        assert_eq!(value.value(), Some(FieldValue::Int(255)));
+
+        let mut value = 0i64;
+        assert!(value.set_value(FieldValue::Int(i64::MIN)));
+        assert_eq!(value.value(), Some(FieldValue::Int(i64::MIN)));
+
+        assert_eq!((i64::MAX as u64).value(), Some(FieldValue::Int(i64::MAX)));
+        assert_eq!(u64::MAX.value(), None);
+    }
+
+    #[test]
+    fn values_of_another_kind_are_rejected() {
+        let mut value = 1i32;
+        assert!(!value.set_value(FieldValue::Float(2.0)));
+        assert!(!value.set_value(FieldValue::Bool(true)));
+        assert_eq!(value, 1);
+
+        let mut value = String::from("a");
+        assert!(!value.set_value(FieldValue::Int(1)));
+        assert!(value.set_value(FieldValue::String("b".to_string())));
+        assert_eq!(value, "b");
+    }
+
+    #[test]
+    fn floats_too_large_for_f32_are_rejected() {
+        let mut value = 1.0f32;
+        assert!(!value.set_value(FieldValue::Float(1e300)));
+        assert_eq!(value, 1.0);
+
+        assert!(value.set_value(FieldValue::Float(0.5)));
+        assert_eq!(value.value(), Some(FieldValue::Float(0.5)));
+
+        assert!(value.set_value(FieldValue::Float(f64::INFINITY)));
+        assert_eq!(value, f32::INFINITY);
+    }
+
+    #[test]
+    fn nested_fields_are_set_by_path() {
+        let mut transform = Transform::default();
+        let reflect: &mut dyn Reflect = &mut transform;
+
+        let x = reflect.path_mut("position.x").unwrap();
+        assert!(x.set_value(FieldValue::Float(3.0)));
+        assert!(reflect.path_mut("position.w").is_none());
+        assert!(reflect.path_mut("scale.y.z").is_none());
+
+        assert_eq!(transform.position.x, 3.0);
+    }
+
+    #[test]
+    fn registered_components_are_listed_and_edited() {
+        let mut ecs = ECS::new();
+        ecs.register_type::<Transform>();
+
+        let mut entity = Entity::new();
+        entity.set(Transform::default());
+        entity.set(5u32);
+        let id = ecs.add_entity(entity);
+
+        let names: Vec<_> = ecs
+            .get(id)
+            .unwrap()
+            .components()
+            .map(|component| component.name())
+            .collect();
+        assert_eq!(names, vec!["Transform"]);
+
+        let transform = ecs.reflect_mut(id, "Transform").unwrap();
+        let y = transform.path_mut("position.y").unwrap();
+        assert!(y.set_value(FieldValue::Float(2.0)));
+        assert!(ecs.reflect_mut(id, "u32").is_none());
+
+        let entity = ecs.get(id).unwrap();
+        let position = entity
+            .component("Transform")
+            .unwrap()
+            .value
+            .path("position.y")
+            .unwrap()
+            .value();
+        assert_eq!(position, Some(FieldValue::Float(2.0)));
+    }
+}
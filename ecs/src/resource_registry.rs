@@ -1,49 +1,399 @@
-use anymap::AnyMap;
+use std::any::{type_name, Any, TypeId};
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::fmt;
 use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicIsize, AtomicU32, Ordering};
+
+// Positive values count shared borrows, -1 marks a unique borrow. Atomic since services
+// running on the thread pool borrow from the same registry
+const UNIQUE: isize = -1;
+
+struct Resource {
+    borrow: AtomicIsize,
+    // Ticks of the insertion and of the last mutable access
+    added: u32,
+    changed: AtomicU32,
+    value: UnsafeCell<Box<dyn Any>>,
+}
+
+impl Resource {
+    fn new<T: 'static>(value: T, tick: u32) -> Self {
+        Self {
+            borrow: AtomicIsize::new(0),
+            added: tick,
+            changed: AtomicU32::new(tick),
+            value: UnsafeCell::new(Box::new(value)),
+        }
+    }
+}
+
+pub struct Res<'a, T: 'static> {
+    value: &'a T,
+    borrow: &'a AtomicIsize,
+    added: u32,
+    changed: u32,
+    last_change_tick: u32,
+}
+
+impl<'a, T: 'static> Res<'a, T> {
+    // Inserted since the system holding it last ran
+    pub fn is_added(&self) -> bool {
+        self.added > self.last_change_tick
+    }
+
+    // Inserted or mutably accessed since the system holding it last ran
+    pub fn is_changed(&self) -> bool {
+        self.changed > self.last_change_tick
+    }
+}
+
+impl<'a, T: 'static> Deref for Res<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<'a, T: 'static> Drop for Res<'a, T> {
+    fn drop(&mut self) {
+        self.borrow.fetch_sub(1, Ordering::Release);
+    }
+}
+
+impl<'a, T: fmt::Debug + 'static> fmt::Debug for Res<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+pub struct ResMut<'a, T: 'static> {
+    value: &'a mut T,
+    borrow: &'a AtomicIsize,
+    added: u32,
+    changed: &'a AtomicU32,
+    change_tick: u32,
+    last_change_tick: u32,
+}
+
+impl<'a, T: 'static> ResMut<'a, T> {
+    pub fn is_added(&self) -> bool {
+        self.added > self.last_change_tick
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.changed.load(Ordering::Relaxed) > self.last_change_tick
+    }
+}
+
+impl<'a, T: 'static> Deref for ResMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<'a, T: 'static> DerefMut for ResMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.changed.store(self.change_tick, Ordering::Relaxed);
+        self.value
+    }
+}
+
+impl<'a, T: 'static> Drop for ResMut<'a, T> {
+    fn drop(&mut self) {
+        self.borrow.store(0, Ordering::Release);
+    }
+}
+
+impl<'a, T: fmt::Debug + 'static> fmt::Debug for ResMut<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
 
 pub struct ResourceRegistry {
-    resources: AnyMap,
+    resources: HashMap<TypeId, Resource>,
+    // Ticks of whoever holds the registry, kept up to date by the ECS around every system
+    pub(crate) change_tick: u32,
+    pub(crate) last_change_tick: u32,
+}
+
+impl Default for ResourceRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ResourceRegistry {
     pub fn new() -> Self {
         Self {
-            resources: AnyMap::new(),
+            resources: HashMap::new(),
+            change_tick: 1,
+            last_change_tick: 0,
+        }
+    }
+
+    fn borrow<T: 'static>(&self, key: TypeId, last_change_tick: u32) -> Option<Res<'_, T>> {
+        let resource = self.resources.get(&key)?;
+
+        let borrowed =
+            resource
+                .borrow
+                .fetch_update(Ordering::Acquire, Ordering::Relaxed, |borrow| {
+                    if borrow == UNIQUE {
+                        None
+                    } else {
+                        Some(borrow + 1)
+                    }
+                });
+        if borrowed.is_err() {
+            panic!(
+                "Cannot borrow resource `{}`, it is already mutably borrowed",
+                type_name::<T>()
+            );
         }
+
+        // The borrow flag guarantees no `ResMut` to this value is alive
+        let value = unsafe { &*resource.value.get() };
+
+        Some(Res {
+            value: value.downcast_ref::<T>().unwrap(),
+            borrow: &resource.borrow,
+            added: resource.added,
+            changed: resource.changed.load(Ordering::Relaxed),
+            last_change_tick,
+        })
+    }
+
+    fn borrow_mut<T: 'static>(
+        &self,
+        key: TypeId,
+        change_tick: u32,
+        last_change_tick: u32,
+    ) -> Option<ResMut<'_, T>> {
+        let resource = self.resources.get(&key)?;
+
+        match resource
+            .borrow
+            .compare_exchange(0, UNIQUE, Ordering::Acquire, Ordering::Relaxed)
+        {
+            Ok(_) => (),
+            Err(UNIQUE) => panic!(
+                "Cannot mutably borrow resource `{}`, it is already mutably borrowed",
+                type_name::<T>()
+            ),
+            Err(_) => panic!(
+                "Cannot mutably borrow resource `{}`, it is already borrowed",
+                type_name::<T>()
+            ),
+        }
+
+        // The borrow flag guarantees this is the only reference to the value
+        let value = unsafe { &mut *resource.value.get() };
+
+        Some(ResMut {
+            value: value.downcast_mut::<T>().unwrap(),
+            borrow: &resource.borrow,
+            added: resource.added,
+            changed: &resource.changed,
+            change_tick,
+            last_change_tick,
+        })
     }
 
     pub fn tagged_set<K: 'static, T: 'static>(&mut self, resource: T) {
-        let marker: std::marker::PhantomData<K> = std::marker::PhantomData;
+        self.resources.insert(
+            TypeId::of::<(PhantomData<K>, T)>(),
+            Resource::new(resource, self.change_tick),
+        );
+    }
+
+    pub fn tagged_get_mut<K: 'static, T: 'static>(&self) -> Option<ResMut<'_, T>> {
+        self.borrow_mut::<T>(
+            TypeId::of::<(PhantomData<K>, T)>(),
+            self.change_tick,
+            self.last_change_tick,
+        )
+    }
+
+    pub fn tagged_get<K: 'static, T: 'static>(&self) -> Option<Res<'_, T>> {
+        self.borrow::<T>(TypeId::of::<(PhantomData<K>, T)>(), self.last_change_tick)
+    }
+
+    pub fn set<T: 'static>(&mut self, resource: T) {
         self.resources
-            .insert::<(PhantomData<K>, T)>((marker, resource));
+            .insert(TypeId::of::<T>(), Resource::new(resource, self.change_tick));
     }
 
-    pub fn tagged_get_mut<K: 'static, T: 'static>(&mut self) -> Option<&'static mut T> {
-        unsafe {
-            if let Some(tuple) = self.resources.get_mut::<(PhantomData<K>, T)>() {
-                let (_, b) = tuple;
-                return Some(std::mem::transmute(b));
-            }
-            None
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.resources
+            .remove(&TypeId::of::<T>())
+            .and_then(|resource| resource.value.into_inner().downcast::<T>().ok())
+            .map(|value| *value)
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn get_mut<T: 'static>(&self) -> Option<ResMut<'_, T>> {
+        self.borrow_mut::<T>(TypeId::of::<T>(), self.change_tick, self.last_change_tick)
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<Res<'_, T>> {
+        self.borrow::<T>(TypeId::of::<T>(), self.last_change_tick)
+    }
+
+    // Borrows with the ticks of a service, which may differ from the ones of the registry
+    // when services run in parallel
+    pub(crate) fn get_mut_ticked<T: 'static>(
+        &self,
+        change_tick: u32,
+        last_change_tick: u32,
+    ) -> Option<ResMut<'_, T>> {
+        self.borrow_mut::<T>(TypeId::of::<T>(), change_tick, last_change_tick)
+    }
+
+    pub(crate) fn get_ticked<T: 'static>(&self, last_change_tick: u32) -> Option<Res<'_, T>> {
+        self.borrow::<T>(TypeId::of::<T>(), last_change_tick)
+    }
+
+    // Whether `T` was inserted or mutably accessed since the current system last ran
+    pub fn resource_changed<T: 'static>(&self) -> bool {
+        match self.resources.get(&TypeId::of::<T>()) {
+            Some(resource) => resource.changed.load(Ordering::Relaxed) > self.last_change_tick,
+            None => false,
         }
     }
 
-    pub fn tagged_get<K: 'static, T: 'static>(&self) -> Option<&T> {
-        if let Some(tuple) = self.resources.get::<(PhantomData<K>, T)>() {
-            return Some(&tuple.1);
+    pub fn resource_added<T: 'static>(&self) -> bool {
+        match self.resources.get(&TypeId::of::<T>()) {
+            Some(resource) => resource.added > self.last_change_tick,
+            None => false,
         }
-        None
     }
 
-    pub fn set<T: 'static>(&mut self, resource: T) {
-        self.resources.insert::<T>(resource);
+    pub fn get_many_mut<'a, T: ResourceSet<'a>>(&'a self) -> Option<T::Guards> {
+        T::fetch(self)
     }
+}
+
+pub trait ResourceSet<'a> {
+    type Guards;
 
-    pub fn get_mut<T: 'static>(&mut self) -> Option<&'static mut T> {
-        unsafe { std::mem::transmute(self.resources.get_mut::<T>()) }
+    fn fetch(registry: &'a ResourceRegistry) -> Option<Self::Guards>;
+}
+
+macro_rules! impl_resource_set {
+    ($($name:ident),+) => {
+        impl<'a, $($name: 'static),+> ResourceSet<'a> for ($($name,)+) {
+            type Guards = ($(ResMut<'a, $name>,)+);
+
+            fn fetch(registry: &'a ResourceRegistry) -> Option<Self::Guards> {
+                Some(($(registry.get_mut::<$name>()?,)+))
+            }
+        }
+    };
+}
+
+impl_resource_set!(A);
+impl_resource_set!(A, B);
+impl_resource_set!(A, B, C);
+impl_resource_set!(A, B, C, D);
+impl_resource_set!(A, B, C, D, E);
+impl_resource_set!(A, B, C, D, E, F);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Score(u32);
+    struct Lives(u32);
+    struct Player;
+
+    #[test]
+    fn shared_borrows_stack_and_release_on_drop() {
+        let mut resources = ResourceRegistry::new();
+        resources.set(Score(1));
+
+        let first = resources.get::<Score>().unwrap();
+        let second = resources.get::<Score>().unwrap();
+        assert_eq!(first.0 + second.0, 2);
+        drop((first, second));
+
+        resources.get_mut::<Score>().unwrap().0 = 5;
+        assert_eq!(resources.get::<Score>().unwrap().0, 5);
     }
 
-    pub fn get<T: 'static>(&self) -> Option<&T> {
-        self.resources.get::<T>()
+    #[test]
+    #[should_panic(expected = "already borrowed")]
+    fn mutable_borrow_panics_while_borrowed() {
+        let mut resources = ResourceRegistry::new();
+        resources.set(Score(1));
+
+        let _score = resources.get::<Score>().unwrap();
+        resources.get_mut::<Score>();
+    }
+
+    #[test]
+    #[should_panic(expected = "already mutably borrowed")]
+    fn borrow_panics_while_mutably_borrowed() {
+        let mut resources = ResourceRegistry::new();
+        resources.set(Score(1));
+
+        let _score = resources.get_mut::<Score>().unwrap();
+        resources.get::<Score>();
+    }
+
+    #[test]
+    fn different_resources_borrow_independently() {
+        let mut resources = ResourceRegistry::new();
+        resources.set(Score(1));
+        resources.set(Lives(3));
+        resources.tagged_set::<Player, Score>(Score(10));
+
+        let (mut score, mut lives) = resources.get_many_mut::<(Score, Lives)>().unwrap();
+        let mut player_score = resources.tagged_get_mut::<Player, Score>().unwrap();
+        score.0 += 1;
+        lives.0 -= 1;
+        player_score.0 += 1;
+        drop((score, lives, player_score));
+
+        assert_eq!(resources.get::<Score>().unwrap().0, 2);
+        assert_eq!(resources.get::<Lives>().unwrap().0, 2);
+        assert_eq!(resources.tagged_get::<Player, Score>().unwrap().0, 11);
+        assert!(resources.get_many_mut::<(Score, Player)>().is_none());
+    }
+
+    #[test]
+    fn removed_resources_are_handed_back() {
+        let mut resources = ResourceRegistry::new();
+        resources.set(Score(7));
+
+        assert_eq!(resources.remove::<Score>().map(|score| score.0), Some(7));
+        assert!(!resources.contains::<Score>());
+        assert!(resources.get::<Score>().is_none());
+    }
+
+    #[test]
+    fn resources_are_changed_by_mutable_access_only() {
+        let mut resources = ResourceRegistry::new();
+        resources.set(Score(1));
+        assert!(resources.get::<Score>().unwrap().is_added());
+
+        resources.last_change_tick = resources.change_tick;
+        resources.change_tick += 1;
+        assert_eq!(resources.get_mut::<Score>().unwrap().0, 1);
+        assert!(!resources.resource_changed::<Score>());
+        assert!(!resources.resource_added::<Score>());
+
+        resources.get_mut::<Score>().unwrap().0 += 1;
+        assert!(resources.get::<Score>().unwrap().is_changed());
+        assert!(resources.resource_changed::<Score>());
+        assert!(!resources.get::<Score>().unwrap().is_added());
     }
 }
@@ -0,0 +1,453 @@
+use super::{Entity, EntityId, Parent, ResourceRegistry, ECS};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::any::{Any, TypeId};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::mem;
+
+// Snapshot of the registered components and resources of an ECS, keyed by their registered
+// names. It goes through any serde format, `to_json` and `from_json` cover the usual case
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Scene {
+    pub entities: Vec<SceneEntity>,
+    pub resources: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SceneEntity {
+    // Index of the parent in `Scene::entities`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>,
+    pub components: BTreeMap<String, Value>,
+}
+
+impl Scene {
+    pub fn to_json(&self) -> Result<String, SceneError> {
+        serde_json::to_string_pretty(self).map_err(SceneError::Format)
+    }
+
+    pub fn from_json(data: &str) -> Result<Self, SceneError> {
+        serde_json::from_str(data).map_err(SceneError::Format)
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    UnknownComponent(String),
+    UnknownResource(String),
+    InvalidParent(usize),
+    Value {
+        name: String,
+        error: serde_json::Error,
+    },
+    Format(serde_json::Error),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::UnknownComponent(name) => {
+                write!(
+                    f,
+                    "Scene contains component `{}`, which was never registered",
+                    name
+                )
+            }
+            SceneError::UnknownResource(name) => {
+                write!(
+                    f,
+                    "Scene contains resource `{}`, which was never registered",
+                    name
+                )
+            }
+            SceneError::InvalidParent(index) => {
+                write!(
+                    f,
+                    "Scene entity {} has a parent that is not part of the scene",
+                    index
+                )
+            }
+            SceneError::Value { name, error } => write!(f, "Invalid `{}`: {}", name, error),
+            SceneError::Format(error) => write!(f, "Invalid scene: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+type SaveComponent = fn(&dyn Any) -> Result<Value, serde_json::Error>;
+
+type LoadComponent = fn(&mut Entity, Value) -> Result<(), serde_json::Error>;
+
+type SaveResource = fn(&ResourceRegistry) -> Option<Result<Value, serde_json::Error>>;
+
+// Deserializes a resource, then sets it once the whole scene is known to load
+type LoadResource = fn(Value) -> Result<SetResource, serde_json::Error>;
+
+type SetResource = Box<dyn FnOnce(&mut ResourceRegistry)>;
+
+type LoadHook = Box<dyn FnMut(&mut ECS, &[EntityId])>;
+
+struct ComponentType {
+    name: &'static str,
+    save: SaveComponent,
+    load: LoadComponent,
+}
+
+struct ResourceType {
+    save: SaveResource,
+    load: LoadResource,
+}
+
+// Components and resources that take part in scenes. Anything else, like GL handles, is left
+// out and rebuilt by the hooks given to `ECS::on_scene_load`
+#[derive(Default)]
+pub(crate) struct SceneRegistry {
+    components: HashMap<TypeId, ComponentType>,
+    component_names: HashMap<&'static str, TypeId>,
+    resources: BTreeMap<&'static str, ResourceType>,
+    on_load: Vec<LoadHook>,
+}
+
+impl SceneRegistry {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn value_error(name: &str) -> impl FnOnce(serde_json::Error) -> SceneError + '_ {
+    move |error| SceneError::Value {
+        name: name.to_string(),
+        error,
+    }
+}
+
+impl ECS {
+    // `name` is what the component is stored as in scenes, it has to stay the same across
+    // versions for old scenes to load
+    pub fn register_component<T>(&mut self, name: &'static str)
+    where
+        T: Serialize + DeserializeOwned + 'static,
+    {
+        let scene_types = &mut self.scene_types;
+        scene_types.component_names.insert(name, TypeId::of::<T>());
+        scene_types.components.insert(
+            TypeId::of::<T>(),
+            ComponentType {
+                name,
+                save: |value| serde_json::to_value(value.downcast_ref::<T>().unwrap()),
+                load: |entity, value| {
+                    entity.set(serde_json::from_value::<T>(value)?);
+                    Ok(())
+                },
+            },
+        );
+    }
+
+    pub fn register_resource<T>(&mut self, name: &'static str)
+    where
+        T: Serialize + DeserializeOwned + 'static,
+    {
+        self.scene_types.resources.insert(
+            name,
+            ResourceType {
+                save: |resources| {
+                    resources
+                        .get::<T>()
+                        .map(|value| serde_json::to_value(&*value))
+                },
+                load: |value| {
+                    let resource = serde_json::from_value::<T>(value)?;
+                    Ok(Box::new(move |resources| resources.set(resource)))
+                },
+            },
+        );
+    }
+
+    // Runs after every `load_scene` with the entities it spawned
+    pub fn on_scene_load<F>(&mut self, hook: F)
+    where
+        F: FnMut(&mut ECS, &[EntityId]) + 'static,
+    {
+        self.scene_types.on_load.push(Box::new(hook));
+    }
+
+    // Saves every entity with at least one registered component, along with its parent when
+    // that one is saved too
+    pub fn save_scene(&self) -> Result<Scene, SceneError> {
+        let mut scene = Scene::default();
+        let mut saved = Vec::new();
+
+        for archetype in self.archetypes.iter() {
+            let columns: Vec<_> = archetype
+                .columns
+                .iter()
+                .filter_map(|(type_id, column)| {
+                    let component = self.scene_types.components.get(type_id)?;
+                    Some((component, column))
+                })
+                .collect();
+            if columns.is_empty() {
+                continue;
+            }
+
+            for (row, id) in archetype.entities().iter().enumerate() {
+                let mut components = BTreeMap::new();
+                for (component, column) in columns.iter() {
+                    let value =
+                        (component.save)(column.row(row)).map_err(value_error(component.name))?;
+                    components.insert(component.name.to_string(), value);
+                }
+
+                saved.push(*id);
+                scene.entities.push(SceneEntity {
+                    parent: None,
+                    components,
+                });
+            }
+        }
+
+        let indices: HashMap<EntityId, usize> = saved
+            .iter()
+            .enumerate()
+            .map(|(index, id)| (*id, index))
+            .collect();
+        for (index, id) in saved.iter().enumerate() {
+            let parent = self.get(*id).and_then(|entity| entity.get::<Parent>());
+            scene.entities[index].parent =
+                parent.and_then(|parent| indices.get(&parent.get()).copied());
+        }
+
+        for (name, resource) in self.scene_types.resources.iter() {
+            if let Some(value) = (resource.save)(&self.resources) {
+                scene
+                    .resources
+                    .insert(name.to_string(), value.map_err(value_error(name))?);
+            }
+        }
+        Ok(scene)
+    }
+
+    // Spawns the entities of `scene` next to the existing ones and replaces the resources it
+    // contains. Nothing is spawned or replaced when one of its entities or resources fails to
+    // load
+    pub fn load_scene(&mut self, scene: Scene) -> Result<Vec<EntityId>, SceneError> {
+        let mut entities = Vec::with_capacity(scene.entities.len());
+        let mut parents = Vec::new();
+
+        for (index, scene_entity) in scene.entities.into_iter().enumerate() {
+            let mut entity = Entity::new();
+            for (name, value) in scene_entity.components {
+                let component = self
+                    .scene_types
+                    .component_names
+                    .get(name.as_str())
+                    .and_then(|type_id| self.scene_types.components.get(type_id))
+                    .ok_or_else(|| SceneError::UnknownComponent(name.clone()))?;
+                (component.load)(&mut entity, value).map_err(value_error(&name))?;
+            }
+
+            if let Some(parent) = scene_entity.parent {
+                parents.push((index, parent));
+            }
+            entities.push(entity);
+        }
+
+        let count = entities.len();
+        if let Some((index, _)) = parents
+            .iter()
+            .find(|(index, parent)| parent == index || *parent >= count)
+        {
+            return Err(SceneError::InvalidParent(*index));
+        }
+
+        let mut resources = Vec::with_capacity(scene.resources.len());
+        for (name, value) in scene.resources {
+            let resource = self
+                .scene_types
+                .resources
+                .get(name.as_str())
+                .ok_or_else(|| SceneError::UnknownResource(name.clone()))?;
+            resources.push((resource.load)(value).map_err(value_error(&name))?);
+        }
+
+        for set in resources {
+            set(&mut self.resources);
+        }
+
+        let ids: Vec<EntityId> = entities
+            .into_iter()
+            .map(|entity| self.add_entity(entity))
+            .collect();
+        for (index, parent) in parents {
+            self.set_parent(ids[index], ids[parent]);
+        }
+
+        let mut hooks = mem::take(&mut self.scene_types.on_load);
+        for hook in hooks.iter_mut() {
+            hook(self, &ids);
+        }
+        hooks.append(&mut self.scene_types.on_load);
+        self.scene_types.on_load = hooks;
+
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Health(u32);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Name(String);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Score(u32);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Seed(u64);
+
+    // Never registered, so it stays out of scenes
+    struct Handle;
+
+    fn registered() -> ECS {
+        let mut ecs = ECS::new();
+        ecs.register_component::<Health>("health");
+        ecs.register_component::<Name>("name");
+        ecs.register_resource::<Score>("score");
+        ecs.register_resource::<Seed>("seed");
+        ecs
+    }
+
+    fn spawn(ecs: &mut ECS, health: u32, name: &str) -> EntityId {
+        let mut entity = Entity::new();
+        entity.set(Health(health));
+        entity.set(Name(name.to_string()));
+        ecs.add_entity(entity)
+    }
+
+    #[test]
+    fn round_trip_keeps_components_parents_and_resources() {
+        let mut ecs = registered();
+        let parent = spawn(&mut ecs, 10, "parent");
+        let child = spawn(&mut ecs, 5, "child");
+        ecs.set_parent(child, parent);
+        let mut entity = Entity::new();
+        entity.set(Handle);
+        ecs.add_entity(entity);
+        ecs.resources.set(Score(42));
+
+        let json = ecs.save_scene().unwrap().to_json().unwrap();
+        assert_eq!(ecs.save_scene().unwrap().entities.len(), 2);
+
+        let mut loaded = registered();
+        let ids = loaded.load_scene(Scene::from_json(&json).unwrap()).unwrap();
+        assert_eq!(ids.len(), 2);
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(*loaded.resources.get::<Score>().unwrap(), Score(42));
+        assert!(loaded.resources.get::<Seed>().is_none());
+
+        let child = ids
+            .iter()
+            .copied()
+            .find(|id| loaded.get(*id).unwrap().get::<Name>() == Some(&Name("child".into())))
+            .unwrap();
+        let parent = loaded.get(child).unwrap().get::<Parent>().unwrap().get();
+        assert_eq!(
+            loaded.get(parent).unwrap().get::<Health>(),
+            Some(&Health(10))
+        );
+    }
+
+    #[test]
+    fn failing_resource_leaves_the_world_untouched() {
+        let mut ecs = registered();
+        ecs.resources.set(Score(1));
+        ecs.resources.set(Seed(2));
+
+        let mut scene = Scene::default();
+        scene.entities.push(SceneEntity {
+            parent: None,
+            components: vec![("health".to_string(), serde_json::json!(3))]
+                .into_iter()
+                .collect(),
+        });
+        // `score` comes first and is valid, `seed` is not
+        scene
+            .resources
+            .insert("score".to_string(), serde_json::json!(100));
+        scene
+            .resources
+            .insert("seed".to_string(), serde_json::json!("not a number"));
+
+        match ecs.load_scene(scene) {
+            Err(SceneError::Value { name, .. }) => assert_eq!(name, "seed"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(ecs.len(), 0);
+        assert_eq!(*ecs.resources.get::<Score>().unwrap(), Score(1));
+        assert_eq!(*ecs.resources.get::<Seed>().unwrap(), Seed(2));
+    }
+
+    #[test]
+    fn unknown_names_and_parents_are_errors() {
+        let mut ecs = registered();
+
+        let mut scene = Scene::default();
+        scene.entities.push(SceneEntity {
+            parent: None,
+            components: vec![("mana".to_string(), serde_json::json!(1))]
+                .into_iter()
+                .collect(),
+        });
+        assert!(matches!(
+            ecs.load_scene(scene),
+            Err(SceneError::UnknownComponent(name)) if name == "mana"
+        ));
+
+        let mut scene = Scene::default();
+        scene
+            .resources
+            .insert("weather".to_string(), serde_json::json!(null));
+        assert!(matches!(
+            ecs.load_scene(scene),
+            Err(SceneError::UnknownResource(name)) if name == "weather"
+        ));
+
+        let mut scene = Scene::default();
+        scene.entities.push(SceneEntity {
+            parent: Some(1),
+            components: BTreeMap::new(),
+        });
+        assert!(matches!(
+            ecs.load_scene(scene),
+            Err(SceneError::InvalidParent(0))
+        ));
+        assert_eq!(ecs.len(), 0);
+    }
+
+    #[test]
+    fn load_hooks_get_the_spawned_entities() {
+        let mut ecs = registered();
+        spawn(&mut ecs, 1, "a");
+        let scene = ecs.save_scene().unwrap();
+
+        let seen = Rc::new(Cell::new(0));
+        let counter = seen.clone();
+        ecs.on_scene_load(move |ecs, ids| {
+            assert!(ids.iter().all(|id| ecs.is_alive(*id)));
+            counter.set(counter.get() + ids.len());
+        });
+
+        ecs.load_scene(scene).unwrap();
+        assert_eq!(seen.get(), 1);
+        assert_eq!(ecs.len(), 2);
+    }
+}
@@ -0,0 +1,235 @@
+use std::fmt;
+
+// Labels of a system and the labels it has to run before or after, within its phase
+#[derive(Debug, Default, Clone)]
+pub struct SystemOrder {
+    pub(crate) labels: Vec<&'static str>,
+    pub(crate) before: Vec<&'static str>,
+    pub(crate) after: Vec<&'static str>,
+}
+
+impl SystemOrder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn labels(&self) -> &[&'static str] {
+        &self.labels
+    }
+}
+
+#[derive(Debug)]
+pub enum ScheduleError {
+    MissingLabel {
+        system: &'static str,
+        label: &'static str,
+    },
+    Cycle {
+        systems: Vec<&'static str>,
+    },
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScheduleError::MissingLabel { system, label } => write!(
+                f,
+                "System `{}` is ordered against label `{}`, which no system of its phase has",
+                system, label
+            ),
+            ScheduleError::Cycle { systems } => {
+                write!(
+                    f,
+                    "Systems form an ordering cycle: {}",
+                    systems.join(" -> ")
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+// A phase runs its stages in order: before services, entity systems, after services
+pub(crate) type Stage<'a> = Vec<(&'static str, &'a SystemOrder)>;
+
+// Registration order of `stages[stage]` sorted so every `before`/`after` holds, stable otherwise
+pub(crate) fn sort_stage(stages: &[Stage], stage: usize) -> Result<Vec<usize>, ScheduleError> {
+    let nodes = &stages[stage];
+    let mut edges: Vec<Vec<usize>> = vec![Vec::new(); nodes.len()];
+    let mut incoming = vec![0; nodes.len()];
+
+    for (index, (name, order)) in nodes.iter().enumerate() {
+        let constraints = order
+            .after
+            .iter()
+            .map(|label| (*label, true))
+            .chain(order.before.iter().map(|label| (*label, false)));
+
+        for (label, after) in constraints {
+            let mut found = false;
+
+            for (other_stage, others) in stages.iter().enumerate() {
+                for (other, (other_name, other_order)) in others.iter().enumerate() {
+                    if !other_order.labels.contains(&label) {
+                        continue;
+                    }
+                    found = true;
+
+                    if other_stage == stage {
+                        if other == index {
+                            continue;
+                        }
+                        let (from, to) = if after {
+                            (other, index)
+                        } else {
+                            (index, other)
+                        };
+                        edges[from].push(to);
+                        incoming[to] += 1;
+                    } else if after == (other_stage > stage) {
+                        // The stages already run in the opposite order
+                        return Err(ScheduleError::Cycle {
+                            systems: vec![name, other_name],
+                        });
+                    }
+                }
+            }
+
+            if !found {
+                return Err(ScheduleError::MissingLabel {
+                    system: name,
+                    label,
+                });
+            }
+        }
+    }
+
+    let mut sorted = Vec::with_capacity(nodes.len());
+    let mut done = vec![false; nodes.len()];
+
+    while sorted.len() < nodes.len() {
+        let next = (0..nodes.len()).find(|index| !done[*index] && incoming[*index] == 0);
+
+        let next = match next {
+            Some(next) => next,
+            None => {
+                return Err(ScheduleError::Cycle {
+                    systems: (0..nodes.len())
+                        .filter(|index| !done[*index])
+                        .map(|index| nodes[index].0)
+                        .collect(),
+                })
+            }
+        };
+
+        done[next] = true;
+        sorted.push(next);
+        for to in &edges[next] {
+            incoming[*to] -= 1;
+        }
+    }
+
+    Ok(sorted)
+}
+
+// Whether either system has a `before`/`after` constraint on a label of the other
+pub(crate) fn is_ordered(a: &SystemOrder, b: &SystemOrder) -> bool {
+    let targets = |from: &SystemOrder, to: &SystemOrder| {
+        from.before
+            .iter()
+            .chain(from.after.iter())
+            .any(|label| to.labels.contains(label))
+    };
+    targets(a, b) || targets(b, a)
+}
+
+pub(crate) fn reorder<T>(items: &mut Vec<T>, order: &[usize]) {
+    let mut slots: Vec<Option<T>> = items.drain(..).map(Some).collect();
+    items.extend(order.iter().map(|index| slots[*index].take().unwrap()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(
+        labels: &[&'static str],
+        before: &[&'static str],
+        after: &[&'static str],
+    ) -> SystemOrder {
+        SystemOrder {
+            labels: labels.to_vec(),
+            before: before.to_vec(),
+            after: after.to_vec(),
+        }
+    }
+
+    fn stage<'a>(orders: &'a [(&'static str, SystemOrder)]) -> Stage<'a> {
+        orders.iter().map(|(name, order)| (*name, order)).collect()
+    }
+
+    #[test]
+    fn constraints_reorder_and_the_rest_keeps_its_place() {
+        let orders = [
+            ("a", order(&[], &[], &["c"])),
+            ("b", order(&["b"], &[], &[])),
+            ("c", order(&["c"], &[], &[])),
+            ("d", order(&[], &["b"], &[])),
+        ];
+        let stages = [Vec::new(), stage(&orders), Vec::new()];
+
+        assert_eq!(sort_stage(&stages, 1).unwrap(), [2, 0, 3, 1]);
+    }
+
+    #[test]
+    fn unknown_labels_and_cycles_are_errors() {
+        let orders = [("a", order(&[], &["missing"], &[]))];
+        let stages = [Vec::new(), stage(&orders), Vec::new()];
+        let result = sort_stage(&stages, 1);
+        assert!(matches!(
+            result,
+            Err(ScheduleError::MissingLabel {
+                label: "missing",
+                ..
+            })
+        ));
+
+        let orders = [
+            ("a", order(&["a"], &[], &["b"])),
+            ("b", order(&["b"], &[], &["a"])),
+            ("c", order(&[], &[], &[])),
+        ];
+        let stages = [Vec::new(), stage(&orders), Vec::new()];
+        match sort_stage(&stages, 1) {
+            Err(ScheduleError::Cycle { systems }) => assert_eq!(systems, ["a", "b"]),
+            result => panic!("Expected a cycle, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn labels_of_other_stages_only_hold_in_stage_order() {
+        let before = [("before", order(&["before"], &[], &[]))];
+        let systems = [("system", order(&[], &[], &["before"]))];
+        let stages = [stage(&before), stage(&systems), Vec::new()];
+        assert!(sort_stage(&stages, 1).is_ok());
+
+        let systems = [("system", order(&[], &["before"], &[]))];
+        let stages = [stage(&before), stage(&systems), Vec::new()];
+        assert!(matches!(
+            sort_stage(&stages, 1),
+            Err(ScheduleError::Cycle { .. })
+        ));
+    }
+
+    #[test]
+    fn ordered_systems_know_it() {
+        let a = order(&["a"], &[], &[]);
+        let b = order(&["b"], &["a"], &[]);
+        let c = order(&["c"], &[], &[]);
+
+        assert!(is_ordered(&a, &b));
+        assert!(is_ordered(&b, &a));
+        assert!(!is_ordered(&a, &c));
+    }
+}
@@ -1,15 +1,26 @@
 use std::any::TypeId;
 
 #[derive(Debug)]
-pub struct EntityShape(pub(crate) Vec<TypeId>);
+pub struct EntityShape {
+    pub(crate) include: Vec<TypeId>,
+    pub(crate) exclude: Vec<TypeId>,
+}
 
 impl EntityShape {
     pub fn new() -> Self {
-        Self(Vec::new())
+        Self {
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
     }
     pub fn add(&mut self, type_id: TypeId) {
-        if !self.0.contains(&type_id) {
-            self.0.push(type_id);
+        if !self.include.contains(&type_id) {
+            self.include.push(type_id);
+        }
+    }
+    pub fn exclude(&mut self, type_id: TypeId) {
+        if !self.exclude.contains(&type_id) {
+            self.exclude.push(type_id);
         }
     }
 }
@@ -26,6 +37,11 @@ impl EntityShapeBuilder {
         self
     }
 
+    pub fn without<T: 'static>(mut self) -> Self {
+        self.0.exclude(TypeId::of::<T>());
+        self
+    }
+
     pub fn build(self) -> EntityShape {
         self.0
     }
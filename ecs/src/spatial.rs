@@ -0,0 +1,595 @@
+use super::{Changed, EntityId, GlobalTransform, ResourceRegistry, Transform, With, ECS};
+use math::Vec3;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+type Cell = [i32; 3];
+
+// Most cells a box is spread over. Larger boxes are kept aside and tested by every query, and
+// larger query areas scan the entries instead of walking their cells
+const MAX_CELLS: i64 = 4096;
+
+// Half size of the box an entity takes in the `SpatialIndex`, in world units. Entities with a
+// `Transform` but no `Bounds` are indexed as points
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Bounds {
+    pub half_extents: Vec3,
+}
+
+impl Bounds {
+    pub fn new(half_extents: Vec3) -> Self {
+        Self { half_extents }
+    }
+
+    pub fn cube(half_size: f32) -> Self {
+        Self::new(Vec3::from_scalar(half_size))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Aabb {
+    pub fn new(min: &Vec3, max: &Vec3) -> Self {
+        Self {
+            min: [min.x, min.y, min.z],
+            max: [max.x, max.y, max.z],
+        }
+    }
+
+    pub fn from_center(center: &Vec3, half_extents: &Vec3) -> Self {
+        Self {
+            min: [
+                center.x - half_extents.x,
+                center.y - half_extents.y,
+                center.z - half_extents.z,
+            ],
+            max: [
+                center.x + half_extents.x,
+                center.y + half_extents.y,
+                center.z + half_extents.z,
+            ],
+        }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        Vec3::from_components(
+            (self.min[0] + self.max[0]) / 2.0,
+            (self.min[1] + self.max[1]) / 2.0,
+            (self.min[2] + self.max[2]) / 2.0,
+        )
+    }
+
+    pub fn contains(&self, point: &Vec3) -> bool {
+        let point = [point.x, point.y, point.z];
+        (0..3).all(|axis| self.min[axis] <= point[axis] && point[axis] <= self.max[axis])
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] <= other.max[axis] && other.min[axis] <= self.max[axis])
+    }
+
+    fn distance_squared(&self, point: &Vec3) -> f32 {
+        let point = [point.x, point.y, point.z];
+        (0..3)
+            .map(|axis| {
+                let closest = point[axis].max(self.min[axis]).min(self.max[axis]);
+                (point[axis] - closest) * (point[axis] - closest)
+            })
+            .sum()
+    }
+
+    // Distance along the normalized `direction` at which the ray enters the box, 0 from inside
+    fn ray_distance(&self, origin: &[f32; 3], direction: &[f32; 3]) -> Option<f32> {
+        let (mut near, mut far) = (0.0f32, f32::INFINITY);
+        for axis in 0..3 {
+            if direction[axis] == 0.0 {
+                if origin[axis] < self.min[axis] || origin[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            let a = (self.min[axis] - origin[axis]) / direction[axis];
+            let b = (self.max[axis] - origin[axis]) / direction[axis];
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+            if near > far {
+                return None;
+            }
+        }
+        Some(near)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub entity: EntityId,
+    pub distance: f32,
+}
+
+// Uniform grid over the entities with a `Transform`, placed at their `GlobalTransform` when they
+// have one. Kept up to date by `ECS::update_spatial_index`
+pub struct SpatialIndex {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<EntityId>>,
+    entries: HashMap<EntityId, Aabb>,
+    // Entries over more than `MAX_CELLS` cells, in no cell
+    large: HashSet<EntityId>,
+    // Cells ever used, where ray casts stop walking
+    extent: Option<(Cell, Cell)>,
+}
+
+impl SpatialIndex {
+    pub fn new(cell_size: f32) -> Self {
+        assert!(
+            cell_size > 0.0 && cell_size.is_finite(),
+            "The cell size of a spatial index has to be positive and finite, not {}",
+            cell_size
+        );
+
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            entries: HashMap::new(),
+            large: HashSet::new(),
+            extent: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&Aabb> {
+        self.entries.get(&id)
+    }
+
+    fn cell(&self, point: &[f32; 3]) -> Cell {
+        [
+            (point[0] / self.cell_size).floor() as i32,
+            (point[1] / self.cell_size).floor() as i32,
+            (point[2] / self.cell_size).floor() as i32,
+        ]
+    }
+
+    // `None` past `MAX_CELLS`
+    fn cells_of(&self, aabb: &Aabb) -> Option<impl Iterator<Item = Cell>> {
+        let (min, max) = (self.cell(&aabb.min), self.cell(&aabb.max));
+        let count = (0..3).fold(1i64, |count, axis| {
+            let cells = (i64::from(max[axis]) - i64::from(min[axis]) + 1).max(0);
+            count.saturating_mul(cells)
+        });
+        if count > MAX_CELLS {
+            return None;
+        }
+
+        Some((min[0]..=max[0]).flat_map(move |x| {
+            (min[1]..=max[1]).flat_map(move |y| (min[2]..=max[2]).map(move |z| [x, y, z]))
+        }))
+    }
+
+    // Replaces the box of `id` when it is already indexed
+    pub fn insert(&mut self, id: EntityId, aabb: Aabb) {
+        self.remove(id);
+        self.entries.insert(id, aabb);
+
+        let cells: Vec<Cell> = match self.cells_of(&aabb) {
+            Some(cells) => cells.collect(),
+            None => {
+                self.large.insert(id);
+                return;
+            }
+        };
+        for cell in cells {
+            self.cells.entry(cell).or_default().push(id);
+        }
+
+        let (min, max) = (self.cell(&aabb.min), self.cell(&aabb.max));
+        self.extent = Some(match self.extent {
+            Some((low, high)) => (
+                [low[0].min(min[0]), low[1].min(min[1]), low[2].min(min[2])],
+                [
+                    high[0].max(max[0]),
+                    high[1].max(max[1]),
+                    high[2].max(max[2]),
+                ],
+            ),
+            None => (min, max),
+        });
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> Option<Aabb> {
+        let aabb = self.entries.remove(&id)?;
+        if self.large.remove(&id) {
+            return Some(aabb);
+        }
+
+        let cells: Vec<Cell> = self.cells_of(&aabb).into_iter().flatten().collect();
+        for cell in cells {
+            if let Some(ids) = self.cells.get_mut(&cell) {
+                ids.retain(|other| *other != id);
+                if ids.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+        Some(aabb)
+    }
+
+    // Entities of the cells `aabb` covers whose box passes `test`, each once
+    fn candidates<F>(&self, aabb: &Aabb, test: F) -> Vec<EntityId>
+    where
+        F: Fn(&Aabb) -> bool,
+    {
+        let cells = match self.cells_of(aabb) {
+            Some(cells) => cells,
+            None => {
+                return self
+                    .entries
+                    .iter()
+                    .filter(|(_, aabb)| test(aabb))
+                    .map(|(id, _)| *id)
+                    .collect();
+            }
+        };
+
+        let mut seen = HashSet::new();
+        let mut found: Vec<EntityId> = self
+            .large
+            .iter()
+            .filter(|id| test(&self.entries[id]))
+            .copied()
+            .collect();
+        for cell in cells {
+            for id in self.cells.get(&cell).into_iter().flatten() {
+                if seen.insert(*id) && test(&self.entries[id]) {
+                    found.push(*id);
+                }
+            }
+        }
+        found
+    }
+
+    pub fn at_point(&self, point: &Vec3) -> Vec<EntityId> {
+        let cell = Aabb::from_center(point, &Vec3::new());
+        self.candidates(&cell, |aabb| aabb.contains(point))
+    }
+
+    pub fn in_aabb(&self, area: &Aabb) -> Vec<EntityId> {
+        self.candidates(area, |aabb| aabb.intersects(area))
+    }
+
+    pub fn in_sphere(&self, center: &Vec3, radius: f32) -> Vec<EntityId> {
+        let area = Aabb::from_center(center, &Vec3::from_scalar(radius));
+        self.candidates(&area, |aabb| {
+            aabb.distance_squared(center) <= radius * radius
+        })
+    }
+
+    // Entities hit within `max_distance` of `origin`, nearest first. Walks the grid cell by cell
+    // along the ray, stopping at the last cell that ever held an entity
+    pub fn ray_cast(&self, origin: &Vec3, direction: &Vec3, max_distance: f32) -> Vec<RayHit> {
+        let mut hits = Vec::new();
+        if direction.length_squared() <= 0.0 {
+            return hits;
+        }
+
+        let length = direction.length();
+        let origin = [origin.x, origin.y, origin.z];
+        let direction = [
+            direction.x / length,
+            direction.y / length,
+            direction.z / length,
+        ];
+
+        let hit = |id: &EntityId| {
+            let distance = self.entries[id].ray_distance(&origin, &direction)?;
+            if distance <= max_distance {
+                Some(RayHit {
+                    entity: *id,
+                    distance,
+                })
+            } else {
+                None
+            }
+        };
+        hits.extend(self.large.iter().filter_map(hit));
+
+        let (low, high) = match self.extent {
+            Some(extent) => extent,
+            None => return nearest_first(hits),
+        };
+
+        let mut cell = self.cell(&origin);
+        let mut step = [0; 3];
+        let mut next = [f32::INFINITY; 3];
+        let mut delta = [f32::INFINITY; 3];
+        for axis in 0..3 {
+            if direction[axis] > 0.0 {
+                step[axis] = 1;
+                let boundary = (cell[axis] + 1) as f32 * self.cell_size;
+                next[axis] = (boundary - origin[axis]) / direction[axis];
+                delta[axis] = self.cell_size / direction[axis];
+            } else if direction[axis] < 0.0 {
+                step[axis] = -1;
+                let boundary = cell[axis] as f32 * self.cell_size;
+                next[axis] = (boundary - origin[axis]) / direction[axis];
+                delta[axis] = -self.cell_size / direction[axis];
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let mut distance = 0.0;
+        while distance <= max_distance {
+            for id in self.cells.get(&cell).into_iter().flatten() {
+                if seen.insert(*id) {
+                    hits.extend(hit(id));
+                }
+            }
+
+            let axis = if next[0] < next[1] && next[0] < next[2] {
+                0
+            } else if next[1] < next[2] {
+                1
+            } else {
+                2
+            };
+            distance = next[axis];
+            cell[axis] += step[axis];
+            next[axis] += delta[axis];
+
+            let outside = (0..3).any(|axis| {
+                (step[axis] > 0 && cell[axis] > high[axis])
+                    || (step[axis] < 0 && cell[axis] < low[axis])
+            });
+            if outside || !distance.is_finite() {
+                break;
+            }
+        }
+
+        nearest_first(hits)
+    }
+}
+
+fn nearest_first(mut hits: Vec<RayHit>) -> Vec<RayHit> {
+    hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+    hits
+}
+
+impl ECS {
+    // Inserts the `SpatialIndex` resource with every entity that has a `Transform` so far
+    pub fn add_spatial_index(&mut self, cell_size: f32) {
+        if self.resources.contains::<SpatialIndex>() {
+            return;
+        }
+
+        let mut index = SpatialIndex::new(cell_size);
+        let ids: Vec<EntityId> = self.query_filtered::<EntityId, With<Transform>>().collect();
+        for id in ids {
+            if let Some(aabb) = self.world_bounds(id) {
+                index.insert(id, aabb);
+            }
+        }
+        self.resources.set(index);
+
+        let unindex = |id, _: &mut Transform, resources: &mut ResourceRegistry| {
+            if let Some(mut index) = resources.get_mut::<SpatialIndex>() {
+                index.remove(id);
+            }
+        };
+        self.on_remove(unindex);
+        self.on_take(unindex);
+        self.on_remove(|id, _: &mut Bounds, resources| {
+            if let Some(mut index) = resources.get_mut::<SpatialIndex>() {
+                if let Some(aabb) = index.get(id).copied() {
+                    index.insert(id, Aabb::from_center(&aabb.center(), &Vec3::new()));
+                }
+            }
+        });
+    }
+
+    fn world_bounds(&self, id: EntityId) -> Option<Aabb> {
+        let entity = self.get(id)?;
+        let transform = entity.get::<Transform>()?;
+
+        let mut center = Vec3::new();
+        match entity.get::<GlobalTransform>() {
+            Some(global) => center.set_from_matrix_position(global.matrix()),
+            None => center.copy_from(&transform.position),
+        }
+        match entity.get::<Bounds>() {
+            Some(bounds) => Some(Aabb::from_center(&center, &bounds.half_extents)),
+            None => Some(Aabb::from_center(&center, &Vec3::new())),
+        }
+    }
+
+    // Moves the entities whose `Transform`, `GlobalTransform` or `Bounds` changed since the last
+    // `clear_trackers`, usually called after `propagate_transforms`
+    pub fn update_spatial_index(&mut self) {
+        if !self.resources.contains::<SpatialIndex>() {
+            return;
+        }
+
+        let mut changed: Vec<EntityId> = self
+            .query_filtered::<EntityId, Changed<Transform>>()
+            .collect();
+        changed.extend(self.query_filtered::<EntityId, Changed<GlobalTransform>>());
+        changed.extend(self.query_filtered::<EntityId, Changed<Bounds>>());
+
+        let updates: Vec<(EntityId, Aabb)> = changed
+            .into_iter()
+            .filter_map(|id| Some((id, self.world_bounds(id)?)))
+            .collect();
+
+        let mut index = self.resources.get_mut::<SpatialIndex>().unwrap();
+        for (id, aabb) in updates {
+            index.insert(id, aabb);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Entity;
+
+    fn spawn(ecs: &mut ECS, x: f32, y: f32, z: f32, half_size: Option<f32>) -> EntityId {
+        let position = Vec3::from_components(x, y, z);
+        let mut entity = Entity::new().with(Transform::from_position(position));
+        if let Some(half_size) = half_size {
+            entity.set(Bounds::cube(half_size));
+        }
+        ecs.add_entity(entity)
+    }
+
+    fn sorted(mut ids: Vec<EntityId>) -> Vec<EntityId> {
+        ids.sort_by_key(|id| format!("{:?}", id));
+        ids
+    }
+
+    #[test]
+    #[should_panic(expected = "positive and finite")]
+    fn zero_cell_size_is_rejected() {
+        SpatialIndex::new(0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "positive and finite")]
+    fn nan_cell_size_is_rejected() {
+        SpatialIndex::new(f32::NAN);
+    }
+
+    #[test]
+    fn point_box_and_sphere_queries() {
+        let mut ecs = ECS::new();
+        let a = spawn(&mut ecs, 0.0, 0.0, 0.0, Some(0.5));
+        let b = spawn(&mut ecs, 3.0, 0.0, 0.0, None);
+        let c = spawn(&mut ecs, -10.0, 4.0, 2.0, Some(1.0));
+        ecs.add_spatial_index(1.0);
+        let index = ecs.resources.get::<SpatialIndex>().unwrap();
+
+        assert_eq!(index.len(), 3);
+        assert_eq!(
+            index.at_point(&Vec3::from_components(0.4, -0.4, 0.0)),
+            vec![a]
+        );
+        assert!(index
+            .at_point(&Vec3::from_components(0.6, 0.0, 0.0))
+            .is_empty());
+
+        let area = Aabb::new(
+            &Vec3::from_components(-1.0, -1.0, -1.0),
+            &Vec3::from_components(3.0, 1.0, 1.0),
+        );
+        assert_eq!(sorted(index.in_aabb(&area)), sorted(vec![a, b]));
+
+        let near_c = Vec3::from_components(-10.0, 6.5, 2.0);
+        assert_eq!(index.in_sphere(&near_c, 1.5), vec![c]);
+        assert!(index.in_sphere(&near_c, 1.0).is_empty());
+    }
+
+    #[test]
+    fn ray_hits_are_sorted_by_distance() {
+        let mut ecs = ECS::new();
+        let far = spawn(&mut ecs, 8.0, 0.0, 0.0, Some(0.5));
+        let near = spawn(&mut ecs, 2.0, 0.0, 0.0, Some(0.5));
+        spawn(&mut ecs, 5.0, 3.0, 0.0, Some(0.5));
+        ecs.add_spatial_index(1.0);
+        let index = ecs.resources.get::<SpatialIndex>().unwrap();
+
+        let origin = Vec3::new();
+        let hits = index.ray_cast(&origin, &Vec3::from_components(2.0, 0.0, 0.0), 100.0);
+        let entities: Vec<EntityId> = hits.iter().map(|hit| hit.entity).collect();
+        assert_eq!(entities, vec![near, far]);
+        assert_eq!(hits[0].distance, 1.5);
+
+        let short = index.ray_cast(&origin, &Vec3::from_components(1.0, 0.0, 0.0), 5.0);
+        assert_eq!(short.len(), 1);
+
+        let backwards = index.ray_cast(&origin, &Vec3::from_components(-1.0, 0.0, 0.0), 100.0);
+        assert!(backwards.is_empty());
+
+        let nan = Vec3::from_components(f32::NAN, 0.0, 0.0);
+        index.ray_cast(&nan, &Vec3::from_components(1.0, 0.0, 0.0), 100.0);
+        index.ray_cast(&origin, &nan, 100.0);
+    }
+
+    #[test]
+    fn huge_boxes_and_areas_skip_the_cell_walk() {
+        let mut ecs = ECS::new();
+        let huge = spawn(&mut ecs, 0.0, 0.0, 0.0, Some(1e6));
+        let small = spawn(&mut ecs, 5.0, 5.0, 5.0, Some(0.5));
+        ecs.add_spatial_index(0.1);
+        let mut index = ecs.resources.get_mut::<SpatialIndex>().unwrap();
+
+        let point = Vec3::from_components(5.0, 5.0, 5.0);
+        assert_eq!(sorted(index.at_point(&point)), sorted(vec![huge, small]));
+        assert_eq!(
+            sorted(index.in_sphere(&point, 1e7)),
+            sorted(vec![huge, small])
+        );
+
+        let origin = Vec3::from_components(5.0, 5.0, -100.0);
+        let hits = index.ray_cast(&origin, &Vec3::from_components(0.0, 0.0, 1.0), 1e3);
+        let entities: Vec<EntityId> = hits.iter().map(|hit| hit.entity).collect();
+        assert_eq!(entities, vec![huge, small]);
+
+        assert!(index.remove(huge).is_some());
+        assert_eq!(index.at_point(&point), vec![small]);
+    }
+
+    #[test]
+    fn index_follows_the_entities() {
+        let mut ecs = ECS::new();
+        let moved = spawn(&mut ecs, 0.0, 0.0, 0.0, Some(0.5));
+        let despawned = spawn(&mut ecs, 10.0, 0.0, 0.0, None);
+        let taken = spawn(&mut ecs, 20.0, 0.0, 0.0, None);
+        let shrunk = spawn(&mut ecs, 30.0, 0.0, 0.0, Some(2.0));
+        ecs.add_spatial_index(1.0);
+        ecs.clear_trackers();
+
+        ecs.get_mut(moved)
+            .unwrap()
+            .get_mut::<Transform>()
+            .unwrap()
+            .position = Vec3::from_components(0.0, 50.0, 0.0);
+        ecs.update_spatial_index();
+        ecs.despawn(despawned);
+        let entity = ecs.take_entity(taken).unwrap();
+        ecs.remove_component::<Bounds>(shrunk);
+
+        let index = ecs.resources.get::<SpatialIndex>().unwrap();
+        assert_eq!(index.len(), 2);
+        assert!(index.at_point(&Vec3::new()).is_empty());
+        assert_eq!(
+            index.at_point(&Vec3::from_components(0.0, 50.0, 0.0)),
+            vec![moved]
+        );
+        assert!(index
+            .at_point(&Vec3::from_components(31.0, 0.0, 0.0))
+            .is_empty());
+        assert_eq!(
+            index.at_point(&Vec3::from_components(30.0, 0.0, 0.0)),
+            vec![shrunk]
+        );
+        drop(index);
+
+        let mut other = ECS::new();
+        other.add_spatial_index(1.0);
+        let id = other.add_entity(entity);
+        other.update_spatial_index();
+        let index = other.resources.get::<SpatialIndex>().unwrap();
+        assert_eq!(
+            index.at_point(&Vec3::from_components(20.0, 0.0, 0.0)),
+            vec![id]
+        );
+    }
+}
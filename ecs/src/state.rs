@@ -0,0 +1,259 @@
+use super::ecs::run_services;
+use super::system_param::IntoService;
+use super::{ResourceRegistry, RunSystemPhase, Service, SystemContext, SystemPhase, ECS};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// Current value of an app state like `Loading -> Playing -> Paused`, kept as a resource. `set`
+// only queues the transition, it happens in `ECS::apply_state_transitions`
+pub struct State<S> {
+    current: S,
+    next: Option<S>,
+}
+
+impl<S> State<S> {
+    pub fn new(initial: S) -> Self {
+        Self {
+            current: initial,
+            next: None,
+        }
+    }
+
+    pub fn get(&self) -> &S {
+        &self.current
+    }
+
+    // Setting the current state again does nothing, its `on_enter` services don't run twice
+    pub fn set(&mut self, next: S) {
+        self.next = Some(next);
+    }
+
+    pub fn next(&self) -> Option<&S> {
+        self.next.as_ref()
+    }
+}
+
+// Run condition, true while the state of type `S` is `state`
+pub fn in_state<S>(state: S) -> impl Fn(&ResourceRegistry) -> bool + Send + Sync
+where
+    S: PartialEq + Send + Sync + 'static,
+{
+    move |resources| match resources.get::<State<S>>() {
+        Some(current) => *current.get() == state,
+        None => false,
+    }
+}
+
+pub fn resource_exists<T: 'static>() -> impl Fn(&ResourceRegistry) -> bool + Send + Sync {
+    |resources| resources.contains::<T>()
+}
+
+struct StateServices<S> {
+    entered: bool,
+    on_enter: HashMap<S, Vec<Service>>,
+    on_exit: HashMap<S, Vec<Service>>,
+}
+
+impl<S: Eq + Hash + 'static> StateServices<S> {
+    fn get(services: &mut HashMap<TypeId, Box<dyn Any>>) -> &mut Self {
+        services
+            .entry(TypeId::of::<S>())
+            .or_insert_with(|| {
+                Box::new(Self {
+                    entered: false,
+                    on_enter: HashMap::new(),
+                    on_exit: HashMap::new(),
+                })
+            })
+            .downcast_mut()
+            .unwrap()
+    }
+}
+
+fn apply_transition<S: Clone + Eq + Hash + 'static>(ecs: &mut ECS) {
+    let (previous, current) = {
+        let mut state = ecs.resources.get_mut::<State<S>>().unwrap();
+        let previous = state.current.clone();
+        if let Some(next) = state.next.take() {
+            state.current = next;
+        }
+        (previous, state.current.clone())
+    };
+
+    let services = StateServices::<S>::get(&mut ecs.state_services);
+    let change_tick = &mut ecs.change_tick;
+    let context = SystemContext::new(
+        &mut ecs.archetypes,
+        &mut ecs.resources,
+        &RunSystemPhase::Update,
+    );
+
+    let mut run = |services: &mut HashMap<S, Vec<Service>>, state: &S| {
+        if let Some(services) = services.get_mut(state) {
+            run_services(services, &context, change_tick);
+        }
+    };
+
+    if !services.entered {
+        services.entered = true;
+        run(&mut services.on_enter, &previous);
+    }
+    if previous != current {
+        run(&mut services.on_exit, &previous);
+        run(&mut services.on_enter, &current);
+    }
+
+    ecs.apply_commands();
+}
+
+impl ECS {
+    // Inserts the `State<S>` resource, the `on_enter` services of `initial` run on the first
+    // `apply_state_transitions`
+    pub fn add_state<S>(&mut self, initial: S)
+    where
+        S: Clone + Eq + Hash + Send + Sync + 'static,
+    {
+        if self.resources.contains::<State<S>>() {
+            return;
+        }
+        self.resources.set(State::new(initial));
+        self.state_updaters.push(apply_transition::<S>);
+    }
+
+    pub fn on_enter<S, P>(&mut self, state: S, calls: impl IntoService<P>)
+    where
+        S: Clone + Eq + Hash + Send + Sync + 'static,
+    {
+        let mut service = Service::new(SystemPhase::Update, calls.into_service());
+        self.init_service(&mut service);
+        let services = StateServices::<S>::get(&mut self.state_services);
+        services.on_enter.entry(state).or_default().push(service);
+    }
+
+    pub fn on_exit<S, P>(&mut self, state: S, calls: impl IntoService<P>)
+    where
+        S: Clone + Eq + Hash + Send + Sync + 'static,
+    {
+        let mut service = Service::new(SystemPhase::Update, calls.into_service());
+        self.init_service(&mut service);
+        let services = StateServices::<S>::get(&mut self.state_services);
+        services.on_exit.entry(state).or_default().push(service);
+    }
+
+    // Moves every state to the one queued by `State::set`, running the `on_exit` services of the
+    // old state and then the `on_enter` ones of the new. One transition per state and call,
+    // usually made once per frame
+    pub fn apply_state_transitions(&mut self) {
+        for update in self.state_updaters.clone() {
+            update(self);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Commands, Entity, NonSendMut, ResMut};
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    enum Mode {
+        Menu,
+        Playing,
+        Paused,
+    }
+
+    struct Log(Vec<&'static str>);
+    struct Ticks(u32);
+    struct Spawned;
+
+    fn world() -> ECS {
+        let mut ecs = ECS::new();
+        ecs.resources.set(Log(Vec::new()));
+        ecs.add_state(Mode::Menu);
+        ecs.on_enter(Mode::Menu, |mut log: ResMut<Log>| log.0.push("enter menu"));
+        ecs.on_exit(Mode::Menu, |mut log: ResMut<Log>| log.0.push("exit menu"));
+        ecs.on_enter(Mode::Playing, |mut log: ResMut<Log>| {
+            log.0.push("enter playing")
+        });
+        ecs.on_exit(Mode::Playing, |mut log: ResMut<Log>| {
+            log.0.push("exit playing")
+        });
+        ecs
+    }
+
+    fn queue(ecs: &ECS, mode: Mode) {
+        ecs.resources.get_mut::<State<Mode>>().unwrap().set(mode);
+    }
+
+    fn set(ecs: &mut ECS, mode: Mode) {
+        queue(ecs, mode);
+        ecs.apply_state_transitions();
+    }
+
+    fn take_log(ecs: &mut ECS) -> Vec<&'static str> {
+        std::mem::take(&mut ecs.resources.get_mut::<Log>().unwrap().0)
+    }
+
+    #[test]
+    fn transitions_exit_the_old_state_and_enter_the_new() {
+        let mut ecs = world();
+        ecs.apply_state_transitions();
+        ecs.apply_state_transitions();
+        assert_eq!(take_log(&mut ecs), ["enter menu"]);
+
+        set(&mut ecs, Mode::Playing);
+        assert_eq!(take_log(&mut ecs), ["exit menu", "enter playing"]);
+        let state = ecs.resources.get::<State<Mode>>().unwrap();
+        assert_eq!(*state.get(), Mode::Playing);
+        drop(state);
+
+        set(&mut ecs, Mode::Playing);
+        assert!(take_log(&mut ecs).is_empty());
+    }
+
+    #[test]
+    fn the_last_queued_state_wins() {
+        let mut ecs = world();
+        ecs.apply_state_transitions();
+        take_log(&mut ecs);
+
+        queue(&ecs, Mode::Playing);
+        queue(&ecs, Mode::Paused);
+        ecs.apply_state_transitions();
+
+        assert_eq!(take_log(&mut ecs), ["exit menu"]);
+        let state = ecs.resources.get::<State<Mode>>().unwrap();
+        assert_eq!((state.get(), state.next()), (&Mode::Paused, None));
+    }
+
+    #[test]
+    fn conditions_follow_the_state() {
+        let mut ecs = world();
+        ecs.resources.set(Ticks(0));
+        let tick = |mut ticks: ResMut<Ticks>| ticks.0 += 1;
+        ecs.add_before_service(Service::at_tick(tick).run_if(in_state(Mode::Playing)));
+
+        ecs.run_systems(RunSystemPhase::Tick);
+        set(&mut ecs, Mode::Playing);
+        ecs.run_systems(RunSystemPhase::Tick);
+        ecs.run_systems(RunSystemPhase::Tick);
+        set(&mut ecs, Mode::Paused);
+        ecs.run_systems(RunSystemPhase::Tick);
+
+        assert_eq!(ecs.resources.get::<Ticks>().unwrap().0, 2);
+        assert!(resource_exists::<Ticks>()(&ecs.resources));
+        assert!(!in_state(Mode::Menu)(&ECS::new().resources));
+    }
+
+    #[test]
+    fn commands_of_transitions_are_applied() {
+        let mut ecs = world();
+        ecs.on_enter(Mode::Paused, |mut commands: NonSendMut<Commands>| {
+            commands.spawn(Entity::new().with(Spawned));
+        });
+
+        set(&mut ecs, Mode::Paused);
+        assert_eq!(ecs.query::<&Spawned>().count(), 1);
+    }
+}
@@ -1,14 +1,18 @@
-use super::entity::Entity;
+use super::archetype::EntityMut;
+use super::events::Subscription;
 use super::resource_registry::ResourceRegistry;
+use super::schedule::{reorder, sort_stage, ScheduleError, SystemOrder};
 use super::shape::EntityShape;
+use super::system_param::{IntoService, ServiceFn, SystemAccess};
 use anymap::AnyMap;
 use glutin::event::Event;
-use std::any::TypeId;
+use std::any::type_name;
 
 pub type Resources = AnyMap;
 
-type SystemFn = fn(&mut Entity, resourceRegistry: &mut ResourceRegistry, value: &RunSystemPhase);
-type ServiceFn = fn(resourceRegistry: &mut ResourceRegistry, value: &RunSystemPhase);
+type SystemFn = Box<dyn FnMut(&mut EntityMut, &mut ResourceRegistry, &RunSystemPhase)>;
+
+pub(crate) type RunCondition = Box<dyn Fn(&ResourceRegistry) -> bool + Send + Sync>;
 
 pub enum ServicePhase {
     Before,
@@ -24,7 +28,7 @@ pub enum SystemPhase {
 
 #[derive(Clone)]
 pub enum RunSystemPhase {
-    Update(TypeId),
+    Update,
     Event(Event<'static, ()>),
     Tick,
     Render,
@@ -33,82 +37,199 @@ pub enum RunSystemPhase {
 pub struct Service {
     pub(crate) phase: SystemPhase,
     pub(crate) calls: ServiceFn,
-    pub(crate) update_key: Option<TypeId>,
+    pub(crate) access: SystemAccess,
+    pub(crate) order: SystemOrder,
+    pub(crate) subscription: Option<Subscription>,
+    pub(crate) conditions: Vec<RunCondition>,
+    // Tick of the last run, what `Changed` and `Added` compare against
+    pub(crate) last_run: u32,
 }
 
 impl Service {
-    pub fn at_update<T: 'static>(_update_key: T, calls: ServiceFn) -> Self {
+    pub(crate) fn new(phase: SystemPhase, calls: ServiceFn) -> Self {
         Self {
-            phase: SystemPhase::Update,
+            phase,
             calls,
-            update_key: Some(TypeId::of::<T>()),
+            access: SystemAccess::new(),
+            order: SystemOrder::new(),
+            subscription: None,
+            conditions: Vec::new(),
+            last_run: 0,
         }
     }
-    pub fn at_event(calls: ServiceFn) -> Self {
-        Self {
-            phase: SystemPhase::Event,
-            calls,
-            update_key: None,
-        }
+
+    pub fn at_update<T: Send + Sync + 'static, P>(_event: T, calls: impl IntoService<P>) -> Self {
+        let mut service = Self::new(SystemPhase::Update, calls.into_service());
+        service.subscription = Some(Subscription::new::<T>());
+        service
     }
-    pub fn at_tick(calls: ServiceFn) -> Self {
-        Self {
-            phase: SystemPhase::Tick,
-            calls,
-            update_key: None,
-        }
+    pub fn at_event<P>(calls: impl IntoService<P>) -> Self {
+        Self::new(SystemPhase::Event, calls.into_service())
     }
-    pub fn at_render(calls: ServiceFn) -> Self {
-        Self {
-            phase: SystemPhase::Render,
-            calls,
-            update_key: None,
-        }
+    pub fn at_tick<P>(calls: impl IntoService<P>) -> Self {
+        Self::new(SystemPhase::Tick, calls.into_service())
+    }
+    pub fn at_render<P>(calls: impl IntoService<P>) -> Self {
+        Self::new(SystemPhase::Render, calls.into_service())
+    }
+
+    pub fn label(mut self, label: &'static str) -> Self {
+        self.order.labels.push(label);
+        self
+    }
+
+    pub fn before(mut self, label: &'static str) -> Self {
+        self.order.before.push(label);
+        self
+    }
+
+    pub fn after(mut self, label: &'static str) -> Self {
+        self.order.after.push(label);
+        self
+    }
+
+    // Skips the service while any of its conditions is false, see `in_state`
+    pub fn run_if<F>(mut self, condition: F) -> Self
+    where
+        F: Fn(&ResourceRegistry) -> bool + Send + Sync + 'static,
+    {
+        self.conditions.push(Box::new(condition));
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.calls.name
+    }
+
+    pub fn access(&self) -> &SystemAccess {
+        &self.access
+    }
+
+    pub fn order(&self) -> &SystemOrder {
+        &self.order
     }
 }
 
 pub struct System {
     pub(crate) phase: SystemPhase,
     pub(crate) query: EntityShape,
+    pub(crate) name: &'static str,
     pub(crate) calls: SystemFn,
-    pub(crate) update_key: Option<TypeId>,
+    pub(crate) order: SystemOrder,
+    pub(crate) subscription: Option<Subscription>,
+    pub(crate) conditions: Vec<RunCondition>,
+    pub(crate) last_run: u32,
 }
 
 impl System {
-    pub fn at_update<T: 'static>(_event_key: T, query: EntityShape, calls: SystemFn) -> Self {
+    fn new<F>(phase: SystemPhase, query: EntityShape, calls: F) -> Self
+    where
+        F: FnMut(&mut EntityMut, &mut ResourceRegistry, &RunSystemPhase) + 'static,
+    {
         Self {
-            phase: SystemPhase::Update,
+            phase,
             query,
-            calls,
-            update_key: Some(TypeId::of::<T>()),
+            name: type_name::<F>(),
+            calls: Box::new(calls),
+            order: SystemOrder::new(),
+            subscription: None,
+            conditions: Vec::new(),
+            last_run: 0,
         }
     }
-    pub fn at_event(query: EntityShape, calls: SystemFn) -> Self {
-        Self {
-            phase: SystemPhase::Event,
-            query,
-            calls,
-            update_key: None,
-        }
+
+    pub fn at_update<T: Send + Sync + 'static, F>(_event: T, query: EntityShape, calls: F) -> Self
+    where
+        F: FnMut(&mut EntityMut, &mut ResourceRegistry, &RunSystemPhase) + 'static,
+    {
+        let mut system = Self::new(SystemPhase::Update, query, calls);
+        system.subscription = Some(Subscription::new::<T>());
+        system
     }
-    pub fn at_tick(query: EntityShape, calls: SystemFn) -> Self {
-        Self {
-            phase: SystemPhase::Tick,
-            query,
-            calls,
-            update_key: None,
-        }
+    pub fn at_event<F>(query: EntityShape, calls: F) -> Self
+    where
+        F: FnMut(&mut EntityMut, &mut ResourceRegistry, &RunSystemPhase) + 'static,
+    {
+        Self::new(SystemPhase::Event, query, calls)
     }
-    pub fn at_render(query: EntityShape, calls: SystemFn) -> Self {
-        Self {
-            phase: SystemPhase::Render,
-            query,
-            calls,
-            update_key: None,
-        }
+    pub fn at_tick<F>(query: EntityShape, calls: F) -> Self
+    where
+        F: FnMut(&mut EntityMut, &mut ResourceRegistry, &RunSystemPhase) + 'static,
+    {
+        Self::new(SystemPhase::Tick, query, calls)
+    }
+    pub fn at_render<F>(query: EntityShape, calls: F) -> Self
+    where
+        F: FnMut(&mut EntityMut, &mut ResourceRegistry, &RunSystemPhase) + 'static,
+    {
+        Self::new(SystemPhase::Render, query, calls)
+    }
+}
+
+impl System {
+    pub fn label(mut self, label: &'static str) -> Self {
+        self.order.labels.push(label);
+        self
+    }
+
+    pub fn before(mut self, label: &'static str) -> Self {
+        self.order.before.push(label);
+        self
+    }
+
+    pub fn after(mut self, label: &'static str) -> Self {
+        self.order.after.push(label);
+        self
+    }
+
+    pub fn run_if<F>(mut self, condition: F) -> Self
+    where
+        F: Fn(&ResourceRegistry) -> bool + Send + Sync + 'static,
+    {
+        self.conditions.push(Box::new(condition));
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn order(&self) -> &SystemOrder {
+        &self.order
     }
 }
 
+fn sort_phase(
+    before: &mut Vec<Service>,
+    systems: &mut Vec<System>,
+    after: &mut Vec<Service>,
+) -> Result<(), ScheduleError> {
+    let stages = [
+        before
+            .iter()
+            .map(|service| (service.name(), &service.order))
+            .collect(),
+        systems
+            .iter()
+            .map(|system| (system.name, &system.order))
+            .collect(),
+        after
+            .iter()
+            .map(|service| (service.name(), &service.order))
+            .collect(),
+    ];
+    let orders = [
+        sort_stage(&stages, 0)?,
+        sort_stage(&stages, 1)?,
+        sort_stage(&stages, 2)?,
+    ];
+
+    reorder(before, &orders[0]);
+    reorder(systems, &orders[1]);
+    reorder(after, &orders[2]);
+    Ok(())
+}
+
 pub struct SystemRunner {
     pub(crate) update: Vec<System>,
     pub(crate) before_update: Vec<Service>,
@@ -122,6 +243,7 @@ pub struct SystemRunner {
     pub(crate) event: Vec<System>,
     pub(crate) before_event: Vec<Service>,
     pub(crate) after_event: Vec<Service>,
+    pub(crate) dirty: bool,
 }
 
 impl SystemRunner {
@@ -139,10 +261,12 @@ impl SystemRunner {
             event: Vec::new(),
             before_event: Vec::new(),
             after_event: Vec::new(),
+            dirty: false,
         }
     }
 
     pub fn add(&mut self, system: System) {
+        self.dirty = true;
         match system.phase {
             SystemPhase::Update => self.update.push(system),
             SystemPhase::Event => self.event.push(system),
@@ -152,6 +276,7 @@ impl SystemRunner {
     }
 
     pub fn add_service(&mut self, service: Service, phase: ServicePhase) {
+        self.dirty = true;
         match phase {
             ServicePhase::Before => match service.phase {
                 SystemPhase::Update => self.before_update.push(service),
@@ -167,4 +292,26 @@ impl SystemRunner {
             },
         }
     }
+
+    // Orders every phase by the labels of its systems, keeping registration order otherwise
+    pub fn sort(&mut self) -> Result<(), ScheduleError> {
+        sort_phase(
+            &mut self.before_update,
+            &mut self.update,
+            &mut self.after_update,
+        )?;
+        sort_phase(
+            &mut self.before_event,
+            &mut self.event,
+            &mut self.after_event,
+        )?;
+        sort_phase(&mut self.before_tick, &mut self.tick, &mut self.after_tick)?;
+        sort_phase(
+            &mut self.before_render,
+            &mut self.render,
+            &mut self.after_render,
+        )?;
+        self.dirty = false;
+        Ok(())
+    }
 }
@@ -0,0 +1,839 @@
+use super::archetype::Archetype;
+use super::events::{EventReader, EventWriter, Events};
+use super::query::{Access, Fetch, QueryFilter, QueryIter, SendQuery, WorldQuery};
+use super::resource_registry::{Res, ResMut, ResourceRegistry};
+use super::system::RunSystemPhase;
+use std::any::type_name;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+// Everything a service can ask for, valid for a single run of a phase. The ticks are the one
+// the service runs at and the one it last ran at
+pub struct SystemContext {
+    archetypes: *mut Archetype,
+    archetype_count: usize,
+    resources: *mut ResourceRegistry,
+    phase: *const RunSystemPhase,
+    change_tick: u32,
+    last_change_tick: u32,
+}
+
+impl SystemContext {
+    pub(crate) fn new(
+        archetypes: &mut [Archetype],
+        resources: &mut ResourceRegistry,
+        phase: &RunSystemPhase,
+    ) -> Self {
+        Self {
+            archetypes: archetypes.as_mut_ptr(),
+            archetype_count: archetypes.len(),
+            resources,
+            phase,
+            change_tick: 0,
+            last_change_tick: 0,
+        }
+    }
+
+    pub(crate) fn with_ticks(&self, change_tick: u32, last_change_tick: u32) -> Self {
+        Self {
+            change_tick,
+            last_change_tick,
+            ..*self
+        }
+    }
+
+    pub fn phase(&self) -> &RunSystemPhase {
+        unsafe { &*self.phase }
+    }
+
+    pub(crate) fn resources(&self) -> &ResourceRegistry {
+        unsafe { &*self.resources }
+    }
+}
+
+// Services sharing a context on the thread pool never have conflicting access, and the params
+// that hand out non `Send` data keep their service on the main thread
+unsafe impl Sync for SystemContext {}
+
+// Components and resources a service reads and writes, collected at registration
+#[derive(Debug, Default)]
+pub struct SystemAccess {
+    pub(crate) components: Access,
+    pub(crate) resources: Access,
+    pub(crate) all_resources: bool,
+    pub(crate) main_thread: bool,
+}
+
+impl SystemAccess {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Whether the services can run at the same time on different threads
+    pub fn is_compatible(&self, other: &SystemAccess) -> bool {
+        !self.main_thread
+            && !other.main_thread
+            && !self.all_resources
+            && !other.all_resources
+            && self.components.is_compatible(&other.components)
+            && self.resources.is_compatible(&other.resources)
+    }
+
+    pub fn main_thread(&self) -> bool {
+        self.main_thread
+    }
+
+    pub fn components(&self) -> &Access {
+        &self.components
+    }
+
+    pub fn resources(&self) -> &Access {
+        &self.resources
+    }
+}
+
+#[derive(Debug)]
+pub enum SystemError {
+    MissingResource {
+        system: &'static str,
+        resource: &'static str,
+    },
+    ComponentConflict {
+        system: &'static str,
+        component: &'static str,
+    },
+    ResourceConflict {
+        system: &'static str,
+        resource: &'static str,
+    },
+}
+
+impl fmt::Display for SystemError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SystemError::MissingResource { system, resource } => write!(
+                f,
+                "System `{}` requires resource `{}`, which was never inserted",
+                system, resource
+            ),
+            SystemError::ComponentConflict { system, component } => write!(
+                f,
+                "System `{}` borrows component `{}` mutably while borrowing it elsewhere",
+                system, component
+            ),
+            SystemError::ResourceConflict { system, resource } => write!(
+                f,
+                "System `{}` borrows resource `{}` mutably while borrowing it elsewhere",
+                system, resource
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SystemError {}
+
+// A service argument resolved by the ECS on every run: `Res<T>`, `ResMut<T>`, `Option<Res<T>>`,
+// `Option<ResMut<T>>`, `NonSend<T>`, `NonSendMut<T>`, `NonSendMarker`, `Query<Q, F>`,
+// `EventReader<T>`, `EventWriter<T>`, `&RunSystemPhase` or `&mut ResourceRegistry`
+pub trait SystemParam {
+    type Fetch: for<'a> FetchParam<'a>;
+}
+
+// What a param keeps between runs of its service, created when the service is registered
+pub trait ParamState: Send + Sized + 'static {
+    fn init(
+        system: &'static str,
+        access: &mut SystemAccess,
+        resources: &ResourceRegistry,
+    ) -> Result<Self, SystemError>;
+}
+
+pub trait FetchParam<'a>: ParamState {
+    type Item;
+
+    /// # Safety
+    /// The state must come from `init` of the service this is fetched for
+    unsafe fn fetch(&'a mut self, context: &'a SystemContext) -> Self::Item;
+}
+
+fn init_resource<T: 'static>(
+    system: &'static str,
+    access: &mut SystemAccess,
+    resources: &ResourceRegistry,
+    write: bool,
+    required: bool,
+) -> Result<(), SystemError> {
+    if required && !resources.contains::<T>() {
+        return Err(SystemError::MissingResource {
+            system,
+            resource: type_name::<T>(),
+        });
+    }
+
+    let access_result = if write {
+        access.resources.write::<T>()
+    } else {
+        access.resources.read::<T>()
+    };
+
+    if access.all_resources || access_result.is_err() {
+        return Err(SystemError::ResourceConflict {
+            system,
+            resource: type_name::<T>(),
+        });
+    }
+    Ok(())
+}
+
+pub struct FetchRes<T>(PhantomData<fn() -> T>);
+
+pub struct FetchResMut<T>(PhantomData<fn() -> T>);
+
+pub struct FetchOptionRes<T>(PhantomData<fn() -> T>);
+
+pub struct FetchOptionResMut<T>(PhantomData<fn() -> T>);
+
+impl<'x, T: Send + Sync + 'static> SystemParam for Res<'x, T> {
+    type Fetch = FetchRes<T>;
+}
+
+impl<T: Send + Sync + 'static> ParamState for FetchRes<T> {
+    fn init(
+        system: &'static str,
+        access: &mut SystemAccess,
+        resources: &ResourceRegistry,
+    ) -> Result<Self, SystemError> {
+        init_resource::<T>(system, access, resources, false, true)?;
+        Ok(FetchRes(PhantomData))
+    }
+}
+
+impl<'a, T: Send + Sync + 'static> FetchParam<'a> for FetchRes<T> {
+    type Item = Res<'a, T>;
+
+    unsafe fn fetch(&'a mut self, context: &'a SystemContext) -> Self::Item {
+        (*context.resources)
+            .get_ticked::<T>(context.last_change_tick)
+            .unwrap()
+    }
+}
+
+impl<'x, T: Send + Sync + 'static> SystemParam for ResMut<'x, T> {
+    type Fetch = FetchResMut<T>;
+}
+
+impl<T: Send + Sync + 'static> ParamState for FetchResMut<T> {
+    fn init(
+        system: &'static str,
+        access: &mut SystemAccess,
+        resources: &ResourceRegistry,
+    ) -> Result<Self, SystemError> {
+        init_resource::<T>(system, access, resources, true, true)?;
+        Ok(FetchResMut(PhantomData))
+    }
+}
+
+impl<'a, T: Send + Sync + 'static> FetchParam<'a> for FetchResMut<T> {
+    type Item = ResMut<'a, T>;
+
+    unsafe fn fetch(&'a mut self, context: &'a SystemContext) -> Self::Item {
+        (*context.resources)
+            .get_mut_ticked::<T>(context.change_tick, context.last_change_tick)
+            .unwrap()
+    }
+}
+
+impl<'x, T: Send + Sync + 'static> SystemParam for Option<Res<'x, T>> {
+    type Fetch = FetchOptionRes<T>;
+}
+
+impl<T: Send + Sync + 'static> ParamState for FetchOptionRes<T> {
+    fn init(
+        system: &'static str,
+        access: &mut SystemAccess,
+        resources: &ResourceRegistry,
+    ) -> Result<Self, SystemError> {
+        init_resource::<T>(system, access, resources, false, false)?;
+        Ok(FetchOptionRes(PhantomData))
+    }
+}
+
+impl<'a, T: Send + Sync + 'static> FetchParam<'a> for FetchOptionRes<T> {
+    type Item = Option<Res<'a, T>>;
+
+    unsafe fn fetch(&'a mut self, context: &'a SystemContext) -> Self::Item {
+        (*context.resources).get_ticked::<T>(context.last_change_tick)
+    }
+}
+
+impl<'x, T: Send + Sync + 'static> SystemParam for Option<ResMut<'x, T>> {
+    type Fetch = FetchOptionResMut<T>;
+}
+
+impl<T: Send + Sync + 'static> ParamState for FetchOptionResMut<T> {
+    fn init(
+        system: &'static str,
+        access: &mut SystemAccess,
+        resources: &ResourceRegistry,
+    ) -> Result<Self, SystemError> {
+        init_resource::<T>(system, access, resources, true, false)?;
+        Ok(FetchOptionResMut(PhantomData))
+    }
+}
+
+impl<'a, T: Send + Sync + 'static> FetchParam<'a> for FetchOptionResMut<T> {
+    type Item = Option<ResMut<'a, T>>;
+
+    unsafe fn fetch(&'a mut self, context: &'a SystemContext) -> Self::Item {
+        (*context.resources).get_mut_ticked::<T>(context.change_tick, context.last_change_tick)
+    }
+}
+
+// Resources that can't leave the main thread, like the ones wrapping GL or physics handles.
+// Taking one keeps the whole service on the main thread
+pub struct NonSend<'a, T: 'static>(Res<'a, T>);
+
+pub struct NonSendMut<'a, T: 'static>(ResMut<'a, T>);
+
+impl<'a, T: 'static> Deref for NonSend<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<'a, T: 'static> Deref for NonSendMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<'a, T: 'static> DerefMut for NonSendMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+pub struct FetchNonSend<T>(PhantomData<fn() -> T>);
+
+pub struct FetchNonSendMut<T>(PhantomData<fn() -> T>);
+
+impl<'x, T: 'static> SystemParam for NonSend<'x, T> {
+    type Fetch = FetchNonSend<T>;
+}
+
+impl<T: 'static> ParamState for FetchNonSend<T> {
+    fn init(
+        system: &'static str,
+        access: &mut SystemAccess,
+        resources: &ResourceRegistry,
+    ) -> Result<Self, SystemError> {
+        access.main_thread = true;
+        init_resource::<T>(system, access, resources, false, true)?;
+        Ok(FetchNonSend(PhantomData))
+    }
+}
+
+impl<'a, T: 'static> FetchParam<'a> for FetchNonSend<T> {
+    type Item = NonSend<'a, T>;
+
+    unsafe fn fetch(&'a mut self, context: &'a SystemContext) -> Self::Item {
+        NonSend(
+            (*context.resources)
+                .get_ticked::<T>(context.last_change_tick)
+                .unwrap(),
+        )
+    }
+}
+
+impl<'x, T: 'static> SystemParam for NonSendMut<'x, T> {
+    type Fetch = FetchNonSendMut<T>;
+}
+
+impl<T: 'static> ParamState for FetchNonSendMut<T> {
+    fn init(
+        system: &'static str,
+        access: &mut SystemAccess,
+        resources: &ResourceRegistry,
+    ) -> Result<Self, SystemError> {
+        access.main_thread = true;
+        init_resource::<T>(system, access, resources, true, true)?;
+        Ok(FetchNonSendMut(PhantomData))
+    }
+}
+
+impl<'a, T: 'static> FetchParam<'a> for FetchNonSendMut<T> {
+    type Item = NonSendMut<'a, T>;
+
+    unsafe fn fetch(&'a mut self, context: &'a SystemContext) -> Self::Item {
+        NonSendMut(
+            (*context.resources)
+                .get_mut_ticked::<T>(context.change_tick, context.last_change_tick)
+                .unwrap(),
+        )
+    }
+}
+
+// Keeps a service on the main thread without borrowing anything, for services that touch the
+// GL context directly
+pub struct NonSendMarker;
+
+impl SystemParam for NonSendMarker {
+    type Fetch = NonSendMarker;
+}
+
+impl ParamState for NonSendMarker {
+    fn init(
+        _system: &'static str,
+        access: &mut SystemAccess,
+        _resources: &ResourceRegistry,
+    ) -> Result<Self, SystemError> {
+        access.main_thread = true;
+        Ok(NonSendMarker)
+    }
+}
+
+impl<'a> FetchParam<'a> for NonSendMarker {
+    type Item = NonSendMarker;
+
+    unsafe fn fetch(&'a mut self, _context: &'a SystemContext) -> Self::Item {
+        NonSendMarker
+    }
+}
+
+pub struct FetchPhase;
+
+impl SystemParam for &RunSystemPhase {
+    type Fetch = FetchPhase;
+}
+
+impl ParamState for FetchPhase {
+    fn init(
+        _system: &'static str,
+        _access: &mut SystemAccess,
+        _resources: &ResourceRegistry,
+    ) -> Result<Self, SystemError> {
+        Ok(FetchPhase)
+    }
+}
+
+impl<'a> FetchParam<'a> for FetchPhase {
+    type Item = &'a RunSystemPhase;
+
+    unsafe fn fetch(&'a mut self, context: &'a SystemContext) -> Self::Item {
+        &*context.phase
+    }
+}
+
+// The whole registry, for services that can't declare their resources up front
+pub struct FetchResources;
+
+impl SystemParam for &mut ResourceRegistry {
+    type Fetch = FetchResources;
+}
+
+impl ParamState for FetchResources {
+    fn init(
+        system: &'static str,
+        access: &mut SystemAccess,
+        _resources: &ResourceRegistry,
+    ) -> Result<Self, SystemError> {
+        if access.all_resources || !access.resources.is_empty() {
+            return Err(SystemError::ResourceConflict {
+                system,
+                resource: type_name::<ResourceRegistry>(),
+            });
+        }
+        access.all_resources = true;
+        access.main_thread = true;
+        Ok(FetchResources)
+    }
+}
+
+impl<'a> FetchParam<'a> for FetchResources {
+    type Item = &'a mut ResourceRegistry;
+
+    unsafe fn fetch(&'a mut self, context: &'a SystemContext) -> Self::Item {
+        // The service runs alone on the main thread, guards taken from the registry use its ticks
+        let resources = &mut *context.resources;
+        resources.change_tick = context.change_tick;
+        resources.last_change_tick = context.last_change_tick;
+        resources
+    }
+}
+
+pub struct Query<'a, Q: WorldQuery, F: QueryFilter = ()> {
+    archetypes: *mut Archetype,
+    archetype_count: usize,
+    change_tick: u32,
+    last_change_tick: u32,
+    _marker: PhantomData<(&'a mut Archetype, Q, F)>,
+}
+
+impl<'a, Q: WorldQuery, F: QueryFilter> Query<'a, Q, F> {
+    pub fn iter(&mut self) -> QueryIter<'_, Q, F> {
+        // Registration checked that no other query of the service writes what this one accesses
+        unsafe {
+            QueryIter::from_raw(
+                self.archetypes,
+                self.archetype_count,
+                self.change_tick,
+                self.last_change_tick,
+            )
+        }
+    }
+}
+
+impl<'a, 'b, Q: WorldQuery, F: QueryFilter> IntoIterator for &'b mut Query<'a, Q, F> {
+    type Item = <Q::Fetch as Fetch<'b>>::Item;
+    type IntoIter = QueryIter<'b, Q, F>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct FetchQuery<Q, F>(PhantomData<fn() -> (Q, F)>);
+
+impl<'x, Q: SendQuery + 'static, F: QueryFilter + 'static> SystemParam for Query<'x, Q, F> {
+    type Fetch = FetchQuery<Q, F>;
+}
+
+impl<Q: SendQuery + 'static, F: QueryFilter + 'static> ParamState for FetchQuery<Q, F> {
+    fn init(
+        system: &'static str,
+        access: &mut SystemAccess,
+        _resources: &ResourceRegistry,
+    ) -> Result<Self, SystemError> {
+        let conflict = |component| SystemError::ComponentConflict { system, component };
+        let mut query = Access::new();
+        <Q::Fetch as Fetch>::access(&mut query).map_err(conflict)?;
+
+        <Q::Fetch as Fetch>::access(&mut access.components).map_err(conflict)?;
+        F::access(&mut access.components, &query).map_err(conflict)?;
+        Ok(FetchQuery(PhantomData))
+    }
+}
+
+impl<'a, Q: SendQuery + 'static, F: QueryFilter + 'static> FetchParam<'a> for FetchQuery<Q, F> {
+    type Item = Query<'a, Q, F>;
+
+    unsafe fn fetch(&'a mut self, context: &'a SystemContext) -> Self::Item {
+        Query {
+            archetypes: context.archetypes,
+            archetype_count: context.archetype_count,
+            change_tick: context.change_tick,
+            last_change_tick: context.last_change_tick,
+            _marker: PhantomData,
+        }
+    }
+}
+
+pub struct FetchEventReader<T> {
+    cursor: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<'x, T: Send + Sync + 'static> SystemParam for EventReader<'x, T> {
+    type Fetch = FetchEventReader<T>;
+}
+
+impl<T: Send + Sync + 'static> ParamState for FetchEventReader<T> {
+    fn init(
+        system: &'static str,
+        access: &mut SystemAccess,
+        resources: &ResourceRegistry,
+    ) -> Result<Self, SystemError> {
+        init_resource::<Events<T>>(system, access, resources, false, true)?;
+        Ok(FetchEventReader {
+            cursor: 0,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<'a, T: Send + Sync + 'static> FetchParam<'a> for FetchEventReader<T> {
+    type Item = EventReader<'a, T>;
+
+    unsafe fn fetch(&'a mut self, context: &'a SystemContext) -> Self::Item {
+        EventReader {
+            events: (*context.resources)
+                .get_ticked::<Events<T>>(context.last_change_tick)
+                .unwrap(),
+            cursor: &mut self.cursor,
+        }
+    }
+}
+
+pub struct FetchEventWriter<T>(PhantomData<fn() -> T>);
+
+impl<'x, T: Send + Sync + 'static> SystemParam for EventWriter<'x, T> {
+    type Fetch = FetchEventWriter<T>;
+}
+
+impl<T: Send + Sync + 'static> ParamState for FetchEventWriter<T> {
+    fn init(
+        system: &'static str,
+        access: &mut SystemAccess,
+        resources: &ResourceRegistry,
+    ) -> Result<Self, SystemError> {
+        init_resource::<Events<T>>(system, access, resources, true, true)?;
+        Ok(FetchEventWriter(PhantomData))
+    }
+}
+
+impl<'a, T: Send + Sync + 'static> FetchParam<'a> for FetchEventWriter<T> {
+    type Item = EventWriter<'a, T>;
+
+    unsafe fn fetch(&'a mut self, context: &'a SystemContext) -> Self::Item {
+        EventWriter {
+            events: (*context.resources)
+                .get_mut_ticked::<Events<T>>(context.change_tick, context.last_change_tick)
+                .unwrap(),
+        }
+    }
+}
+
+type ServiceCalls = Box<dyn FnMut(&SystemContext) + Send>;
+
+type ServiceInit = Box<
+    dyn FnOnce(&mut SystemAccess, &ResourceRegistry) -> Result<ServiceCalls, SystemError> + Send,
+>;
+
+// A service before and after registration, `init` builds the state of its params once
+pub struct ServiceFn {
+    pub(crate) name: &'static str,
+    init: Option<ServiceInit>,
+    calls: Option<ServiceCalls>,
+}
+
+impl ServiceFn {
+    pub(crate) fn init(
+        &mut self,
+        access: &mut SystemAccess,
+        resources: &ResourceRegistry,
+    ) -> Result<(), SystemError> {
+        if let Some(init) = self.init.take() {
+            self.calls = Some(init(access, resources)?);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn run(&mut self, context: &SystemContext) {
+        match &mut self.calls {
+            Some(calls) => calls(context),
+            None => panic!("Service `{}` ran before it was registered", self.name),
+        }
+    }
+}
+
+// Implemented for every `Send` `FnMut` whose arguments are all `SystemParam`s
+pub trait IntoService<Params> {
+    fn into_service(self) -> ServiceFn;
+}
+
+macro_rules! impl_into_service {
+    ($($param:ident),*) => {
+        impl<Func, $($param: SystemParam),*> IntoService<($($param,)*)> for Func
+        where
+            Func: FnMut($($param),*)
+                + for<'a> FnMut($(<$param::Fetch as FetchParam<'a>>::Item),*)
+                + Send
+                + 'static,
+        {
+            #[allow(non_snake_case, unused_variables, unused_unsafe, unused_mut)]
+            fn into_service(mut self) -> ServiceFn {
+                // Picks the higher ranked `FnMut` impl of `Func`
+                #[allow(clippy::too_many_arguments)]
+                fn call<$($param),*>(mut calls: impl FnMut($($param),*), $($param: $param),*) {
+                    calls($($param),*)
+                }
+
+                let name = type_name::<Func>();
+                let init = move |access: &mut SystemAccess, resources: &ResourceRegistry| {
+                    let mut state =
+                        ($(<$param::Fetch as ParamState>::init(name, access, resources)?,)*);
+                    let calls: ServiceCalls = Box::new(move |context: &SystemContext| unsafe {
+                        let ($($param,)*) = &mut state;
+                        call(&mut self, $(<$param::Fetch as FetchParam>::fetch($param, context)),*)
+                    });
+                    Ok(calls)
+                };
+
+                ServiceFn {
+                    name,
+                    init: Some(Box::new(init)),
+                    calls: None,
+                }
+            }
+        }
+    };
+}
+impl_into_service!();
+impl_into_service!(A);
+impl_into_service!(A, B);
+impl_into_service!(A, B, C);
+impl_into_service!(A, B, C, D);
+impl_into_service!(A, B, C, D, E);
+impl_into_service!(A, B, C, D, E, F);
+impl_into_service!(A, B, C, D, E, F, G);
+impl_into_service!(A, B, C, D, E, F, G, H);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Added, Changed, Entity, EntityId, RunSystemPhase, Service, With, ECS};
+    use std::any::TypeId;
+
+    struct A;
+    struct B;
+    struct Score;
+    struct Window;
+    struct Count(u32);
+
+    fn access_of<P>(
+        calls: impl IntoService<P>,
+        resources: &ResourceRegistry,
+    ) -> Result<SystemAccess, SystemError> {
+        let mut access = SystemAccess::new();
+        calls.into_service().init(&mut access, resources)?;
+        Ok(access)
+    }
+
+    fn resources() -> ResourceRegistry {
+        let mut resources = ResourceRegistry::new();
+        resources.set(Score);
+        resources.set(Window);
+        resources
+    }
+
+    #[test]
+    fn queries_of_a_service_conflict_on_writes() {
+        let resources = resources();
+        let result = access_of(|_: Query<&mut A>, _: Query<&A>| {}, &resources);
+        assert!(matches!(result, Err(SystemError::ComponentConflict { .. })));
+
+        let result = access_of(|_: Query<(&mut A, &B)>, _: Query<&B>| {}, &resources);
+        assert!(result.is_ok());
+
+        let result = access_of(|_: Query<&mut A, With<B>>, _: Query<&mut B>| {}, &resources);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn changed_filter_reads_the_component() {
+        let resources = resources();
+        let result = access_of(
+            |_: Query<&mut A>, _: Query<EntityId, Changed<A>>| {},
+            &resources,
+        );
+        assert!(matches!(result, Err(SystemError::ComponentConflict { .. })));
+
+        let result = access_of(
+            |_: Query<EntityId, Changed<A>>, _: Query<&mut A>| {},
+            &resources,
+        );
+        assert!(matches!(result, Err(SystemError::ComponentConflict { .. })));
+
+        // Within one query the ticks are read row by row, before the row is handed out
+        let access = access_of(|_: Query<&mut A, Changed<A>>| {}, &resources).unwrap();
+        assert_eq!(access.components().writes(), &[TypeId::of::<A>()]);
+
+        let reader = access_of(|_: Query<EntityId, Changed<A>>| {}, &resources).unwrap();
+        let writer = access_of(|_: Query<&mut A>| {}, &resources).unwrap();
+        assert!(!reader.is_compatible(&writer));
+
+        // Insertion ticks only change with the structure of the ECS, outside of any service
+        let added = access_of(|_: Query<EntityId, Added<A>>| {}, &resources).unwrap();
+        assert!(added.is_compatible(&writer));
+    }
+
+    #[test]
+    fn resources_conflict_on_writes() {
+        let resources = resources();
+        let result = access_of(|_: Res<Score>, _: ResMut<Score>| {}, &resources);
+        assert!(matches!(result, Err(SystemError::ResourceConflict { .. })));
+
+        let result = access_of(|_: Res<Score>, _: &mut ResourceRegistry| {}, &resources);
+        assert!(matches!(result, Err(SystemError::ResourceConflict { .. })));
+
+        let result = access_of(|_: Res<A>| {}, &resources);
+        assert!(matches!(result, Err(SystemError::MissingResource { .. })));
+
+        let result = access_of(|_: Option<Res<A>>| {}, &resources);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn compatible_services_share_only_reads() {
+        let resources = resources();
+        let read = access_of(|_: Res<Score>, _: Query<&A>| {}, &resources).unwrap();
+        let other_read = access_of(|_: Res<Score>, _: Query<&A>| {}, &resources).unwrap();
+        let write = access_of(|_: ResMut<Score>| {}, &resources).unwrap();
+        let write_b = access_of(|_: Query<&mut B>| {}, &resources).unwrap();
+
+        assert!(read.is_compatible(&other_read));
+        assert!(!read.is_compatible(&write));
+        assert!(!write.is_compatible(&read));
+        assert!(read.is_compatible(&write_b));
+
+        let registry = access_of(|_: &mut ResourceRegistry| {}, &resources).unwrap();
+        assert!(!registry.is_compatible(&write_b));
+
+        let main_thread = access_of(|_: NonSend<Window>| {}, &resources).unwrap();
+        assert!(main_thread.main_thread());
+        assert!(!main_thread.is_compatible(&write_b));
+    }
+
+    #[test]
+    fn closures_keep_their_state_between_runs() {
+        let mut ecs = ECS::new();
+        ecs.resources.set(Count(0));
+
+        let mut runs = 0;
+        ecs.add_before_service(Service::at_tick(move |mut count: ResMut<Count>| {
+            runs += 1;
+            count.0 = runs * 10;
+        }));
+        for _ in 0..3 {
+            ecs.run_systems(RunSystemPhase::Tick);
+        }
+
+        assert_eq!(ecs.resources.get::<Count>().unwrap().0, 30);
+    }
+
+    #[test]
+    fn params_are_injected_on_every_run() {
+        let mut ecs = ECS::new();
+        ecs.resources.set(Count(0));
+        ecs.add_entity(Entity::new().with(A));
+        ecs.add_entity(Entity::new().with(A).with(B));
+
+        ecs.add_before_service(Service::at_tick(
+            |mut count: ResMut<Count>,
+             mut query: Query<EntityId, With<A>>,
+             window: Option<Res<Window>>,
+             phase: &RunSystemPhase| {
+                assert!(window.is_none());
+                assert!(matches!(phase, RunSystemPhase::Tick));
+                count.0 += query.iter().count() as u32;
+            },
+        ));
+        ecs.run_systems(RunSystemPhase::Tick);
+        ecs.run_systems(RunSystemPhase::Render);
+        ecs.run_systems(RunSystemPhase::Tick);
+
+        assert_eq!(ecs.resources.get::<Count>().unwrap().0, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "which was never inserted")]
+    fn services_need_their_resources_at_registration() {
+        let mut ecs = ECS::new();
+        ecs.add_before_service(Service::at_tick(|_: Res<Count>| {}));
+    }
+}
@@ -0,0 +1,155 @@
+use super::hooks::HookKind;
+use super::{Children, Entity, EntityId, Parent, ECS};
+use std::any::{Any, TypeId};
+
+pub(crate) type CloneComponent = fn(&dyn Any, &mut Entity);
+
+impl ECS {
+    // Components copied by `clone_entity`, the others are left out of the copy
+    pub fn register_clone<T: Clone + 'static>(&mut self) {
+        self.cloners.insert(TypeId::of::<T>(), |value, entity| {
+            entity.set(value.downcast_ref::<T>().unwrap().clone())
+        });
+    }
+
+    // Removes `id` and hands its components back, ready for `add_entity` on this world or another
+    // one. It leaves its hierarchy like a despawned entity, but only the `on_take` hooks run since
+    // the components live on
+    pub fn take_entity(&mut self, id: EntityId) -> Option<Entity> {
+        if !self.is_alive(id) {
+            return None;
+        }
+        self.detach(id);
+
+        let location = self.entities.remove(id.0)?;
+        self.run_hooks(HookKind::Take, id, location.archetype, location.row);
+
+        let archetype = &mut self.archetypes[location.archetype];
+        let (components, moved) = archetype.take_row(location.row);
+        if let Some(moved) = moved {
+            if let Some(moved_location) = self.entities.get_mut(moved.0) {
+                moved_location.row = location.row;
+            }
+        }
+
+        let mut entity = Entity { components };
+        entity.remove::<Parent>();
+        entity.remove::<Children>();
+        Some(entity)
+    }
+
+    // Copy of the components of `id` registered with `register_clone`
+    pub fn clone_entity(&self, id: EntityId) -> Option<Entity> {
+        let location = self.entities.get(id.0)?;
+        let archetype = &self.archetypes[location.archetype];
+
+        let mut entity = Entity::new();
+        for (type_id, column) in archetype.columns.iter() {
+            if let Some(clone) = self.cloners.get(type_id) {
+                clone(column.row(location.row), &mut entity);
+            }
+        }
+        Some(entity)
+    }
+
+    // Moves `id` into `other`, returning its id there. Ids are per world, the old one is dead
+    pub fn move_entity_to(&mut self, id: EntityId, other: &mut ECS) -> Option<EntityId> {
+        let entity = self.take_entity(id)?;
+        Some(other.add_entity(entity))
+    }
+
+    pub fn copy_entity_to(&self, id: EntityId, other: &mut ECS) -> Option<EntityId> {
+        let entity = self.clone_entity(id)?;
+        Some(other.add_entity(entity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{App, Query, RunSystemPhase, Service, SubApp, Transform};
+    use math::Vec3;
+
+    #[derive(Clone)]
+    struct Health(u32);
+    struct Local;
+
+    fn health(ecs: &ECS, id: EntityId) -> Option<u32> {
+        ecs.get(id)?.get::<Health>().map(|health| health.0)
+    }
+
+    #[test]
+    fn moved_entities_keep_their_components() {
+        let (mut from, mut to) = (ECS::new(), ECS::new());
+        let parent = from.add_entity(Entity::new());
+        let id = from.add_entity(Entity::new().with(Health(3)).with(Local));
+        let other = from.add_entity(Entity::new().with(Health(4)).with(Local));
+        from.set_parent(id, parent);
+
+        let moved = from.move_entity_to(id, &mut to).unwrap();
+        assert!(!from.is_alive(id));
+        assert_eq!(health(&from, other), Some(4));
+        let children = from.get(parent).unwrap().get::<Children>().unwrap();
+        assert!(children.is_empty());
+
+        let entity = to.get(moved).unwrap();
+        assert_eq!(health(&to, moved), Some(3));
+        assert!(entity.has::<Local>());
+        assert!(!entity.has::<Parent>());
+        assert!(from.move_entity_to(id, &mut to).is_none());
+    }
+
+    #[test]
+    fn copies_only_hold_registered_components() {
+        let (mut from, mut to) = (ECS::new(), ECS::new());
+        from.register_clone::<Health>();
+        let position = Vec3::from_components(1.0, 2.0, 3.0);
+        let id = from.add_entity(
+            Entity::new()
+                .with(Health(3))
+                .with(Local)
+                .with(Transform::from_position(position)),
+        );
+
+        let copy = from.copy_entity_to(id, &mut to).unwrap();
+        assert_eq!(health(&from, id), Some(3));
+        assert_eq!(health(&to, copy), Some(3));
+
+        let entity = to.get(copy).unwrap();
+        assert!(!entity.has::<Local>());
+        assert_eq!(entity.get::<Transform>().unwrap().position.y, 2.0);
+    }
+
+    fn heal(mut query: Query<&mut Health>) {
+        for mut health in query.iter() {
+            health.0 += 1;
+        }
+    }
+
+    #[test]
+    fn sub_apps_extract_and_run_their_own_schedule() {
+        let mut app = App::new();
+        app.ecs.register_clone::<Health>();
+        let id = app.ecs.add_entity(Entity::new().with(Health(1)));
+
+        let mut sub_ecs = ECS::new();
+        sub_ecs.add_before_service(Service::at_tick(heal));
+        let sub_app = SubApp::new(sub_ecs).with_extract(move |main, sub| {
+            main.copy_entity_to(id, sub);
+        });
+        app.add_sub_app("copy", sub_app);
+        app.build().unwrap();
+
+        app.run_sub_apps(RunSystemPhase::Tick);
+        app.run_sub_apps(RunSystemPhase::Tick);
+
+        let sub = app.sub_app_mut("copy").unwrap();
+        let mut healths: Vec<u32> = sub.query::<&Health>().map(|health| health.0).collect();
+        healths.sort_unstable();
+        assert_eq!(healths, [2, 3]);
+        assert_eq!(health(&app.ecs, id), Some(1));
+
+        assert!(app.remove_sub_app("copy").is_some());
+        assert!(app.sub_app("copy").is_none());
+    }
+}
@@ -0,0 +1,188 @@
+use ecs::{Entity, EntityId, EntityShape, EntityShapeBuilder, RunSystemPhase, System, ECS};
+use proptest::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+struct A;
+struct B;
+struct C;
+struct D;
+
+// Components of an entity or a shape as bits, A = 1, B = 2, C = 4, D = 8
+type Mask = u8;
+
+fn entity(mask: Mask) -> Entity {
+    let mut entity = Entity::new();
+    if mask & 1 != 0 {
+        entity.set(A);
+    }
+    if mask & 2 != 0 {
+        entity.set(B);
+    }
+    if mask & 4 != 0 {
+        entity.set(C);
+    }
+    if mask & 8 != 0 {
+        entity.set(D);
+    }
+    entity
+}
+
+fn shape(with: Mask, without: Mask) -> EntityShape {
+    let mut builder = EntityShapeBuilder::new();
+    if with & 1 != 0 {
+        builder = builder.with::<A>();
+    }
+    if with & 2 != 0 {
+        builder = builder.with::<B>();
+    }
+    if with & 4 != 0 {
+        builder = builder.with::<C>();
+    }
+    if with & 8 != 0 {
+        builder = builder.with::<D>();
+    }
+    if without & 1 != 0 {
+        builder = builder.without::<A>();
+    }
+    if without & 2 != 0 {
+        builder = builder.without::<B>();
+    }
+    if without & 4 != 0 {
+        builder = builder.without::<C>();
+    }
+    if without & 8 != 0 {
+        builder = builder.without::<D>();
+    }
+    builder.build()
+}
+
+fn toggle(ecs: &mut ECS, id: EntityId, bit: Mask, add: bool) {
+    match (bit, add) {
+        (1, true) => drop(ecs.insert_component(id, A)),
+        (2, true) => drop(ecs.insert_component(id, B)),
+        (4, true) => drop(ecs.insert_component(id, C)),
+        (8, true) => drop(ecs.insert_component(id, D)),
+        (1, false) => drop(ecs.remove_component::<A>(id)),
+        (2, false) => drop(ecs.remove_component::<B>(id)),
+        (4, false) => drop(ecs.remove_component::<C>(id)),
+        (8, false) => drop(ecs.remove_component::<D>(id)),
+        _ => unreachable!(),
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Change {
+    Despawn(usize),
+    Toggle(usize, Mask),
+    Spawn(Mask),
+}
+
+fn change() -> impl Strategy<Value = Change> {
+    prop_oneof![
+        any::<usize>().prop_map(Change::Despawn),
+        (any::<usize>(), prop::sample::select(vec![1, 2, 4, 8]))
+            .prop_map(|(index, bit)| Change::Toggle(index, bit)),
+        (0..16u8).prop_map(Change::Spawn),
+    ]
+}
+
+// Entities visited by each shape, in the order the shapes were given
+type Visits = Vec<Rc<RefCell<Vec<EntityId>>>>;
+
+fn add_systems(ecs: &mut ECS, shapes: &[(Mask, Mask)]) -> Visits {
+    shapes
+        .iter()
+        .map(|(with, without)| {
+            let visits = Rc::new(RefCell::new(Vec::new()));
+            let seen = visits.clone();
+            ecs.add_system(System::at_tick(
+                shape(*with, *without),
+                move |entity, _, _| seen.borrow_mut().push(entity.id()),
+            ));
+            visits
+        })
+        .collect()
+}
+
+fn check_visits(
+    visits: &Visits,
+    shapes: &[(Mask, Mask)],
+    alive: &[(EntityId, Mask)],
+) -> Result<(), TestCaseError> {
+    for (visits, (with, without)) in visits.iter().zip(shapes.iter()) {
+        let mut visits = visits.borrow_mut();
+        let visited: HashSet<EntityId> = visits.iter().copied().collect();
+        prop_assert_eq!(visited.len(), visits.len(), "an entity was visited twice");
+
+        let expected: HashSet<EntityId> = alive
+            .iter()
+            .filter(|(_, mask)| mask & with == *with && mask & without == 0)
+            .map(|(id, _)| *id)
+            .collect();
+        prop_assert_eq!(
+            visited,
+            expected,
+            "with {:04b} without {:04b}",
+            with,
+            without
+        );
+        visits.clear();
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn run_systems_visits_matching_entities(
+        masks in prop::collection::vec(0..16u8, 0..64),
+        shapes in prop::collection::vec((0..16u8, 0..16u8), 1..8),
+    ) {
+        let mut ecs = ECS::new();
+        let alive: Vec<(EntityId, Mask)> = masks
+            .iter()
+            .map(|mask| (ecs.add_entity(entity(*mask)), *mask))
+            .collect();
+        let visits = add_systems(&mut ecs, &shapes);
+
+        ecs.run_systems(RunSystemPhase::Tick);
+        check_visits(&visits, &shapes, &alive)?;
+    }
+
+    // Entities moving between archetypes, or leaving holes behind, are still visited once
+    #[test]
+    fn run_systems_follows_changes(
+        masks in prop::collection::vec(0..16u8, 1..64),
+        shapes in prop::collection::vec((0..16u8, 0..16u8), 1..8),
+        changes in prop::collection::vec(change(), 0..64),
+    ) {
+        let mut ecs = ECS::new();
+        let mut alive: Vec<(EntityId, Mask)> = masks
+            .iter()
+            .map(|mask| (ecs.add_entity(entity(*mask)), *mask))
+            .collect();
+        let visits = add_systems(&mut ecs, &shapes);
+
+        for change in changes {
+            match change {
+                Change::Despawn(index) if !alive.is_empty() => {
+                    let (id, _) = alive.remove(index % alive.len());
+                    prop_assert!(ecs.despawn(id));
+                }
+                Change::Toggle(index, bit) if !alive.is_empty() => {
+                    let index = index % alive.len();
+                    let (id, mask) = alive[index];
+                    toggle(&mut ecs, id, bit, mask & bit == 0);
+                    alive[index].1 = mask ^ bit;
+                }
+                Change::Spawn(mask) => alive.push((ecs.add_entity(entity(mask)), mask)),
+                _ => (),
+            }
+
+            ecs.run_systems(RunSystemPhase::Tick);
+            check_visits(&visits, &shapes, &alive)?;
+        }
+        prop_assert_eq!(ecs.len(), alive.len());
+    }
+}
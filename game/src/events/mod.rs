@@ -2,18 +2,20 @@
 // 539855cdb92c44dc0f755e6ea6debcf13a77f049
 // alacritty/alacritty/src/scheduler.rs
 
+use ecs::{Events, ResourceRegistry};
 pub use glutin::event::{ElementState, Event, MouseButton, VirtualKeyCode, WindowEvent};
-use std::any::TypeId;
 use std::time::{Duration, Instant};
 use winit::{
     event_loop::{ControlFlow, EventLoop},
     platform::desktop::EventLoopExtDesktop,
 };
 
+type SendEvent = Box<dyn Fn(&ResourceRegistry) + Send + Sync>;
+
 pub struct Timer {
     pub deadline: Instant,
     pub interval: Duration,
-    pub event: TypeId,
+    pub event: SendEvent,
     pub repeat: bool,
 }
 
@@ -67,28 +69,24 @@ impl EventChannel {
         Self { timers: Vec::new() }
     }
 
-    pub fn schedule<T: 'static>(&mut self, _event: T, interval: Duration, repeat: bool) {
+    // Sends a copy of `event` to the ECS every time the timer fires, `T` has to be added with
+    // `ECS::add_event` first
+    pub fn schedule<T>(&mut self, event: T, interval: Duration, repeat: bool)
+    where
+        T: Clone + Send + Sync + 'static,
+    {
         self.timers.push(Timer {
             deadline: Instant::now() + interval,
             interval,
-            event: TypeId::of::<T>(),
+            event: Box::new(move |resources| {
+                let mut events = resources.get_mut::<Events<T>>().unwrap();
+                events.send(event.clone());
+            }),
             repeat,
         });
     }
 
-    pub fn issue<T: 'static>(&mut self, _event: T) {
-        self.timers.push(Timer {
-            deadline: Instant::now(),
-            interval: Duration::from_nanos(0),
-            event: TypeId::of::<T>(),
-            repeat: false,
-        });
-    }
-
-    pub fn update_timers<F>(&mut self, mut handler: F)
-    where
-        F: FnMut(TypeId),
-    {
+    pub fn update_timers(&mut self, resources: &ResourceRegistry) {
         let now = Instant::now();
 
         let mut invalid = Vec::new();
@@ -96,7 +94,7 @@ impl EventChannel {
 
         for timer in &mut self.timers {
             if timer.deadline < now {
-                handler(timer.event);
+                (timer.event)(resources);
 
                 if timer.repeat {
                     timer.deadline += timer.interval;
@@ -104,10 +102,10 @@ impl EventChannel {
                     invalid.push(index);
                 }
             }
-            index += 0;
+            index += 1;
         }
 
-        for index in invalid {
+        for index in invalid.into_iter().rev() {
             self.timers.remove(index);
         }
     }
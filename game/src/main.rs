@@ -5,40 +5,102 @@ mod modules;
 mod time;
 mod window_context;
 
-use ecs::{RunSystemPhase, ECS};
+use ecs::{App, EntityId, RunSystemPhase, Scene, State};
 use events::{EventChannel, EventSystem};
-use modules::MODULE_LOADER;
+use modules::game_state::GameState;
+use modules::{DebugUiPlugins, DefaultPlugins, WorldPlugins};
 use renderer::RendererDevice;
+use std::fs;
+use std::io;
+use time::TimeContext;
 
 use window_context::WindowContext;
 extern crate bulletrs;
 extern crate cgmath;
 
+const SCENE_PATH: &str = "world.json";
+
 fn main() {
-  let mut ecs = ECS::new();
+  let mut app = App::new();
   let mut event_system = EventSystem::new();
 
   let window_context = WindowContext::from_event_loop(&event_system);
   let renderer = RendererDevice::from_window(&window_context.window_context);
   let event_channel = EventChannel::new();
 
-  ecs.resources.set(renderer);
-  ecs.resources.set(window_context);
-  ecs.resources.set(event_channel);
+  app.ecs.resources.set(renderer);
+  app.ecs.resources.set(window_context);
+  app.ecs.resources.set(event_channel);
+
+  app
+    .add_plugins(DefaultPlugins)
+    .add_plugins(DebugUiPlugins)
+    .add_plugins(WorldPlugins);
 
-  for loader in MODULE_LOADER.iter() {
-    loader(&mut ecs);
+  if let Err(error) = app.build() {
+    panic!("{}", error);
   }
 
+  let ecs = &mut app.ecs;
+
+  // A broken save is reported and replaced by a fresh world instead of keeping the game from
+  // starting, `load_scene` leaves the world untouched when it fails
+  match fs::read_to_string(SCENE_PATH) {
+    Ok(data) => {
+      if let Err(error) = Scene::from_json(&data).and_then(|scene| ecs.load_scene(scene)) {
+        eprintln!("Could not load {}, starting a new world: {}", SCENE_PATH, error);
+      }
+    }
+    Err(error) if error.kind() != io::ErrorKind::NotFound => {
+      eprintln!("Could not read {}, starting a new world: {}", SCENE_PATH, error);
+    }
+    Err(_) => {}
+  }
+
+  ecs.resources.get_mut::<State<GameState>>().unwrap().set(GameState::Playing);
+
   while event_system.running {
+    ecs.apply_state_transitions();
     event_system.update_events(|event| ecs.run_systems(RunSystemPhase::Event(event)));
 
     ecs
       .resources
       .get_mut::<EventChannel>()
       .unwrap()
-      .update_timers(|event| ecs.run_systems(RunSystemPhase::Update(event)));
+      .update_timers(&ecs.resources);
+    ecs.run_systems(RunSystemPhase::Update);
+
+    while ecs.resources.get_mut::<TimeContext>().unwrap().check_update() {
+      ecs.run_systems(RunSystemPhase::Tick);
+    }
 
+    ecs.propagate_transforms();
+    ecs.update_spatial_index();
     ecs.run_systems(RunSystemPhase::Render);
+    ecs.clear_trackers();
+    ecs.update_events();
+  }
+
+  // A failed save is only reported, the shutdown below still has to release the GL resources
+  match ecs.save_scene().and_then(|scene| scene.to_json()) {
+    Ok(data) => {
+      if let Err(error) = fs::write(SCENE_PATH, data) {
+        eprintln!("Could not write {}: {}", SCENE_PATH, error);
+      }
+    }
+    Err(error) => eprintln!("Could not save the world: {}", error),
+  }
+
+  // Despawning runs the `RenderComponent` hooks, anything left after that leaked
+  let entities: Vec<EntityId> = ecs.query::<EntityId>().collect();
+  for id in entities {
+    ecs.despawn(id);
+  }
+  modules::post_process::release(&mut ecs.resources);
+  modules::render_all::release(&mut ecs.resources);
+
+  let leaks = ecs.resources.get_mut::<RendererDevice>().unwrap().shutdown();
+  if !leaks.is_empty() {
+    eprintln!("{}", leaks);
   }
 }
@@ -1,22 +1,25 @@
 use crate::input::keyboard::KeyboardState;
 use crate::internal::FPSControls;
-use crate::modules::debug_info::{DebugInfo, DebugKey};
-use crate::modules::render_all::ViewportUBO;
+use crate::modules::debug_info::{DebugInfo, DebugInfoPlugin, DebugKey};
+use crate::modules::game_state::{GameState, GameStatePlugin};
+use crate::modules::input::InputPlugin;
+use crate::modules::render_all::{RenderAllPlugin, ViewportUBO};
+use crate::modules::time::TimePlugin;
 use crate::time::TimeContext;
-use ecs::{ResourceRegistry, RunSystemPhase, Service, ECS};
-use glutin::event::{ElementState, Event, MouseButton, VirtualKeyCode, WindowEvent};
+use ecs::{in_state, App, Plugin, PluginId, ResourceRegistry, RunSystemPhase, Service};
+use glutin::event::{Event, WindowEvent};
 
 fn emit_events(resources: &mut ResourceRegistry, value: &RunSystemPhase) {
-    let active_controls = resources.get_mut::<FPSControls>().unwrap();
-    let time_context = resources.get_mut::<TimeContext>().unwrap();
-    let keyboard_state = resources.get_mut::<KeyboardState>().unwrap();
-    let dbg_info = resources.get_mut::<DebugInfo>().unwrap();
+    let mut active_controls = resources.get_mut::<FPSControls>().unwrap();
+    let time_context = resources.get::<TimeContext>().unwrap();
+    let keyboard_state = resources.get::<KeyboardState>().unwrap();
+    let mut dbg_info = resources.get_mut::<DebugInfo>().unwrap();
 
     match value {
         RunSystemPhase::Event(event) => match event.clone() {
             Event::MainEventsCleared => {
                 active_controls
-                    .read_last_keyboard(keyboard_state, time_context.last_delta() as f32);
+                    .read_last_keyboard(&keyboard_state, time_context.last_delta() as f32);
 
                 dbg_info.set(
                     DebugKey::CameraPosition,
@@ -30,32 +33,6 @@ fn emit_events(resources: &mut ResourceRegistry, value: &RunSystemPhase) {
                 );
             }
             Event::WindowEvent { event, .. } => match event {
-                WindowEvent::Focused(is_focused) => {
-                    if !is_focused {
-                        active_controls.camera.set_enabled(false);
-                    }
-                }
-                WindowEvent::MouseInput {
-                    state: ElementState::Pressed,
-                    button: MouseButton::Left,
-                    ..
-                } => {
-                    active_controls.camera.set_enabled(true);
-                }
-                WindowEvent::KeyboardInput {
-                    input,
-                    is_synthetic: false,
-                    ..
-                } => {
-                    if let Some(x) = input.virtual_keycode {
-                        if x == VirtualKeyCode::Escape {
-                            active_controls.camera.set_enabled(false);
-                        }
-                    }
-                }
-                WindowEvent::Resized(physical_size) => {
-                    active_controls.camera.on_resize(physical_size);
-                }
                 WindowEvent::CursorMoved { position, .. } => {
                     let h_width = active_controls.camera.f_width / 2.0;
                     let h_height = active_controls.camera.f_height / 2.0;
@@ -73,18 +50,53 @@ fn emit_events(resources: &mut ResourceRegistry, value: &RunSystemPhase) {
     }
 }
 
+fn resize_camera(resources: &mut ResourceRegistry, value: &RunSystemPhase) {
+    let mut active_controls = resources.get_mut::<FPSControls>().unwrap();
+
+    if let RunSystemPhase::Event(Event::WindowEvent {
+        event: WindowEvent::Resized(physical_size),
+        ..
+    }) = value
+    {
+        active_controls.camera.on_resize(*physical_size);
+    }
+}
+
 fn before_frame(resources: &mut ResourceRegistry, _value: &RunSystemPhase) {
-    let active_controls = resources.get_mut::<FPSControls>().unwrap();
-    let viewport_ubo = resources.get_mut::<ViewportUBO>().unwrap();
+    let active_controls = resources.get::<FPSControls>().unwrap();
+    let mut viewport_ubo = resources.get_mut::<ViewportUBO>().unwrap();
+    let matrices = &mut viewport_ubo.data[0];
 
-    active_controls.camera.write_matrix(
-        &mut viewport_ubo.data[0].view,
-        &mut viewport_ubo.data[0].projection,
-    );
+    active_controls
+        .camera
+        .write_matrix(&mut matrices.view, &mut matrices.projection);
 }
 
-pub fn load(ecs: &mut ECS) {
-    ecs.resources.set(FPSControls::new());
-    ecs.add_before_service(Service::at_event(emit_events));
-    ecs.add_before_service(Service::at_render(before_frame));
+pub struct ActiveControlsPlugin;
+
+impl Plugin for ActiveControlsPlugin {
+    fn build(&self, app: &mut App) {
+        let ecs = &mut app.ecs;
+        let mut controls = FPSControls::new();
+        controls.camera.set_enabled(true);
+
+        ecs.resources.set(controls);
+        ecs.add_before_service(
+            Service::at_event(emit_events)
+                .after("input")
+                .run_if(in_state(GameState::Playing)),
+        );
+        ecs.add_before_service(Service::at_event(resize_camera));
+        ecs.add_before_service(Service::at_render(before_frame).label("camera"));
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![
+            PluginId::of::<DebugInfoPlugin>(),
+            PluginId::of::<RenderAllPlugin>(),
+            PluginId::of::<TimePlugin>(),
+            PluginId::of::<InputPlugin>(),
+            PluginId::of::<GameStatePlugin>(),
+        ]
+    }
 }
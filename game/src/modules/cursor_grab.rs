@@ -1,45 +1,28 @@
+use crate::modules::game_state::{GameState, GameStatePlugin};
 use crate::window_context::WindowContext;
-use ecs::{ResourceRegistry, RunSystemPhase, Service, ECS};
+use ecs::{App, Plugin, PluginId, ResourceRegistry, RunSystemPhase};
 
-use glutin::event::{ElementState, Event, MouseButton, VirtualKeyCode, WindowEvent};
+fn grab_cursor(resources: &mut ResourceRegistry, _value: &RunSystemPhase) {
+    let mut window_context = resources.get_mut::<WindowContext>().unwrap();
+    window_context.set_cursor_grab(true);
+}
+
+fn release_cursor(resources: &mut ResourceRegistry, _value: &RunSystemPhase) {
+    let mut window_context = resources.get_mut::<WindowContext>().unwrap();
+    window_context.set_cursor_grab(false);
+}
 
-fn emit_events(resources: &mut ResourceRegistry, value: &RunSystemPhase) {
-    let window_context = resources.get_mut::<WindowContext>().unwrap();
+// The cursor is grabbed only while playing
+pub struct CursorGrabPlugin;
 
-    match value {
-        RunSystemPhase::Event(event) => match event {
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::Focused(is_focused) => {
-                    if !is_focused {
-                        window_context.set_cursor_grab(false);
-                    }
-                }
-                WindowEvent::MouseInput {
-                    state: ElementState::Pressed,
-                    button: MouseButton::Left,
-                    ..
-                } => {
-                    window_context.set_cursor_grab(true);
-                }
-                WindowEvent::KeyboardInput {
-                    input,
-                    is_synthetic: false,
-                    ..
-                } => {
-                    if let Some(key) = input.virtual_keycode {
-                        if key == VirtualKeyCode::Escape {
-                            window_context.set_cursor_grab(false);
-                        }
-                    }
-                }
-                _ => (),
-            },
-            _ => (),
-        },
-        _ => (),
+impl Plugin for CursorGrabPlugin {
+    fn build(&self, app: &mut App) {
+        let ecs = &mut app.ecs;
+        ecs.on_enter(GameState::Playing, grab_cursor);
+        ecs.on_exit(GameState::Playing, release_cursor);
     }
-}
 
-pub fn load(ecs: &mut ECS) {
-    ecs.add_before_service(Service::at_event(emit_events));
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![PluginId::of::<GameStatePlugin>()]
+    }
 }
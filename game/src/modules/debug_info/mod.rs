@@ -1,5 +1,5 @@
 use crate::time::TimeContext;
-use ecs::{ResourceRegistry, RunSystemPhase, Service, ECS};
+use ecs::{App, Plugin, ResourceRegistry, RunSystemPhase, Service};
 use std::collections::hash_map::HashMap;
 use std::hash::Hash;
 
@@ -15,6 +15,7 @@ pub struct DebugInfo {
     map: HashMap<DebugKey, String>,
 }
 
+#[derive(Clone)]
 pub struct FPSUpdateEvent;
 
 impl DebugInfo {
@@ -34,12 +35,18 @@ impl DebugInfo {
 }
 
 fn before_frame(resources: &mut ResourceRegistry, _value: &RunSystemPhase) {
-    let time_context = resources.get_mut::<TimeContext>().unwrap();
-    let dbg_info = resources.get_mut::<DebugInfo>().unwrap();
+    let time_context = resources.get::<TimeContext>().unwrap();
+    let mut dbg_info = resources.get_mut::<DebugInfo>().unwrap();
     dbg_info.set(DebugKey::CurrentFPS, time_context.get_fps().to_string());
 }
 
-pub fn load(ecs: &mut ECS) {
-    ecs.resources.set(DebugInfo::new());
-    ecs.add_before_service(Service::at_render(before_frame));
+pub struct DebugInfoPlugin;
+
+impl Plugin for DebugInfoPlugin {
+    fn build(&self, app: &mut App) {
+        let ecs = &mut app.ecs;
+        ecs.resources.set(DebugInfo::new());
+        ecs.add_event::<FPSUpdateEvent>();
+        ecs.add_before_service(Service::at_render(before_frame));
+    }
 }
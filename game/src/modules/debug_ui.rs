@@ -1,19 +1,30 @@
 use crate::events::EventChannel;
-use crate::modules::debug_info::{DebugInfo, DebugKey, FPSUpdateEvent};
-use ecs::{Entity, EntityShapeBuilder, ResourceRegistry, RunSystemPhase, System, ECS};
+use crate::modules::debug_info::{DebugInfo, DebugInfoPlugin, DebugKey, FPSUpdateEvent};
+use ecs::{
+    App, EntityMut, EntityShapeBuilder, Plugin, PluginId, ResourceRegistry, RunSystemPhase, System,
+};
 use renderer::{RenderComponent, TextureStorage, RendererDevice};
 use skia_safe::Color;
 use std::time::Duration;
 
 pub struct IsDebugUITag;
 
-fn render_ui(entity: &mut Entity, resources: &mut ResourceRegistry, _value: &RunSystemPhase) {
-    let dbg_info = resources.get_mut::<DebugInfo>().unwrap();
+fn render_ui(entity: &mut EntityMut, resources: &mut ResourceRegistry, _value: &RunSystemPhase) {
+    // Nothing to redraw, skip the texture upload
+    if !resources.resource_changed::<DebugInfo>() {
+        return;
+    }
+
+    let dbg_info = resources.get::<DebugInfo>().unwrap();
     let render = entity.get_mut::<RenderComponent>().unwrap();
-    let device = resources.get_mut::<RendererDevice>().unwrap();
+    let mut device = resources.get_mut::<RendererDevice>().unwrap();
+
+    let texture_idx = device
+        .material(render.material)
+        .and_then(|material| material.textures.get(0).cloned());
 
-    if let Some(texture_idx) = render.textures.get_mut(0) {
-        let mut texture = device.get_texture_mut(*texture_idx).unwrap();
+    if let Some(texture_idx) = texture_idx {
+        let mut texture = device.get_texture_mut(texture_idx).unwrap();
         texture.needs_update = true;
         match &mut texture.storage {
             TextureStorage::Canvas2D(canvas) => {
@@ -40,17 +51,37 @@ fn render_ui(entity: &mut Entity, resources: &mut ResourceRegistry, _value: &Run
     }
 }
 
-pub fn load(ecs: &mut ECS) {
-    ecs.add_system(System::at_update(
-        FPSUpdateEvent,
-        EntityShapeBuilder::new()
-            .with::<IsDebugUITag>()
-            .with::<RenderComponent>()
-            .build(),
-        render_ui,
-    ));
+pub struct DebugUiPlugin {
+    // How often the FPS counter is redrawn
+    pub fps_interval: Duration,
+}
+
+impl Default for DebugUiPlugin {
+    fn default() -> Self {
+        Self {
+            fps_interval: Duration::from_millis(1000),
+        }
+    }
+}
+
+impl Plugin for DebugUiPlugin {
+    fn build(&self, app: &mut App) {
+        let ecs = &mut app.ecs;
+        ecs.add_system(System::at_update(
+            FPSUpdateEvent,
+            EntityShapeBuilder::new()
+                .with::<IsDebugUITag>()
+                .with::<RenderComponent>()
+                .build(),
+            render_ui,
+        ));
 
-    let event_channel = ecs.resources.get_mut::<EventChannel>().unwrap();
+        let mut event_channel = ecs.resources.get_mut::<EventChannel>().unwrap();
 
-    event_channel.schedule(FPSUpdateEvent, Duration::from_millis(1000), true);
+        event_channel.schedule(FPSUpdateEvent, self.fps_interval, true);
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![PluginId::of::<DebugInfoPlugin>()]
+    }
 }
@@ -0,0 +1,60 @@
+use ecs::{App, Plugin, ResourceRegistry, RunSystemPhase, Service, State};
+use glutin::event::{ElementState, Event, MouseButton, VirtualKeyCode, WindowEvent};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameState {
+    Loading,
+    Playing,
+    Paused,
+}
+
+fn emit_events(resources: &mut ResourceRegistry, value: &RunSystemPhase) {
+    let mut state = resources.get_mut::<State<GameState>>().unwrap();
+
+    match value {
+        RunSystemPhase::Event(event) => match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::Focused(false) => {
+                    if *state.get() == GameState::Playing {
+                        state.set(GameState::Paused);
+                    }
+                }
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button: MouseButton::Left,
+                    ..
+                } => {
+                    if *state.get() == GameState::Paused {
+                        state.set(GameState::Playing);
+                    }
+                }
+                WindowEvent::KeyboardInput {
+                    input,
+                    is_synthetic: false,
+                    ..
+                } => {
+                    if input.virtual_keycode == Some(VirtualKeyCode::Escape)
+                        && *state.get() == GameState::Playing
+                    {
+                        state.set(GameState::Paused);
+                    }
+                }
+                _ => (),
+            },
+            _ => (),
+        },
+        _ => (),
+    }
+}
+
+// Starts in `Loading`, the game switches to `Playing` once the world is loaded. Focus loss and
+// Escape pause it, a click resumes it
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut App) {
+        let ecs = &mut app.ecs;
+        ecs.add_state(GameState::Loading);
+        ecs.add_before_service(Service::at_event(emit_events));
+    }
+}
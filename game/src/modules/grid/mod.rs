@@ -1,6 +1,7 @@
 mod vertex;
 
-use ecs::{Entity, ECS};
+use crate::modules::render_all::RenderAllPlugin;
+use ecs::{App, Entity, Plugin, PluginId};
 use renderer::{RendererDevice, MeshFlags};
 use std::path::Path;
 use vertex::Vertex;
@@ -9,33 +10,45 @@ const GRID_SIZE: i32 = 10;
 const GRID_STEP: f32 = 4.0;
 const GRID_HEIGHT: f32 = -20.0;
 
-pub fn load(ecs: &mut ECS) {
-    let context = ecs.resources.get_mut::<RendererDevice>().unwrap();
+pub struct GridPlugin;
 
-    let mut vertex_data: Vec<Vertex> = Vec::new();
+impl Plugin for GridPlugin {
+    fn build(&self, app: &mut App) {
+        let ecs = &mut app.ecs;
+        let mut context = ecs.resources.get_mut::<RendererDevice>().unwrap();
 
-    let h_size = (GRID_SIZE as f32 * GRID_STEP) / 2.0;
-    let mut cursor = -h_size;
+        let mut vertex_data: Vec<Vertex> = Vec::new();
 
-    for _ in 0..(GRID_SIZE + 1) {
-        vertex_data.push(Vertex::new(cursor, GRID_HEIGHT, -h_size, 1.0, 0.0, 0.0));
-        vertex_data.push(Vertex::new(cursor, GRID_HEIGHT, h_size, 0.0, 1.0, 0.0));
-        vertex_data.push(Vertex::new(-h_size, GRID_HEIGHT, cursor, 0.0, 0.0, 1.0));
-        vertex_data.push(Vertex::new(h_size, GRID_HEIGHT, cursor, 1.0, 1.0, 0.0));
+        let h_size = (GRID_SIZE as f32 * GRID_STEP) / 2.0;
+        let mut cursor = -h_size;
 
-        vertex_data.push(Vertex::new(cursor, -GRID_HEIGHT, -h_size, 1.0, 0.0, 0.0));
-        vertex_data.push(Vertex::new(cursor, -GRID_HEIGHT, h_size, 0.0, 1.0, 0.0));
-        vertex_data.push(Vertex::new(-h_size, -GRID_HEIGHT, cursor, 0.0, 0.0, 1.0));
-        vertex_data.push(Vertex::new(h_size, -GRID_HEIGHT, cursor, 1.0, 1.0, 0.0));
+        for _ in 0..(GRID_SIZE + 1) {
+            vertex_data.push(Vertex::new(cursor, GRID_HEIGHT, -h_size, 1.0, 0.0, 0.0));
+            vertex_data.push(Vertex::new(cursor, GRID_HEIGHT, h_size, 0.0, 1.0, 0.0));
+            vertex_data.push(Vertex::new(-h_size, GRID_HEIGHT, cursor, 0.0, 0.0, 1.0));
+            vertex_data.push(Vertex::new(h_size, GRID_HEIGHT, cursor, 1.0, 1.0, 0.0));
 
-        cursor += GRID_STEP;
+            vertex_data.push(Vertex::new(cursor, -GRID_HEIGHT, -h_size, 1.0, 0.0, 0.0));
+            vertex_data.push(Vertex::new(cursor, -GRID_HEIGHT, h_size, 0.0, 1.0, 0.0));
+            vertex_data.push(Vertex::new(-h_size, -GRID_HEIGHT, cursor, 0.0, 0.0, 1.0));
+            vertex_data.push(Vertex::new(h_size, -GRID_HEIGHT, cursor, 1.0, 1.0, 0.0));
+
+            cursor += GRID_STEP;
+        }
+
+        let mesh = context.new_mesh(
+            &Path::new("shaders/color.glsl"),
+            vertex_data,
+            None,
+            vec![],
+            MeshFlags::new().lines_mode().opt(),
+        );
+        drop(context);
+
+        ecs.add_entity(Entity::new().with(mesh));
     }
 
-    ecs.add_entity(Entity::new().with(context.new_mesh(
-        &Path::new("shaders/color.glsl"),
-        vertex_data,
-        None,
-        vec![],
-        MeshFlags::new().lines_mode().opt(),
-    )));
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![PluginId::of::<RenderAllPlugin>()]
+    }
 }
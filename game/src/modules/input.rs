@@ -1,10 +1,8 @@
 use crate::input::keyboard::KeyboardState;
-use ecs::{ResourceRegistry, RunSystemPhase, Service, ECS};
+use ecs::{App, Plugin, ResMut, RunSystemPhase, Service};
 use glutin::event::{Event, WindowEvent};
 
-fn emit_events(resources: &mut ResourceRegistry, value: &RunSystemPhase) {
-    let keyboard_state = resources.get_mut::<KeyboardState>().unwrap();
-
+fn emit_events(mut keyboard_state: ResMut<KeyboardState>, value: &RunSystemPhase) {
     match value {
         RunSystemPhase::Event(event) => match event.clone() {
             Event::WindowEvent { event, .. } => match event {
@@ -23,7 +21,12 @@ fn emit_events(resources: &mut ResourceRegistry, value: &RunSystemPhase) {
     }
 }
 
-pub fn load(ecs: &mut ECS) {
-    ecs.resources.set(KeyboardState::new());
-    ecs.add_before_service(Service::at_event(emit_events));
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        let ecs = &mut app.ecs;
+        ecs.resources.set(KeyboardState::new());
+        ecs.add_before_service(Service::at_event(emit_events).label("input"));
+    }
 }
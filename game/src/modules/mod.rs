@@ -1,13 +1,15 @@
-use ecs::ECS;
+use ecs::{PluginGroup, PluginGroupBuilder};
 
 pub mod active_controls;
 pub mod cube;
 pub mod cursor_grab;
 pub mod debug_info;
 pub mod debug_ui;
+pub mod game_state;
 pub mod grid;
 pub mod input;
 pub mod on_resize;
+pub mod post_process;
 pub mod render_all;
 pub mod textured;
 pub mod time;
@@ -15,23 +17,45 @@ pub mod ui;
 pub mod physics;
 pub mod multi_texture;
 
-use lazy_static::lazy_static;
+// Rendering, input, the camera and the game state, what every scene needs
+pub struct DefaultPlugins;
 
-lazy_static! {
-    pub static ref MODULE_LOADER: Vec<fn(&mut ECS)> = vec![
-        debug_info::load,
-        on_resize::load,
-        debug_ui::load,
-        render_all::load,
-        ui::load,
-        cube::load,
-        grid::load,
-        time::load,
-        input::load,
-        active_controls::load,
-        cursor_grab::load,
-        textured::load,
-        physics::load,
-        multi_texture::load,
-    ];
+impl PluginGroup for DefaultPlugins {
+    fn build(self, group: &mut PluginGroupBuilder) {
+        group
+            .add(game_state::GameStatePlugin)
+            .add(debug_info::DebugInfoPlugin)
+            .add(on_resize::OnResizePlugin)
+            .add(render_all::RenderAllPlugin)
+            .add(post_process::PostProcessPlugin)
+            .add(time::TimePlugin::default())
+            .add(input::InputPlugin)
+            .add(active_controls::ActiveControlsPlugin)
+            .add(cursor_grab::CursorGrabPlugin);
+    }
+}
+
+// FPS counter drawn over the scene
+pub struct DebugUiPlugins;
+
+impl PluginGroup for DebugUiPlugins {
+    fn build(self, group: &mut PluginGroupBuilder) {
+        group
+            .add(debug_ui::DebugUiPlugin::default())
+            .add(ui::UiPlugin);
+    }
+}
+
+// The test world: the voxel chunk, physics bodies and the sample meshes
+pub struct WorldPlugins;
+
+impl PluginGroup for WorldPlugins {
+    fn build(self, group: &mut PluginGroupBuilder) {
+        group
+            .add(cube::CubePlugin)
+            .add(grid::GridPlugin)
+            .add(textured::TexturedPlugin)
+            .add(physics::PhysicsPlugin::default())
+            .add(multi_texture::MultiTexturePlugin);
+    }
 }
@@ -1,5 +1,6 @@
 mod vertex;
-use ecs::{Entity, ECS};
+use crate::modules::render_all::RenderAllPlugin;
+use ecs::{App, Entity, Plugin, PluginId};
 use renderer::{
     RendererDevice, Texture, TextureFiltering, TextureStorage, TextureWrapping,
 };
@@ -7,64 +8,71 @@ use renderer::{
 use std::path::Path;
 use vertex::Vertex;
 
-pub fn load(ecs: &mut ECS) {
-    let context = ecs.resources.get_mut::<RendererDevice>().unwrap();
+pub struct MultiTexturePlugin;
 
-    let idx1 = context.register_texture(Texture::new_initialized(
-        TextureWrapping::ClampToEdge,
-        TextureFiltering::Pixelated,
-        TextureStorage::from_image(&Path::new("textures/normal.jpg")),
-    ));
-    let idx2 = context.register_texture(Texture::new_initialized(
-        TextureWrapping::ClampToEdge,
-        TextureFiltering::Pixelated,
-        TextureStorage::from_image(&Path::new("textures/box.png")),
-    ));
-    let idx3 = context.register_texture(Texture::new_initialized(
-        TextureWrapping::ClampToEdge,
-        TextureFiltering::Pixelated,
-        TextureStorage::from_image(&Path::new("textures/wood.jpg")),
-    ));
-    let idx4 = context.register_texture(Texture::new_initialized(
-        TextureWrapping::ClampToEdge,
-        TextureFiltering::Pixelated,
-        TextureStorage::from_image(&Path::new("textures/metal.jpg")),
-    ));
+impl Plugin for MultiTexturePlugin {
+    fn build(&self, app: &mut App) {
+        let ecs = &mut app.ecs;
+        let mut context = ecs.resources.get_mut::<RendererDevice>().unwrap();
 
-    ecs.add_entity(
-        Entity::new()
-            .with(context.new_mesh(
-                &Path::new("shaders/multi-texture.glsl"),
-                vec![
-                    Vertex::new(0.5, 2.0 + 0.5, 0.0, 1.0, 1.0),
-                    Vertex::new(-0.5, 2.0 + -0.5, 0.0, 0.0, 0.0),
-                    Vertex::new(0.5, 2.0 + -0.5, 0.0, 1.0, 0.0),
-                    Vertex::new(-0.5, 2.0 + 0.5, 0.0, 0.0, 1.0),
-                ],
-                Some(vec![0, 1, 2, 0, 3, 1]),
-                vec![
-                    idx1, idx2
-                ],
-                None,
-            )),
-    );
+        let idx1 = context.register_texture(Texture::new_initialized(
+            TextureWrapping::ClampToEdge,
+            TextureFiltering::Pixelated,
+            TextureStorage::from_image(&Path::new("textures/normal.jpg")),
+        ));
+        let idx2 = context.register_texture(Texture::new_initialized(
+            TextureWrapping::ClampToEdge,
+            TextureFiltering::Pixelated,
+            TextureStorage::from_image(&Path::new("textures/box.png")),
+        ));
+        let idx3 = context.register_texture(Texture::new_initialized(
+            TextureWrapping::ClampToEdge,
+            TextureFiltering::Pixelated,
+            TextureStorage::from_image(&Path::new("textures/wood.jpg")),
+        ));
+        let idx4 = context.register_texture(Texture::new_initialized(
+            TextureWrapping::ClampToEdge,
+            TextureFiltering::Pixelated,
+            TextureStorage::from_image(&Path::new("textures/metal.jpg")),
+        ));
 
-    ecs.add_entity(
-        Entity::new()
-            .with(context.new_mesh(
-                &Path::new("shaders/multi-texture.glsl"),
-                vec![
-                    Vertex::new(0.5, 3.0 + 0.5, 0.0, 1.0, 1.0),
-                    Vertex::new(-0.5, 3.0 + -0.5, 0.0, 0.0, 0.0),
-                    Vertex::new(0.5, 3.0 + -0.5, 0.0, 1.0, 0.0),
-                    Vertex::new(-0.5, 3.0 + 0.5, 0.0, 0.0, 1.0),
-                ],
-                Some(vec![0, 1, 2, 0, 3, 1]),
-                vec![
-                    idx3,
-                    idx4
-                ],
-                None,
-            )),
-    );
+        let first = context.new_mesh(
+            &Path::new("shaders/multi-texture.glsl"),
+            vec![
+                Vertex::new(0.5, 2.0 + 0.5, 0.0, 1.0, 1.0),
+                Vertex::new(-0.5, 2.0 + -0.5, 0.0, 0.0, 0.0),
+                Vertex::new(0.5, 2.0 + -0.5, 0.0, 1.0, 0.0),
+                Vertex::new(-0.5, 2.0 + 0.5, 0.0, 0.0, 1.0),
+            ],
+            Some(vec![0, 1, 2, 0, 3, 1]),
+            vec![
+                idx1, idx2
+            ],
+            None,
+        );
+
+        let second = context.new_mesh(
+            &Path::new("shaders/multi-texture.glsl"),
+            vec![
+                Vertex::new(0.5, 3.0 + 0.5, 0.0, 1.0, 1.0),
+                Vertex::new(-0.5, 3.0 + -0.5, 0.0, 0.0, 0.0),
+                Vertex::new(0.5, 3.0 + -0.5, 0.0, 1.0, 0.0),
+                Vertex::new(-0.5, 3.0 + 0.5, 0.0, 0.0, 1.0),
+            ],
+            Some(vec![0, 1, 2, 0, 3, 1]),
+            vec![
+                idx3,
+                idx4
+            ],
+            None,
+        );
+        drop(context);
+
+        ecs.add_entity(Entity::new().with(first));
+        ecs.add_entity(Entity::new().with(second));
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![PluginId::of::<RenderAllPlugin>()]
+    }
 }
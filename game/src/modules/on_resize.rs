@@ -1,20 +1,26 @@
 use crate::window_context::WindowContext;
-use ecs::{Entity, EntityShapeBuilder, ResourceRegistry, RunSystemPhase, Service, System, ECS};
+use ecs::{
+    App, EntityMut, EntityShapeBuilder, Plugin, ResourceRegistry, RunSystemPhase, Service, System,
+};
 use glutin::event::{Event, WindowEvent};
 use renderer::{RenderComponent, RendererDevice, TextureStorage};
 
 pub struct ViewportSizedUITag;
 
-fn resize_uis(entity: &mut Entity, resources: &mut ResourceRegistry, value: &RunSystemPhase) {
+fn resize_uis(entity: &mut EntityMut, resources: &mut ResourceRegistry, value: &RunSystemPhase) {
     match value {
         RunSystemPhase::Event(event) => match event {
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::Resized(physical_size) => {
-                    let device = resources.get_mut::<RendererDevice>().unwrap();
+                    let mut device = resources.get_mut::<RendererDevice>().unwrap();
                     let render = entity.get_mut::<RenderComponent>().unwrap();
-                    if let Some(texture_index) = render.textures.get_mut(0) {
+                    let texture_index = device
+                        .material(render.material)
+                        .and_then(|material| material.textures.get(0).cloned());
 
-                        let mut texture = device.get_texture_mut(*texture_index).unwrap();
+                    if let Some(texture_index) = texture_index {
+
+                        let mut texture = device.get_texture_mut(texture_index).unwrap();
 
                         texture.needs_update = true;
 
@@ -39,8 +45,8 @@ fn resize_uis(entity: &mut Entity, resources: &mut ResourceRegistry, value: &Run
 }
 
 fn emit_events(resources: &mut ResourceRegistry, value: &RunSystemPhase) {
-    let game_context = resources.get_mut::<WindowContext>().unwrap();
-    let renderer_device = resources.get_mut::<RendererDevice>().unwrap();
+    let mut game_context = resources.get_mut::<WindowContext>().unwrap();
+    let mut renderer_device = resources.get_mut::<RendererDevice>().unwrap();
 
     match value {
         RunSystemPhase::Event(event) => match event {
@@ -57,14 +63,19 @@ fn emit_events(resources: &mut ResourceRegistry, value: &RunSystemPhase) {
     }
 }
 
-pub fn load(ecs: &mut ECS) {
-    ecs.add_system(System::at_event(
-        EntityShapeBuilder::new()
-            .with::<RenderComponent>()
-            .with::<ViewportSizedUITag>()
-            .build(),
-        resize_uis,
-    ));
+pub struct OnResizePlugin;
+
+impl Plugin for OnResizePlugin {
+    fn build(&self, app: &mut App) {
+        let ecs = &mut app.ecs;
+        ecs.add_system(System::at_event(
+            EntityShapeBuilder::new()
+                .with::<RenderComponent>()
+                .with::<ViewportSizedUITag>()
+                .build(),
+            resize_uis,
+        ));
 
-    ecs.add_before_service(Service::at_event(emit_events));
+        ecs.add_before_service(Service::at_event(emit_events));
+    }
 }
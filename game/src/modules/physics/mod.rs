@@ -1,27 +1,37 @@
-mod vertex;
 mod utils;
+mod vertex;
 
 use crate::internal::FPSControls;
-use crate::modules::textured::Sprite;
+use crate::modules::active_controls::ActiveControlsPlugin;
+use crate::modules::textured::{Sprite, TexturedPlugin};
+use crate::modules::time::TimePlugin;
 use crate::time::TimeContext;
 use bulletrs::*;
-use cgmath::{Vector3, Vector4};
-use ecs::{Entity, EntityShapeBuilder, ResourceRegistry, RunSystemPhase, Service, System, ECS};
-use renderer::{ShaderVariable, MeshFlags, RenderComponent, RendererDevice, Texture,TextureWrapping, TextureFiltering, TextureStorage};
+use cgmath::{InnerSpace, Vector3, Vector4};
+use ecs::{
+    App, Entity, EntityMut, EntityShapeBuilder, GlobalTransform, NonSendMut, Plugin, PluginId, Res,
+    ResourceRegistry, RunSystemPhase, Service, System, Transform,
+};
+use math::{Quat, Vec3, Vec4};
+use renderer::{
+    MeshFlags, RenderComponent, RendererDevice, Texture, TextureFiltering, TextureStorage,
+    TextureWrapping,
+};
 use std::path::Path;
-use math::{Vec3, Vec4};
-use vertex::{Vertex};
 use utils::build_block_mesh;
+use vertex::Vertex;
 
 extern crate bulletrs;
 extern crate cgmath;
 
 struct PhysicsWorld {
     world: DynamicsWorld,
+    // Removed constraints leave a `None` so the slots kept by bodies stay valid
+    constraints: Vec<Option<ConstraintHandle>>,
 }
 
 impl PhysicsWorld {
-    pub fn new() -> Self {
+    pub fn new(gravity: Vector3<f64>) -> Self {
         let configuration = CollisionConfiguration::new_default();
 
         let mut world = DynamicsWorld::new_discrete_world(
@@ -30,9 +40,12 @@ impl PhysicsWorld {
             ConstraintSolver::new(),
             configuration,
         );
-        world.set_gravity(Vector3::new(0.0, -10.0, 0.0));
+        world.set_gravity(gravity);
 
-        Self { world }
+        Self {
+            world,
+            constraints: Vec::new(),
+        }
     }
 
     pub fn new_box(
@@ -65,10 +78,36 @@ impl PhysicsWorld {
             restitution,
         )
     }
+
+    // Both bodies keep the slot of the constraint, so removing either of them removes it too
+    pub fn add_hinge(
+        &mut self,
+        hinge: HingeConstraint,
+        body_a: &mut PhysicsBody,
+        body_b: &mut PhysicsBody,
+    ) {
+        let slot = self.constraints.len();
+        let constraint = self.world.add_constraint(hinge, true);
+        self.constraints.push(Some(constraint));
+        body_a.constraints.push(slot);
+        body_b.constraints.push(slot);
+    }
+
+    pub fn remove_body(&mut self, body: &PhysicsBody) {
+        for slot in &body.constraints {
+            if let Some(constraint) = self.constraints[*slot].take() {
+                self.world.remove_constraint(constraint);
+            }
+        }
+        self.world.remove_rigid_body(body.body);
+    }
 }
 
 struct PhysicsBody {
     body: RigidBodyHandle,
+    // Position and orientation before the last fixed step, rendering blends towards the current
+    previous: Option<(Vector3<f64>, Vector4<f64>)>,
+    constraints: Vec<usize>,
 }
 
 impl PhysicsBody {
@@ -90,7 +129,11 @@ impl PhysicsBody {
         ));
         body.set_restitution(restitution);
 
-        Self { body }
+        Self {
+            body,
+            previous: None,
+            constraints: Vec::new(),
+        }
     }
 
     pub fn plane_from(
@@ -112,29 +155,64 @@ impl PhysicsBody {
         ));
         body.set_restitution(restitution);
 
-        Self { body }
+        Self {
+            body,
+            previous: None,
+            constraints: Vec::new(),
+        }
     }
 }
 
-fn before_frame(resources: &mut ResourceRegistry, _value: &RunSystemPhase) {
-    let time_context = resources.get_mut::<TimeContext>().unwrap();
-    let world = resources.get_mut::<PhysicsWorld>().unwrap();
+fn store_previous_pose(entity: &mut EntityMut, _: &mut ResourceRegistry, _value: &RunSystemPhase) {
+    let thing = entity.get_mut::<PhysicsBody>().unwrap();
+    thing.previous = Some(thing.body.get_world_position_and_orientation());
+}
 
+fn fixed_update(time_context: Res<TimeContext>, mut world: NonSendMut<PhysicsWorld>) {
     world
         .world
-        .step_simulation(time_context.last_delta(), 0, 0.0);
+        .step_simulation(time_context.fixed_delta(), 0, 0.0);
 }
 
-fn render_hit_point_physics(
-    entity: &mut Entity,
+// Rotation turning the -Z axis towards `direction`
+fn facing(direction: &Vec3) -> Quat {
+    let forward = Vec3::from_components(0.0, 0.0, -1.0);
+    let mut direction = Vec3::from(direction);
+    direction.normalize();
+
+    let mut rotation = Quat::new();
+    let mut axis = Vec3::from_cross(&forward, &direction);
+    let cos = forward.dot(&direction).max(-1.0).min(1.0);
+    if axis.length_squared() > 1e-12 {
+        axis.normalize();
+        rotation.set_from_axis_angle(&axis, cos.acos());
+    } else if cos < 0.0 {
+        rotation.set_from_axis_angle(&Vec3::unit_y(), std::f32::consts::PI);
+    }
+    rotation
+}
+
+// The camera moves on events, so the rig follows it there and `propagate_transforms` places
+// whatever is attached to it before the render
+fn follow_camera(
+    entity: &mut EntityMut,
     resources: &mut ResourceRegistry,
     _value: &RunSystemPhase,
 ) {
-    let active_controls = resources.get_mut::<FPSControls>().unwrap();
+    let active_controls = resources.get::<FPSControls>().unwrap();
+    let transform = entity.get_mut::<Transform>().unwrap();
 
-    let mut world_pos = Vec3::from(&active_controls.camera.pointing);
-    world_pos.multiply_scalar(5.0);
-    world_pos.add(&active_controls.camera.position);
+    transform.position = Vec3::from(&active_controls.camera.position);
+    transform.rotation = facing(&active_controls.camera.pointing);
+}
+
+fn render_hit_point_physics(
+    entity: &mut EntityMut,
+    _: &mut ResourceRegistry,
+    _value: &RunSystemPhase,
+) {
+    let mut world_pos = Vec3::new();
+    world_pos.set_from_matrix_position(entity.get::<GlobalTransform>().unwrap().matrix());
 
     let p = entity.get_mut::<PhysicsBody>().unwrap();
 
@@ -145,12 +223,12 @@ fn render_hit_point_physics(
 }
 
 fn render_hit_point(
-    entity: &mut Entity,
+    entity: &mut EntityMut,
     resources: &mut ResourceRegistry,
     _value: &RunSystemPhase,
 ) {
-    let active_controls = resources.get_mut::<FPSControls>().unwrap();
-    let world = resources.get_mut::<PhysicsWorld>().unwrap();
+    let active_controls = resources.get::<FPSControls>().unwrap();
+    let mut world = resources.get_mut::<PhysicsWorld>().unwrap();
 
     let mut world_pos = Vec3::from(&active_controls.camera.pointing);
     world_pos.multiply_scalar(100.0);
@@ -166,267 +244,345 @@ fn render_hit_point(
     ));
 
     let render = entity.get_mut::<RenderComponent>().unwrap();
+    let mut device = resources.get_mut::<RendererDevice>().unwrap();
+    let material = device.material_mut(render.material).unwrap();
 
     for i in result.intersections() {
-        let u_position = render.material.get_variable::<Vec3>("u_position");
-        let u_orientation = render.material.get_variable::<Vec4>("u_orientation");
-        let u_size = render.material.get_variable::<Vec3>("u_size");
-
-        u_position.set(&Vec3::from_components(
-            i.point.x as f32,
-            i.point.y as f32,
-            i.point.z as f32,
-        ));
-        u_orientation.set(&Vec4::from_components(0.0, 0.0, 0.0, 0.0));
-        u_size.set(&Vec3::from_scalar(0.2));
+        material
+            .set(
+                "u_position",
+                Vec3::from_components(i.point.x as f32, i.point.y as f32, i.point.z as f32),
+            )
+            .set("u_orientation", Vec4::from_components(0.0, 0.0, 0.0, 0.0))
+            .set("u_size", Vec3::from_scalar(0.2));
     }
 }
 
-fn render_static(entity: &mut Entity, _resources: &mut ResourceRegistry, _value: &RunSystemPhase) {
+fn render_static(
+    entity: &mut EntityMut,
+    resources: &mut ResourceRegistry,
+    _value: &RunSystemPhase,
+) {
     let render = entity.get_mut::<RenderComponent>().unwrap();
-    let u_position = render.material.get_variable::<Vec3>("u_position");
-    let u_orientation = render.material.get_variable::<Vec4>("u_orientation");
-    let u_size = render.material.get_variable::<Vec3>("u_size");
-
-    u_position.set(&Vec3::from_components(0.0, 0.0, 0.0));
-    u_orientation.set(&Vec4::from_components(0.0, 0.0, 0.0, 1.0));
-    u_size.set(&Vec3::from_scalar(1.0));
+    let mut device = resources.get_mut::<RendererDevice>().unwrap();
+
+    device
+        .material_mut(render.material)
+        .unwrap()
+        .set("u_position", Vec3::from_components(0.0, 0.0, 0.0))
+        .set("u_orientation", Vec4::from_components(0.0, 0.0, 0.0, 1.0))
+        .set("u_size", Vec3::from_scalar(1.0));
 }
 
-fn render_dynamic(entity: &mut Entity, _: &mut ResourceRegistry, _value: &RunSystemPhase) {
-    let thing = entity.get_mut::<PhysicsBody>().unwrap();
-    let render = entity.get_mut::<RenderComponent>().unwrap();
-
-    // Todo:: call defautls on Vec3 and set just copy
-    let u_position = render.material.get_variable::<Vec3>("u_position");
-    let u_orientation = render.material.get_variable::<Vec4>("u_orientation");
-    let u_size = render.material.get_variable::<Vec3>("u_size");
-
-    let (position, orientation) = thing.body.get_world_position_and_orientation();
+fn render_dynamic(
+    entity: &mut EntityMut,
+    resources: &mut ResourceRegistry,
+    _value: &RunSystemPhase,
+) {
+    let alpha = resources.get::<TimeContext>().unwrap().alpha();
+    let (thing, render) = entity
+        .get_many_mut::<(PhysicsBody, RenderComponent)>()
+        .unwrap();
+
+    let (mut position, mut orientation) = thing.body.get_world_position_and_orientation();
+    if let Some((previous_position, previous_orientation)) = thing.previous {
+        // Shortest way around, `q` and `-q` are the same rotation
+        if previous_orientation.dot(orientation) < 0.0 {
+            orientation = -orientation;
+        }
+        position = previous_position + (position - previous_position) * alpha;
+        orientation =
+            (previous_orientation + (orientation - previous_orientation) * alpha).normalize();
+    }
 
-    u_position.set(&Vec3::from_components(
-        position.x as f32,
-        position.y as f32,
-        position.z as f32,
-    ));
-    u_orientation.set(&Vec4::from_components(
-        orientation.x as f32,
-        orientation.y as f32,
-        orientation.z as f32,
-        orientation.w as f32,
-    ));
-    u_size.set(&Vec3::from_scalar(1.0));
+    let mut device = resources.get_mut::<RendererDevice>().unwrap();
+    device
+        .material_mut(render.material)
+        .unwrap()
+        .set(
+            "u_position",
+            Vec3::from_components(position.x as f32, position.y as f32, position.z as f32),
+        )
+        .set(
+            "u_orientation",
+            Vec4::from_components(
+                orientation.x as f32,
+                orientation.y as f32,
+                orientation.z as f32,
+                orientation.w as f32,
+            ),
+        )
+        .set("u_size", Vec3::from_scalar(1.0));
 }
 
 const GRID_SIZE: i32 = 100;
 const GRID_STEP: f32 = 2.0;
 const GRID_HEIGHT: f32 = -2.0;
 
-struct IsDynamicBodyRendererTag;
 struct IsHitPointTag;
 struct IsStaticBodyRendererTag;
 struct IsPickerCursorTag;
+// Follows the camera, the picker cursor hangs off it a few units in front of the view
+struct IsCameraRigTag;
 
+pub struct PhysicsPlugin {
+    pub gravity: Vector3<f64>,
+}
 
-pub fn load(ecs: &mut ECS) {
-    ecs.resources.set(PhysicsWorld::new());
-    let world = ecs.resources.get_mut::<PhysicsWorld>().unwrap();
-    let context = ecs.resources.get_mut::<RendererDevice>().unwrap();
-
-    let ground_shape = world.new_plane(
-        Vector3::new(0.0, 1.0, 0.0),
-        -2.0,
-        0.0,
-        Vector3::new(0.0, 0.0, 0.0),
-        Vector4::new(0.0, 0.0, 0.0, 1.0),
-        0.0,
-    );
-    
-    let text = context.register_texture(Texture::new_initialized(
-        TextureWrapping::ClampToEdge,
-        TextureFiltering::Pixelated,
-        TextureStorage::from_image(&Path::new("textures/out.png")),
-    ));
-    
-    let context = ecs.resources.get_mut::<RendererDevice>().unwrap();
-    let sprites = ecs.resources.get_mut::<Vec<Sprite>>().unwrap();
-
-    for i in 0..10 {
-        if let Some((new_data, new_index)) = build_block_mesh(sprites, Vec3::from_components(0.0, 0.0, 0.0), 0) {
-            ecs.add_entity(
-                Entity::new()
-                    .with(world.new_box(
-                        Vector3::new(1.0, 1.0, 1.0),
-                        20.1,
-                        Vector3::new(0.0, i as f64 * 2.5, 0.0),
-                        Vector4::new(0.4 * i as f64, 1.0, 0.22 * (i as f64) / 2.0, 1.0),
-                        1.0,
-                    ))
-                    .with(IsDynamicBodyRendererTag)
-                    .with(context.new_mesh(
-                        &Path::new("shaders/textured_body_render.glsl"),
-                        new_data,
-                        Some(new_index),
-                        vec![text],
-                        None,
-                    )),
-            );
+impl Default for PhysicsPlugin {
+    fn default() -> Self {
+        Self {
+            gravity: Vector3::new(0.0, -10.0, 0.0),
         }
     }
+}
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        let ecs = &mut app.ecs;
+        ecs.resources.set(PhysicsWorld::new(self.gravity));
+        ecs.on_remove(
+            |_, body: &mut PhysicsBody, resources: &mut ResourceRegistry| {
+                if let Some(mut world) = resources.get_mut::<PhysicsWorld>() {
+                    world.remove_body(body);
+                }
+            },
+        );
 
-    if let Some((new_data, new_index)) = build_block_mesh(sprites, Vec3::from_components(0.0, 0.0, 0.0), 10) {
-
-
-        if let Some((new_data_2, new_index_2)) = build_block_mesh(sprites, Vec3::from_components(0.0, 0.0, 0.0), 12) {
-
-
-
-            let body_a = world.new_box(
-                Vector3::new(0.5, 0.5, 0.5),
-                0.0,
-                Vector3::new(0.0, 5.0, 0.0),
-                Vector4::new(0.0, 0.0, 0.0, 1.0),
-                0.0,
-            );
-
-            let body_b = world.new_box(
-                Vector3::new(1.0, 1.0, 1.0),
-                20.0,
-                Vector3::new(0.0, 7.0, 0.0),
-                Vector4::new(0.0, 0.0, 0.0, 1.0),
-                1.0,
-            );
-
-            let hinge = HingeConstraint::new(
-                &body_b.body,
-                &body_a.body,
-                Vector3::from([3.0, 3.0, 3.0]),
-                Vector3::from([0.0, 0.0, 0.0]),
-                Vector3::from([1.0, 0.0, 0.0]),
-                Vector3::from([1.0, 0.0, 0.0]),
-                false
-            );
-                    world.world.add_constraint(hinge, true);
-
-            ecs.add_entity(
-                Entity::new()
-                    .with(body_a)
-                    .with(IsDynamicBodyRendererTag)
-                    .with(IsPickerCursorTag)
-                    .with(context.new_mesh(
-                        &Path::new("shaders/textured_body_render.glsl"),
-                        new_data_2,
-                        Some(new_index_2),
-                        vec![text],
-                        None,
-                    )),
-            );
-
-            ecs.add_entity(
-                Entity::new()
-                    .with(body_b)
-                    .with(IsDynamicBodyRendererTag)
-                    .with(context.new_mesh(
-                        &Path::new("shaders/textured_body_render.glsl"),
-                        new_data,
-                        Some(new_index),
-                        vec![text],
-                        None,
-                    )),
-            );
+        let (mut world, mut context) = ecs
+            .resources
+            .get_many_mut::<(PhysicsWorld, RendererDevice)>()
+            .unwrap();
+        let sprites = ecs.resources.get::<Vec<Sprite>>().unwrap();
+        let mut entities = Vec::new();
+
+        let ground_shape = world.new_plane(
+            Vector3::new(0.0, 1.0, 0.0),
+            -2.0,
+            0.0,
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector4::new(0.0, 0.0, 0.0, 1.0),
+            0.0,
+        );
+
+        let text = context.register_texture(Texture::new_initialized(
+            TextureWrapping::ClampToEdge,
+            TextureFiltering::Pixelated,
+            TextureStorage::from_image(&Path::new("textures/out.png")),
+        ));
+
+        for i in 0..10 {
+            if let Some((new_data, new_index)) =
+                build_block_mesh(&sprites, Vec3::from_components(0.0, 0.0, 0.0), 0)
+            {
+                entities.push(
+                    Entity::new()
+                        .with(world.new_box(
+                            Vector3::new(1.0, 1.0, 1.0),
+                            20.1,
+                            Vector3::new(0.0, i as f64 * 2.5, 0.0),
+                            Vector4::new(0.4 * i as f64, 1.0, 0.22 * (i as f64) / 2.0, 1.0),
+                            1.0,
+                        ))
+                        .with(context.new_mesh(
+                            &Path::new("shaders/textured_body_render.glsl"),
+                            new_data,
+                            Some(new_index),
+                            vec![text],
+                            None,
+                        )),
+                );
+            }
         }
-    }
 
+        let mut cursor = None;
+        if let Some((new_data, new_index)) =
+            build_block_mesh(&sprites, Vec3::from_components(0.0, 0.0, 0.0), 10)
+        {
+            if let Some((new_data_2, new_index_2)) =
+                build_block_mesh(&sprites, Vec3::from_components(0.0, 0.0, 0.0), 12)
+            {
+                let mut body_a = world.new_box(
+                    Vector3::new(0.5, 0.5, 0.5),
+                    0.0,
+                    Vector3::new(0.0, 5.0, 0.0),
+                    Vector4::new(0.0, 0.0, 0.0, 1.0),
+                    0.0,
+                );
+
+                let mut body_b = world.new_box(
+                    Vector3::new(1.0, 1.0, 1.0),
+                    20.0,
+                    Vector3::new(0.0, 7.0, 0.0),
+                    Vector4::new(0.0, 0.0, 0.0, 1.0),
+                    1.0,
+                );
+
+                let hinge = HingeConstraint::new(
+                    &body_b.body,
+                    &body_a.body,
+                    Vector3::from([3.0, 3.0, 3.0]),
+                    Vector3::from([0.0, 0.0, 0.0]),
+                    Vector3::from([1.0, 0.0, 0.0]),
+                    Vector3::from([1.0, 0.0, 0.0]),
+                    false,
+                );
+                world.add_hinge(hinge, &mut body_a, &mut body_b);
+
+                cursor = Some(
+                    Entity::new()
+                        .with(body_a)
+                        .with(IsPickerCursorTag)
+                        .with(Transform::from_position(Vec3::from_components(
+                            0.0, 0.0, -5.0,
+                        )))
+                        .with(context.new_mesh(
+                            &Path::new("shaders/textured_body_render.glsl"),
+                            new_data_2,
+                            Some(new_index_2),
+                            vec![text],
+                            None,
+                        )),
+                );
+
+                entities.push(Entity::new().with(body_b).with(context.new_mesh(
+                    &Path::new("shaders/textured_body_render.glsl"),
+                    new_data,
+                    Some(new_index),
+                    vec![text],
+                    None,
+                )));
+            }
+        }
 
+        let mut vertex_data: Vec<Vertex> = Vec::new();
 
+        let h_size = (GRID_SIZE as f32 * GRID_STEP) / 2.0;
+        let mut cursor = -h_size;
 
+        for _ in 0..(GRID_SIZE + 1) {
+            vertex_data.push(Vertex::new(cursor, GRID_HEIGHT, -h_size, 1.0, 0.0, 0.0));
+            vertex_data.push(Vertex::new(cursor, GRID_HEIGHT, h_size, 0.0, 1.0, 0.0));
+            vertex_data.push(Vertex::new(-h_size, GRID_HEIGHT, cursor, 0.0, 0.0, 1.0));
+            vertex_data.push(Vertex::new(h_size, GRID_HEIGHT, cursor, 1.0, 1.0, 0.0));
+            cursor += GRID_STEP;
+        }
 
-    let mut vertex_data: Vec<Vertex> = Vec::new();
+        entities.push(
+            Entity::new()
+                .with(ground_shape)
+                .with(IsStaticBodyRendererTag)
+                .with(context.new_mesh(
+                    &Path::new("shaders/body_render.glsl"),
+                    vertex_data,
+                    None,
+                    vec![],
+                    MeshFlags::new().lines_mode().opt(),
+                )),
+        );
 
-    let h_size = (GRID_SIZE as f32 * GRID_STEP) / 2.0;
-    let mut cursor = -h_size;
+        entities.push(Entity::new().with(IsHitPointTag).with(context.new_mesh(
+            &Path::new("shaders/body_render.glsl"),
+            vec![
+                Vertex::new(-1.0, 1.0, 1.0, 1.0, 0.0, 0.0),
+                Vertex::new(1.0, 1.0, 1.0, 1.0, 0.0, 0.0),
+                Vertex::new(-1.0, 1.0, 1.0, 1.0, 0.0, 0.0),
+                Vertex::new(-1.0, -1.0, 1.0, 1.0, 0.0, 0.0),
+                Vertex::new(1.0, -1.0, 1.0, 1.0, 0.0, 0.0),
+                Vertex::new(1.0, 1.0, 1.0, 1.0, 0.0, 0.0),
+                Vertex::new(1.0, -1.0, 1.0, 1.0, 0.0, 0.0),
+                Vertex::new(-1.0, -1.0, 1.0, 1.0, 0.0, 0.0),
+                Vertex::new(1.0, -1.0, -1.0, 1.0, 0.0, 0.0),
+                Vertex::new(1.0, 1.0, -1.0, 1.0, 0.0, 0.0),
+                Vertex::new(1.0, -1.0, -1.0, 1.0, 0.0, 0.0),
+                Vertex::new(-1.0, -1.0, -1.0, 1.0, 0.0, 0.0),
+                Vertex::new(-1.0, 1.0, -1.0, 1.0, 0.0, 0.0),
+                Vertex::new(1.0, 1.0, -1.0, 1.0, 0.0, 0.0),
+                Vertex::new(-1.0, 1.0, -1.0, 1.0, 0.0, 0.0),
+                Vertex::new(-1.0, -1.0, -1.0, 1.0, 0.0, 0.0),
+                Vertex::new(1.0, 1.0, -1.0, 1.0, 0.0, 0.0),
+                Vertex::new(1.0, 1.0, 1.0, 1.0, 0.0, 0.0),
+                Vertex::new(-1.0, -1.0, 1.0, 1.0, 0.0, 0.0),
+                Vertex::new(-1.0, -1.0, -1.0, 1.0, 0.0, 0.0),
+                Vertex::new(-1.0, 1.0, -1.0, 1.0, 0.0, 0.0),
+                Vertex::new(-1.0, 1.0, 1.0, 1.0, 0.0, 0.0),
+                Vertex::new(1.0, -1.0, -1.0, 1.0, 0.0, 0.0),
+                Vertex::new(1.0, -1.0, 1.0, 1.0, 0.0, 0.0),
+            ],
+            None,
+            vec![],
+            MeshFlags::new().lines_mode().opt(),
+        )));
+        drop(sprites);
+        drop(context);
+        drop(world);
+
+        for entity in entities {
+            ecs.add_entity(entity);
+        }
 
-    for _ in 0..(GRID_SIZE + 1) {
-        vertex_data.push(Vertex::new(cursor, GRID_HEIGHT, -h_size, 1.0, 0.0, 0.0));
-        vertex_data.push(Vertex::new(cursor, GRID_HEIGHT, h_size, 0.0, 1.0, 0.0));
-        vertex_data.push(Vertex::new(-h_size, GRID_HEIGHT, cursor, 0.0, 0.0, 1.0));
-        vertex_data.push(Vertex::new(h_size, GRID_HEIGHT, cursor, 1.0, 1.0, 0.0));
-        cursor += GRID_STEP;
-    }
+        let rig = ecs.add_entity(Entity::new().with(IsCameraRigTag).with(Transform::new()));
+        if let Some(cursor) = cursor {
+            let cursor = ecs.add_entity(cursor);
+            ecs.set_parent(cursor, rig);
+        }
 
-    ecs.add_entity(
-        Entity::new()
-            .with(ground_shape)
-            .with(IsStaticBodyRendererTag)
-            .with(context.new_mesh(&Path::new("shaders/body_render.glsl"), vertex_data, None, vec![], MeshFlags::new().lines_mode().opt())),
-    );
+        ecs.add_system(System::at_event(
+            EntityShapeBuilder::new()
+                .with::<IsCameraRigTag>()
+                .with::<Transform>()
+                .build(),
+            follow_camera,
+        ));
 
-    ecs.add_entity(Entity::new().with(IsHitPointTag).with(context.new_mesh(
-        &Path::new("shaders/body_render.glsl"),
-        vec![
-            Vertex::new(-1.0, 1.0, 1.0, 1.0, 0.0, 0.0),
-            Vertex::new(1.0, 1.0, 1.0, 1.0, 0.0, 0.0),
-            Vertex::new(-1.0, 1.0, 1.0, 1.0, 0.0, 0.0),
-            Vertex::new(-1.0, -1.0, 1.0, 1.0, 0.0, 0.0),
-            Vertex::new(1.0, -1.0, 1.0, 1.0, 0.0, 0.0),
-            Vertex::new(1.0, 1.0, 1.0, 1.0, 0.0, 0.0),
-            Vertex::new(1.0, -1.0, 1.0, 1.0, 0.0, 0.0),
-            Vertex::new(-1.0, -1.0, 1.0, 1.0, 0.0, 0.0),
-            Vertex::new(1.0, -1.0, -1.0, 1.0, 0.0, 0.0),
-            Vertex::new(1.0, 1.0, -1.0, 1.0, 0.0, 0.0),
-            Vertex::new(1.0, -1.0, -1.0, 1.0, 0.0, 0.0),
-            Vertex::new(-1.0, -1.0, -1.0, 1.0, 0.0, 0.0),
-            Vertex::new(-1.0, 1.0, -1.0, 1.0, 0.0, 0.0),
-            Vertex::new(1.0, 1.0, -1.0, 1.0, 0.0, 0.0),
-            Vertex::new(-1.0, 1.0, -1.0, 1.0, 0.0, 0.0),
-            Vertex::new(-1.0, -1.0, -1.0, 1.0, 0.0, 0.0),
-            Vertex::new(1.0, 1.0, -1.0, 1.0, 0.0, 0.0),
-            Vertex::new(1.0, 1.0, 1.0, 1.0, 0.0, 0.0),
-            Vertex::new(-1.0, -1.0, 1.0, 1.0, 0.0, 0.0),
-            Vertex::new(-1.0, -1.0, -1.0, 1.0, 0.0, 0.0),
-            Vertex::new(-1.0, 1.0, -1.0, 1.0, 0.0, 0.0),
-            Vertex::new(-1.0, 1.0, 1.0, 1.0, 0.0, 0.0),
-            Vertex::new(1.0, -1.0, -1.0, 1.0, 0.0, 0.0),
-            Vertex::new(1.0, -1.0, 1.0, 1.0, 0.0, 0.0),
-        ],
-        None,
-        vec![],
-        MeshFlags::new().lines_mode().opt(),
-    )));
-
-    ecs.add_system(System::at_render(
-        EntityShapeBuilder::new()
-            .with::<RenderComponent>()
-            .with::<IsHitPointTag>()
-            .build(),
-        render_hit_point,
-    ));
+        ecs.add_system(System::at_render(
+            EntityShapeBuilder::new()
+                .with::<RenderComponent>()
+                .with::<IsHitPointTag>()
+                .build(),
+            render_hit_point,
+        ));
 
-    ecs.add_system(System::at_render(
-        EntityShapeBuilder::new()
-            .with::<RenderComponent>()
-            .with::<IsPickerCursorTag>()
-            .build(),
-        render_hit_point_physics,
-    ));
+        ecs.add_system(System::at_render(
+            EntityShapeBuilder::new()
+                .with::<RenderComponent>()
+                .with::<IsPickerCursorTag>()
+                .with::<GlobalTransform>()
+                .build(),
+            render_hit_point_physics,
+        ));
 
-    ecs.add_system(System::at_render(
-        EntityShapeBuilder::new()
-            .with::<PhysicsBody>()
-            .with::<RenderComponent>()
-            .with::<IsStaticBodyRendererTag>()
-            .build(),
-        render_static,
-    ));
+        ecs.add_system(System::at_render(
+            EntityShapeBuilder::new()
+                .with::<PhysicsBody>()
+                .with::<RenderComponent>()
+                .with::<IsStaticBodyRendererTag>()
+                .build(),
+            render_static,
+        ));
 
-    ecs.add_system(System::at_render(
-        EntityShapeBuilder::new()
-            .with::<PhysicsBody>()
-            .with::<RenderComponent>()
-            .with::<IsDynamicBodyRendererTag>()
-            .build(),
-        render_dynamic,
-    ));
+        ecs.add_system(System::at_render(
+            EntityShapeBuilder::new()
+                .with::<PhysicsBody>()
+                .with::<RenderComponent>()
+                .without::<IsStaticBodyRendererTag>()
+                .build(),
+            render_dynamic,
+        ));
+
+        ecs.add_system(System::at_tick(
+            EntityShapeBuilder::new().with::<PhysicsBody>().build(),
+            store_previous_pose,
+        ));
 
-    ecs.add_before_service(Service::at_render(before_frame));
+        ecs.add_after_service(Service::at_tick(fixed_update));
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![
+            PluginId::of::<TimePlugin>(),
+            PluginId::of::<ActiveControlsPlugin>(),
+            PluginId::of::<TexturedPlugin>(),
+        ]
+    }
 }
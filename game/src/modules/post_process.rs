@@ -0,0 +1,193 @@
+use crate::modules::render_all::RenderAllPlugin;
+use ecs::{App, Plugin, PluginId, Res, ResMut, ResourceRegistry, RunSystemPhase, Service};
+use glutin::event::{Event, WindowEvent};
+use renderer::{PostProcessStack, RendererDevice};
+use std::path::Path;
+
+// Parameter blocks of the passes in `shaders/post`, laid out as their std140 `PassParams`
+
+#[repr(C)]
+pub struct BloomParams {
+    pub threshold: f32,
+    pub intensity: f32,
+    pub radius: f32,
+    _padding: f32,
+}
+
+#[repr(C)]
+pub struct TonemapParams {
+    pub exposure: f32,
+    pub gamma: f32,
+    _padding: [f32; 2],
+}
+
+#[repr(C)]
+pub struct ColorGradingParams {
+    pub lift: [f32; 4],
+    pub gamma: [f32; 4],
+    pub gain: [f32; 4],
+    pub saturation: f32,
+    pub contrast: f32,
+    _padding: [f32; 2],
+}
+
+#[repr(C)]
+pub struct FxaaParams {
+    pub edge_threshold: f32,
+    pub edge_threshold_min: f32,
+    pub subpixel: f32,
+    _padding: f32,
+}
+
+#[repr(C)]
+pub struct VignetteParams {
+    pub color: [f32; 4],
+    pub intensity: f32,
+    pub smoothness: f32,
+    _padding: [f32; 2],
+}
+
+impl Default for BloomParams {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            intensity: 0.6,
+            radius: 2.0,
+            _padding: 0.0,
+        }
+    }
+}
+
+impl Default for TonemapParams {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            gamma: 2.2,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+impl Default for ColorGradingParams {
+    fn default() -> Self {
+        Self {
+            lift: [0.0; 4],
+            gamma: [1.0; 4],
+            gain: [1.0; 4],
+            saturation: 1.0,
+            contrast: 1.0,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+impl Default for FxaaParams {
+    fn default() -> Self {
+        Self {
+            edge_threshold: 0.125,
+            edge_threshold_min: 0.0312,
+            subpixel: 0.75,
+            _padding: 0.0,
+        }
+    }
+}
+
+impl Default for VignetteParams {
+    fn default() -> Self {
+        Self {
+            color: [0.0, 0.0, 0.0, 1.0],
+            intensity: 0.4,
+            smoothness: 0.6,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+fn before_frame(stack: Res<PostProcessStack>, mut device: ResMut<RendererDevice>) {
+    stack.begin(&mut device);
+}
+
+fn after_frame(stack: Res<PostProcessStack>, mut device: ResMut<RendererDevice>) {
+    stack.present(&mut device);
+}
+
+fn on_resize(resources: &mut ResourceRegistry, value: &RunSystemPhase) {
+    if let RunSystemPhase::Event(Event::WindowEvent {
+        event: WindowEvent::Resized(size),
+        ..
+    }) = value
+    {
+        let mut stack = resources.get_mut::<PostProcessStack>().unwrap();
+        let mut device = resources.get_mut::<RendererDevice>().unwrap();
+
+        if let Err(error) = stack.resize(&mut device, size.width, size.height) {
+            panic!("{}", error);
+        }
+    }
+}
+
+pub fn release(resources: &mut ResourceRegistry) {
+    if let Some(stack) = resources.remove::<PostProcessStack>() {
+        let mut device = resources.get_mut::<RendererDevice>().unwrap();
+        stack.delete(&mut device);
+    }
+}
+
+// Renders the scene in HDR and runs bloom, tonemapping, color grading, FXAA and a vignette
+// over it. Passes are toggled and tuned through the `PostProcessStack` resource by name
+pub struct PostProcessPlugin;
+
+impl Plugin for PostProcessPlugin {
+    fn build(&self, app: &mut App) {
+        let ecs = &mut app.ecs;
+        let mut device = ecs.resources.get_mut::<RendererDevice>().unwrap();
+        let (width, height) = device.ctx.viewport.size();
+
+        let mut stack = match PostProcessStack::new(&mut device, width, height) {
+            Ok(stack) => stack,
+            Err(error) => panic!("{}", error),
+        };
+
+        stack
+            .add_pass(
+                &mut device,
+                "bloom",
+                Path::new("shaders/post/bloom.glsl"),
+                BloomParams::default(),
+            )
+            .add_pass(
+                &mut device,
+                "tonemap",
+                Path::new("shaders/post/tonemap.glsl"),
+                TonemapParams::default(),
+            )
+            .add_pass(
+                &mut device,
+                "color_grading",
+                Path::new("shaders/post/color_grading.glsl"),
+                ColorGradingParams::default(),
+            )
+            .add_pass(
+                &mut device,
+                "fxaa",
+                Path::new("shaders/post/fxaa.glsl"),
+                FxaaParams::default(),
+            )
+            .add_pass(
+                &mut device,
+                "vignette",
+                Path::new("shaders/post/vignette.glsl"),
+                VignetteParams::default(),
+            );
+        drop(device);
+
+        ecs.resources.set(stack);
+        ecs.add_before_service(Service::at_render(before_frame).before("before_frame"));
+        ecs.add_after_service(Service::at_render(after_frame).before("swap_buffers"));
+        ecs.add_before_service(Service::at_event(on_resize));
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![PluginId::of::<RenderAllPlugin>()]
+    }
+}
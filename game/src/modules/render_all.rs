@@ -1,5 +1,7 @@
 use crate::window_context::WindowContext;
-use ecs::{Entity, EntityShapeBuilder, ResourceRegistry, RunSystemPhase, Service, System, ECS};
+use ecs::{
+    App, EntityMut, EntityShapeBuilder, Plugin, ResourceRegistry, RunSystemPhase, Service, System,
+};
 use renderer::gl_vertex_format::get_attribute_format;
 use renderer::{
     offset_of, renderer::gl, Float, GLBuffer, GLUniformBlockIndex, RenderComponent, RendererDevice,
@@ -9,8 +11,8 @@ use std::ffi::c_void;
 use std::path::Path;
 use math::Mat4;
 
-fn render_all(entity: &mut Entity, resources: &mut ResourceRegistry, _value: &RunSystemPhase) {
-    let ctx = resources.get_mut::<RendererDevice>().unwrap();
+fn render_all(entity: &mut EntityMut, resources: &mut ResourceRegistry, _value: &RunSystemPhase) {
+    let mut ctx = resources.get_mut::<RendererDevice>().unwrap();
 
     let thing = entity.get_mut::<RenderComponent>().unwrap();
 
@@ -18,8 +20,8 @@ fn render_all(entity: &mut Entity, resources: &mut ResourceRegistry, _value: &Ru
 }
 
 fn before_frame(resources: &mut ResourceRegistry, _value: &RunSystemPhase) {
-    let device = resources.get_mut::<RendererDevice>().unwrap();
-    let viewport_ubo = resources.get_mut::<ViewportUBO>().unwrap();
+    let mut device = resources.get_mut::<RendererDevice>().unwrap();
+    let viewport_ubo = resources.get::<ViewportUBO>().unwrap();
 
     device.ctx.reset_state();
 
@@ -31,10 +33,20 @@ fn before_frame(resources: &mut ResourceRegistry, _value: &RunSystemPhase) {
 }
 
 fn after_frame(resources: &mut ResourceRegistry, _value: &RunSystemPhase) {
-    let game_context = resources.get_mut::<WindowContext>().unwrap();
+    let mut device = resources.get_mut::<RendererDevice>().unwrap();
+    let mut game_context = resources.get_mut::<WindowContext>().unwrap();
+    device.end_frame();
     game_context.loop_end();
 }
 
+// Frees the viewport UBO, before `RendererDevice::shutdown` reports what leaked
+pub fn release(resources: &mut ResourceRegistry) {
+    if let Some(viewport_ubo) = resources.remove::<ViewportUBO>() {
+        let mut device = resources.get_mut::<RendererDevice>().unwrap();
+        device.ctx.buffer.delete_buffer(&viewport_ubo.buffer);
+    }
+}
+
 pub struct ViewportMatrices {
     pub projection: Mat4,
     pub view: Mat4,
@@ -69,41 +81,59 @@ impl VertexFormat for Vertex {
     }
 }
 
-pub fn load(ecs: &mut ECS) {
-    ecs.add_system(System::at_render(
-        EntityShapeBuilder::new().with::<RenderComponent>().build(),
-        render_all,
-    ));
-
-    let device = ecs.resources.get_mut::<RendererDevice>().unwrap();
-    let program = device
-        .ctx
-        .program
-        .create_from_file::<Vertex>(&Path::new("shaders/ubo_init.glsl"));
-
-    let ubo = device
-        .ctx
-        .buffer
-        .create_buffer(gl::UNIFORM_BUFFER, gl::STREAM_DRAW);
-
-    let matrices_ubo_block_binding =
-        program.get_uniform_block_index::<ViewportMatrices>("ViewportUBO");
-
-    let binding_point: gl::GLuint = 0;
-    program.uniform_block_binding(&matrices_ubo_block_binding, binding_point);
-
-    device.ctx.buffer.bind_buffer_base(binding_point, &ubo);
-
-    let viewport_ubo = ViewportUBO {
-        block: matrices_ubo_block_binding,
-        buffer: ubo,
-        data: [ViewportMatrices {
-            projection: Mat4::new(),
-            view: Mat4::new(),
-        }],
-    };
-
-    ecs.resources.set(viewport_ubo);
-    ecs.add_before_service(Service::at_render(before_frame));
-    ecs.add_after_service(Service::at_render(after_frame));
+pub struct RenderAllPlugin;
+
+impl Plugin for RenderAllPlugin {
+    fn build(&self, app: &mut App) {
+        let ecs = &mut app.ecs;
+        ecs.add_system(System::at_render(
+            EntityShapeBuilder::new().with::<RenderComponent>().build(),
+            render_all,
+        ));
+
+        ecs.on_remove(|_, component: &mut RenderComponent, resources: &mut ResourceRegistry| {
+            if let Some(mut device) = resources.get_mut::<RendererDevice>() {
+                device.destroy_mesh(component);
+            }
+        });
+
+        let mut device = ecs.resources.get_mut::<RendererDevice>().unwrap();
+        let program = device
+            .ctx
+            .program
+            .create_from_file::<Vertex>(&Path::new("shaders/ubo_init.glsl"));
+
+        let ubo = device
+            .ctx
+            .buffer
+            .create_buffer(gl::UNIFORM_BUFFER, gl::STREAM_DRAW);
+
+        let matrices_ubo_block_binding =
+            program.get_uniform_block_index::<ViewportMatrices>("ViewportUBO");
+
+        let binding_point: gl::GLuint = 0;
+        program.uniform_block_binding(&matrices_ubo_block_binding, binding_point);
+
+        device.ctx.buffer.bind_buffer_base(binding_point, &ubo);
+        // Shaders declare `binding = 0` themselves, this one was only needed for the block index
+        device.ctx.program.delete(&program);
+
+        let viewport_ubo = ViewportUBO {
+            block: matrices_ubo_block_binding,
+            buffer: ubo,
+            data: [ViewportMatrices {
+                projection: Mat4::new(),
+                view: Mat4::new(),
+            }],
+        };
+        drop(device);
+
+        ecs.resources.set(viewport_ubo);
+        ecs.add_before_service(
+            Service::at_render(before_frame)
+                .label("before_frame")
+                .after("camera"),
+        );
+        ecs.add_after_service(Service::at_render(after_frame).label("swap_buffers"));
+    }
 }
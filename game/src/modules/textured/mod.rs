@@ -1,22 +1,24 @@
 mod vertex;
-use crate::events::EventChannel;
 use crate::internal::FPSControls;
-use crate::modules::debug_info::{DebugInfo, DebugKey, FPSUpdateEvent};
-use ecs::{Entity, EntityShapeBuilder, ResourceRegistry, RunSystemPhase, Service, System, ECS};
+use crate::modules::active_controls::ActiveControlsPlugin;
+use crate::modules::debug_info::{DebugInfo, DebugInfoPlugin, DebugKey, FPSUpdateEvent};
+use ecs::{
+    App, Bounds, Commands, Entity, EntityId, EntityMut, EntityShapeBuilder, EventWriter, Events,
+    Plugin, PluginId, Res, ResMut, ResourceRegistry, RunSystemPhase, Service, SpatialIndex, System,
+    Transform, With, ECS,
+};
 use glutin::event::{ElementState, Event, MouseButton, VirtualKeyCode, WindowEvent};
+use math::Vec3;
 use renderer::{
     RenderComponent, RendererDevice, Texture, TextureFiltering, TextureStorage, TextureWrapping,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
-use math::Vec3;
 use vertex::Vertex;
 
-
-
 #[derive(Debug, Deserialize)]
 pub struct SpriteSize {
     pub w: f32,
@@ -43,168 +45,368 @@ pub struct Sprite {
 
 struct IsChunkRenderer;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 enum BlockType {
     Full,
     Crossed,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Block {
     pos: Vec3,
     id: usize,
     mode: BlockType,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Chunk {
     blocks: Vec<Block>,
 }
 
+struct ChunkLoadedEvent;
+
+// Entity standing for a block of the `Chunk` in the `SpatialIndex`. The chunk stays the source of
+// truth, so these are left out of scenes and spawned again from it after a load
+struct ChunkBlock;
+
+fn block_entity(pos: &Vec3) -> Entity {
+    Entity::new()
+        .with(ChunkBlock)
+        .with(Transform::from_position(Vec3::from(pos)))
+        .with(Bounds::cube(0.5))
+}
+
+fn respawn_blocks(ecs: &mut ECS) {
+    let stale: Vec<EntityId> = ecs.query_filtered::<EntityId, With<ChunkBlock>>().collect();
+    for id in stale {
+        ecs.despawn(id);
+    }
 
+    let blocks: Vec<Entity> = ecs
+        .resources
+        .get::<Chunk>()
+        .unwrap()
+        .blocks
+        .iter()
+        .map(|block| block_entity(&block.pos))
+        .collect();
+    for block in blocks {
+        ecs.add_entity(block);
+    }
+}
+
+// Chunk meshes hold GL handles, so they are built again from the blocks after a scene load
+fn rebuild_chunk(
+    entity: &mut EntityMut,
+    resources: &mut ResourceRegistry,
+    _value: &RunSystemPhase,
+) {
+    build_chunk_mesh(entity, resources);
+}
+
+fn build_chunk_mesh(entity: &mut EntityMut, resources: &ResourceRegistry) {
+    let mut context = resources.get_mut::<RendererDevice>().unwrap();
+    let sprites = resources.get::<Vec<Sprite>>().unwrap();
+    let chunk = resources.get::<Chunk>().unwrap();
+
+    let render = entity.get_mut::<RenderComponent>().unwrap();
+
+    let mut new_data = Vec::<Vertex>::new();
+    let mut new_index = Vec::<i32>::new();
+    let (width, height) = (626.0, 1782.0);
 
-fn render_ui(entity: &mut Entity, resources: &mut ResourceRegistry, valuex: &RunSystemPhase) {
+    let mut index = 0;
+    for block in &chunk.blocks {
+        // Blocks can come from an old save, so an unknown id skips that block instead of the chunk
+        let value = if let Some(value) = sprites.get(block.id) {
+            value
+        } else {
+            eprintln!("No sprite for block id {}, skipping it", block.id);
+            continue;
+        };
+
+        let x = &value.frame.x;
+        let y = &value.frame.y;
+        let xw = x + &value.frame.w;
+        let yh = y + &value.frame.h;
+
+        let (xx, yy, xw, yh) = (x / width, y / height, xw / width, yh / height);
+
+        let pbs = 0.5;
+        let nbs = -0.5;
+
+        // Top
+        new_data.push(Vertex::new(
+            block.pos.x + nbs,
+            block.pos.y + pbs,
+            block.pos.z + nbs,
+            xx,
+            yy,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + nbs,
+            block.pos.y + pbs,
+            block.pos.z + pbs,
+            xx,
+            yh,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + pbs,
+            block.pos.y + pbs,
+            block.pos.z + pbs,
+            xw,
+            yh,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + pbs,
+            block.pos.y + pbs,
+            block.pos.z + nbs,
+            xw,
+            yy,
+        ));
+
+        // Top
+        new_index.push(((index * 24) + 0) as i32);
+        new_index.push(((index * 24) + 1) as i32);
+        new_index.push(((index * 24) + 2) as i32);
+        new_index.push(((index * 24) + 0) as i32);
+        new_index.push(((index * 24) + 2) as i32);
+        new_index.push(((index * 24) + 3) as i32);
+
+        // Left
+        new_data.push(Vertex::new(
+            block.pos.x + nbs,
+            block.pos.y + pbs,
+            block.pos.z + pbs,
+            xx,
+            yy,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + nbs,
+            block.pos.y + nbs,
+            block.pos.z + pbs,
+            xw,
+            yy,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + nbs,
+            block.pos.y + nbs,
+            block.pos.z + nbs,
+            xw,
+            yh,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + nbs,
+            block.pos.y + pbs,
+            block.pos.z + nbs,
+            xx,
+            yh,
+        ));
+
+        // Left
+        new_index.push(((index * 24) + 5) as i32);
+        new_index.push(((index * 24) + 4) as i32);
+        new_index.push(((index * 24) + 6) as i32);
+        new_index.push(((index * 24) + 6) as i32);
+        new_index.push(((index * 24) + 4) as i32);
+        new_index.push(((index * 24) + 7) as i32);
+
+        // Right
+        new_data.push(Vertex::new(
+            block.pos.x + pbs,
+            block.pos.y + pbs,
+            block.pos.z + pbs,
+            xw,
+            yh,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + pbs,
+            block.pos.y + nbs,
+            block.pos.z + pbs,
+            xx,
+            yh,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + pbs,
+            block.pos.y + nbs,
+            block.pos.z + nbs,
+            xx,
+            yy,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + pbs,
+            block.pos.y + pbs,
+            block.pos.z + nbs,
+            xw,
+            yy,
+        ));
+
+        // Right
+        new_index.push(((index * 24) + 8) as i32);
+        new_index.push(((index * 24) + 9) as i32);
+        new_index.push(((index * 24) + 10) as i32);
+        new_index.push(((index * 24) + 8) as i32);
+        new_index.push(((index * 24) + 10) as i32);
+        new_index.push(((index * 24) + 11) as i32);
+
+        // Front
+        new_data.push(Vertex::new(
+            block.pos.x + pbs,
+            block.pos.y + pbs,
+            block.pos.z + pbs,
+            xw,
+            yh,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + pbs,
+            block.pos.y + nbs,
+            block.pos.z + pbs,
+            xw,
+            yy,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + nbs,
+            block.pos.y + nbs,
+            block.pos.z + pbs,
+            xx,
+            yy,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + nbs,
+            block.pos.y + pbs,
+            block.pos.z + pbs,
+            xx,
+            yh,
+        ));
+
+        // Front
+        new_index.push(((index * 24) + 13) as i32);
+        new_index.push(((index * 24) + 12) as i32);
+        new_index.push(((index * 24) + 14) as i32);
+        new_index.push(((index * 24) + 15) as i32);
+        new_index.push(((index * 24) + 14) as i32);
+        new_index.push(((index * 24) + 12) as i32);
+
+        // Back
+        new_data.push(Vertex::new(
+            block.pos.x + pbs,
+            block.pos.y + pbs,
+            block.pos.z + nbs,
+            xx,
+            yy,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + pbs,
+            block.pos.y + nbs,
+            block.pos.z + nbs,
+            xx,
+            yh,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + nbs,
+            block.pos.y + nbs,
+            block.pos.z + nbs,
+            xw,
+            yh,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + nbs,
+            block.pos.y + pbs,
+            block.pos.z + nbs,
+            xw,
+            yy,
+        ));
+
+        new_index.push(((index * 24) + 16) as i32);
+        new_index.push(((index * 24) + 17) as i32);
+        new_index.push(((index * 24) + 18) as i32);
+        new_index.push(((index * 24) + 16) as i32);
+        new_index.push(((index * 24) + 18) as i32);
+        new_index.push(((index * 24) + 19) as i32);
+
+        // Bottom
+        new_data.push(Vertex::new(
+            block.pos.x + nbs,
+            block.pos.y + nbs,
+            block.pos.z + nbs,
+            xw,
+            yh,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + nbs,
+            block.pos.y + nbs,
+            block.pos.z + pbs,
+            xw,
+            yy,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + pbs,
+            block.pos.y + nbs,
+            block.pos.z + pbs,
+            xx,
+            yy,
+        ));
+        new_data.push(Vertex::new(
+            block.pos.x + pbs,
+            block.pos.y + nbs,
+            block.pos.z + nbs,
+            xx,
+            yh,
+        ));
+
+        new_index.push(((index * 24) + 21) as i32);
+        new_index.push(((index * 24) + 20) as i32);
+        new_index.push(((index * 24) + 22) as i32);
+        new_index.push(((index * 24) + 22) as i32);
+        new_index.push(((index * 24) + 20) as i32);
+        new_index.push(((index * 24) + 23) as i32);
+
+        index += 1;
+    }
+    context.set_vertex_data(render, &new_data);
+    context.set_index_data(render, &new_index);
+}
+
+fn render_ui(entity: &mut EntityMut, resources: &mut ResourceRegistry, valuex: &RunSystemPhase) {
     match valuex {
         RunSystemPhase::Event(event) => match event {
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::MouseInput { state, button, .. } => {
                     if *button == MouseButton::Left && *state == ElementState::Pressed {
-                        let context = resources.get_mut::<RendererDevice>().unwrap();
-                        let sprites = resources.get_mut::<Vec<Sprite>>().unwrap();
-                        let active_controls = resources.get_mut::<FPSControls>().unwrap();
-                        let chunk = resources.get_mut::<Chunk>().unwrap();
-                        let block_idx = resources.get_mut::<BlockIndex>().unwrap();
-
-                        let render = entity.get_mut::<RenderComponent>().unwrap();
-
-                        let mut new_data = Vec::<Vertex>::new();
-                        let mut new_index = Vec::<i32>::new();
+                        let active_controls = resources.get::<FPSControls>().unwrap();
+                        let block_idx = resources.get::<BlockIndex>().unwrap();
 
                         let px = active_controls.camera.position.x.round();
                         let py = active_controls.camera.position.y.round();
                         let pz = active_controls.camera.position.z.round();
-                        let (width, height) = (626.0, 1782.0);
+                        let pos = Vec3::from_components(px, py, pz);
+
+                        // The cell is taken, by anything in the index or by a block placed
+                        // since the index was last updated, whose entity is still pending
+                        let index = resources.get::<SpatialIndex>().unwrap();
+                        if !index.at_point(&pos).is_empty() {
+                            return;
+                        }
+                        let mut chunk = resources.get_mut::<Chunk>().unwrap();
+                        let placed = chunk.blocks.iter().any(|block| {
+                            (block.pos.x, block.pos.y, block.pos.z) == (pos.x, pos.y, pos.z)
+                        });
+                        if placed {
+                            return;
+                        }
 
+                        resources
+                            .get_mut::<Commands>()
+                            .unwrap()
+                            .spawn(block_entity(&pos));
                         chunk.blocks.push(Block {
-                            pos: Vec3::from_components(px, py, pz),
+                            pos,
                             id: block_idx.0,
                             mode: BlockType::Full,
                         });
+                        drop(chunk);
 
-                        let mut index = 0;
-                        for block in &chunk.blocks {
-                            let value = if let Some(value) = sprites.get(block.id) {
-                                value
-                            } else {
-                                return;
-                            };
-
-                            let x = &value.frame.x;
-                            let y = &value.frame.y;
-                            let xw = x + &value.frame.w;
-                            let yh = y + &value.frame.h;
-
-                            let (xx, yy, xw, yh) = (x / width, y / height, xw / width, yh / height);
-
-                            println!("Real   {} {} {} {}", x, y, xw, yh);
-                            println!("Normal {} {} {} {}", xx, yy, xw, yh);
-
-                            let pbs = 0.5;
-                            let nbs = -0.5;
-
-
-                            // Top
-                            new_data.push(Vertex::new(block.pos.x + nbs, block.pos.y + pbs, block.pos.z + nbs, xx, yy));
-                            new_data.push(Vertex::new(block.pos.x + nbs, block.pos.y + pbs, block.pos.z + pbs, xx, yh));
-                            new_data.push(Vertex::new(block.pos.x + pbs, block.pos.y + pbs, block.pos.z + pbs, xw, yh));
-                            new_data.push(Vertex::new(block.pos.x + pbs, block.pos.y + pbs, block.pos.z + nbs, xw, yy));
-
-                            // Top
-                            new_index.push(((index * 24) + 0) as i32);
-                            new_index.push(((index * 24) + 1) as i32);
-                            new_index.push(((index * 24) + 2) as i32);
-                            new_index.push(((index * 24) + 0) as i32);
-                            new_index.push(((index * 24) + 2) as i32);
-                            new_index.push(((index * 24) + 3) as i32);
-
-
-                            // Left
-                            new_data.push(Vertex::new(block.pos.x + nbs, block.pos.y + pbs, block.pos.z + pbs, xx, yy));
-                            new_data.push(Vertex::new(block.pos.x + nbs, block.pos.y + nbs, block.pos.z + pbs, xw, yy));
-                            new_data.push(Vertex::new(block.pos.x + nbs, block.pos.y + nbs, block.pos.z + nbs, xw, yh));
-                            new_data.push(Vertex::new(block.pos.x + nbs, block.pos.y + pbs, block.pos.z + nbs, xx, yh));
-
-                            // Left
-                            new_index.push(((index * 24) + 5) as i32);
-                            new_index.push(((index * 24) + 4) as i32);
-                            new_index.push(((index * 24) + 6) as i32);
-                            new_index.push(((index * 24) + 6) as i32);
-                            new_index.push(((index * 24) + 4) as i32);
-                            new_index.push(((index * 24) + 7) as i32);
-
-
-                            // Right
-                            new_data.push(Vertex::new(block.pos.x + pbs, block.pos.y + pbs, block.pos.z + pbs, xw, yh));
-                            new_data.push(Vertex::new(block.pos.x + pbs, block.pos.y + nbs, block.pos.z + pbs, xx, yh));
-                            new_data.push(Vertex::new(block.pos.x + pbs, block.pos.y + nbs, block.pos.z + nbs, xx, yy));
-                            new_data.push(Vertex::new(block.pos.x + pbs, block.pos.y + pbs, block.pos.z + nbs, xw, yy));
-
-                            // Right
-                            new_index.push(((index * 24) + 8) as i32);
-                            new_index.push(((index * 24) + 9) as i32);
-                            new_index.push(((index * 24) + 10) as i32);
-                            new_index.push(((index * 24) + 8) as i32);
-                            new_index.push(((index * 24) + 10) as i32);
-                            new_index.push(((index * 24) + 11) as i32);
-
-
-
-                            // Front
-                            new_data.push(Vertex::new(block.pos.x + pbs, block.pos.y + pbs, block.pos.z + pbs, xw, yh));
-                            new_data.push(Vertex::new(block.pos.x + pbs, block.pos.y + nbs, block.pos.z + pbs, xw, yy));
-                            new_data.push(Vertex::new(block.pos.x + nbs, block.pos.y + nbs, block.pos.z + pbs, xx, yy));
-                            new_data.push(Vertex::new(block.pos.x + nbs, block.pos.y + pbs, block.pos.z + pbs, xx, yh));
-
-                            // Front
-                            new_index.push(((index * 24) + 13) as i32);
-                            new_index.push(((index * 24) + 12) as i32);
-                            new_index.push(((index * 24) + 14) as i32);
-                            new_index.push(((index * 24) + 15) as i32);
-                            new_index.push(((index * 24) + 14) as i32);
-                            new_index.push(((index * 24) + 12) as i32);
-
-
-
-                            // Back
-                            new_data.push(Vertex::new(block.pos.x + pbs, block.pos.y + pbs, block.pos.z + nbs, xx, yy));
-                            new_data.push(Vertex::new(block.pos.x + pbs, block.pos.y + nbs, block.pos.z + nbs, xx, yh));
-                            new_data.push(Vertex::new(block.pos.x + nbs, block.pos.y + nbs, block.pos.z + nbs, xw, yh));
-                            new_data.push(Vertex::new(block.pos.x + nbs, block.pos.y + pbs, block.pos.z + nbs, xw, yy));
-
-                            new_index.push(((index * 24) + 16) as i32);
-                            new_index.push(((index * 24) + 17) as i32);
-                            new_index.push(((index * 24) + 18) as i32);
-                            new_index.push(((index * 24) + 16) as i32);
-                            new_index.push(((index * 24) + 18) as i32);
-                            new_index.push(((index * 24) + 19) as i32);
-
-
-                            // Bottom
-                            new_data.push(Vertex::new(block.pos.x + nbs, block.pos.y + nbs, block.pos.z + nbs, xw, yh));
-                            new_data.push(Vertex::new(block.pos.x + nbs, block.pos.y + nbs, block.pos.z + pbs, xw, yy));
-                            new_data.push(Vertex::new(block.pos.x + pbs, block.pos.y + nbs, block.pos.z + pbs, xx, yy));
-                            new_data.push(Vertex::new(block.pos.x + pbs, block.pos.y + nbs, block.pos.z + nbs, xx, yh));
-
-                            new_index.push(((index * 24) + 21) as i32);
-                            new_index.push(((index * 24) + 20) as i32);
-                            new_index.push(((index * 24) + 22) as i32);
-                            new_index.push(((index * 24) + 22) as i32);
-                            new_index.push(((index * 24) + 20) as i32);
-                            new_index.push(((index * 24) + 23) as i32);
-
-                            index += 1;
-                        }
-                        context.set_vertex_data(render, &new_data);
-                        context.set_index_data(render, &new_index);
+                        build_chunk_mesh(entity, resources);
                     }
                 }
                 _ => (),
@@ -217,7 +419,13 @@ fn render_ui(entity: &mut Entity, resources: &mut ResourceRegistry, valuex: &Run
 
 struct BlockIndex(usize);
 
-fn emit_events(resources: &mut ResourceRegistry, value: &RunSystemPhase) {
+fn emit_events(
+    mut block_idx: ResMut<BlockIndex>,
+    sprites: Res<Vec<Sprite>>,
+    mut dbg_info: ResMut<DebugInfo>,
+    mut fps_events: EventWriter<FPSUpdateEvent>,
+    value: &RunSystemPhase,
+) {
     match value {
         RunSystemPhase::Event(event) => match event {
             Event::WindowEvent { event, .. } => match event {
@@ -228,11 +436,6 @@ fn emit_events(resources: &mut ResourceRegistry, value: &RunSystemPhase) {
                 } => {
                     if input.state == ElementState::Pressed {
                         if let Some(key) = input.virtual_keycode {
-                            let block_idx = resources.get_mut::<BlockIndex>().unwrap();
-                            let sprites = resources.get_mut::<Vec<Sprite>>().unwrap();
-                            let dbg_info = resources.get_mut::<DebugInfo>().unwrap();
-                            let event_channel = resources.get_mut::<EventChannel>().unwrap();
-
                             let original = block_idx.0;
 
                             if key == VirtualKeyCode::Add {
@@ -240,15 +443,14 @@ fn emit_events(resources: &mut ResourceRegistry, value: &RunSystemPhase) {
                             }
 
                             if key == VirtualKeyCode::Subtract {
-                                let block_idx = resources.get_mut::<BlockIndex>().unwrap();
-                                if block_idx.0 < 1 {
+                                if block_idx.0 > 0 {
                                     block_idx.0 -= 1;
                                 }
                             }
 
                             if let Some(sprite) = &sprites.get(block_idx.0) {
                                 dbg_info.set(DebugKey::CurrentBlock, sprite.filename.clone());
-                                event_channel.issue(FPSUpdateEvent);
+                                fps_events.send(FPSUpdateEvent);
                             } else {
                                 block_idx.0 = original;
                             }
@@ -263,60 +465,86 @@ fn emit_events(resources: &mut ResourceRegistry, value: &RunSystemPhase) {
     }
 }
 
-pub fn load(ecs: &mut ECS) {
-    let context = ecs.resources.get_mut::<RendererDevice>().unwrap();
-
-    let mut file = File::open("textures/out.json").unwrap();
-    let mut data = String::new();
-    file.read_to_string(&mut data).unwrap();
-
-    let sprites: Vec<Sprite> = serde_json::from_str(&data).expect("JSON was not well-formatted");
+pub struct TexturedPlugin;
+
+impl Plugin for TexturedPlugin {
+    fn build(&self, app: &mut App) {
+        let ecs = &mut app.ecs;
+        let mut context = ecs.resources.get_mut::<RendererDevice>().unwrap();
+
+        let mut file = File::open("textures/out.json").unwrap();
+        let mut data = String::new();
+        file.read_to_string(&mut data).unwrap();
+
+        let sprites: Vec<Sprite> =
+            serde_json::from_str(&data).expect("JSON was not well-formatted");
+
+        let (width, height) = (626.0, 1782.0);
+        let value = sprites.get(2).expect(
+            "textures/out.json has to hold at least 3 sprites, the third is the chunk texture",
+        );
+        let x = &value.frame.x;
+        let y = &value.frame.y;
+        let xw = x + &value.frame.w;
+        let yh = y + &value.frame.h;
+        let (x, y, xw, yh) = (x / width, y / height, xw / width, yh / height);
+
+        let text = context.register_texture(Texture::new_initialized(
+            TextureWrapping::ClampToEdge,
+            TextureFiltering::Pixelated,
+            TextureStorage::from_image(&Path::new("textures/out.png")),
+        ));
+
+        let mesh = context.new_mesh(
+            &Path::new("shaders/single-texture.glsl"),
+            vec![
+                Vertex::new(0.5, 2.0 + 0.5, 0.0, xw, yh),
+                Vertex::new(-0.5, 2.0 + -0.5, 0.0, x, y),
+                Vertex::new(0.5, 2.0 + -0.5, 0.0, xw, y),
+                Vertex::new(-0.5, 2.0 + 0.5, 0.0, x, yh),
+            ],
+            Some(vec![0, 1, 2, 0, 3, 1]),
+            vec![text],
+            None,
+        );
+        drop(context);
+
+        ecs.add_entity(Entity::new().with(IsChunkRenderer).with(mesh));
+
+        ecs.resources.set(BlockIndex(0));
+        ecs.resources.set::<Vec<Sprite>>(sprites);
+        ecs.resources.set(Chunk { blocks: Vec::new() });
+        ecs.register_resource::<Chunk>("Chunk");
+        ecs.add_spatial_index(1.0);
+        ecs.on_scene_load(|ecs, _| {
+            respawn_blocks(ecs);
+            let mut events = ecs.resources.get_mut::<Events<ChunkLoadedEvent>>().unwrap();
+            events.send(ChunkLoadedEvent);
+        });
+        ecs.add_before_service(Service::at_event(emit_events));
+
+        ecs.add_system(System::at_event(
+            EntityShapeBuilder::new()
+                .with::<IsChunkRenderer>()
+                .with::<RenderComponent>()
+                .build(),
+            render_ui,
+        ));
+
+        ecs.add_system(System::at_update(
+            ChunkLoadedEvent,
+            EntityShapeBuilder::new()
+                .with::<IsChunkRenderer>()
+                .with::<RenderComponent>()
+                .build(),
+            rebuild_chunk,
+        ));
+    }
 
-    let (width, height) = (626.0, 1782.0);
-    let value = if let Some(value) = sprites.get(2) {
-        value
-    } else {
-        return;
-    };
-    let x = &value.frame.x;
-    let y = &value.frame.y;
-    let xw = x + &value.frame.w;
-    let yh = y + &value.frame.h;
-    let (x, y, xw, yh) = (x / width, y / height, xw / width, yh / height);
-
-    let text = context.register_texture(Texture::new_initialized(
-        TextureWrapping::ClampToEdge,
-        TextureFiltering::Pixelated,
-        TextureStorage::from_image(&Path::new("textures/out.png")),
-    ));
-
-    ecs.add_entity(
-        Entity::new()
-            .with(IsChunkRenderer)
-            .with(context.new_mesh(
-                &Path::new("shaders/single-texture.glsl"),
-                vec![
-                    Vertex::new(0.5, 2.0 + 0.5, 0.0, xw, yh),
-                    Vertex::new(-0.5, 2.0 + -0.5, 0.0, x, y),
-                    Vertex::new(0.5, 2.0 + -0.5, 0.0, xw, y),
-                    Vertex::new(-0.5, 2.0 + 0.5, 0.0, x, yh),
-                ],
-                Some(vec![0, 1, 2, 0, 3, 1]),
-                vec![text],
-                None,
-            )),
-    );
-
-    ecs.resources.set(BlockIndex(0));
-    ecs.resources.set::<Vec<Sprite>>(sprites);
-    ecs.resources.set(Chunk { blocks: Vec::new() });
-    ecs.add_before_service(Service::at_event(emit_events));
-
-    ecs.add_system(System::at_event(
-        EntityShapeBuilder::new()
-            .with::<IsChunkRenderer>()
-            .with::<RenderComponent>()
-            .build(),
-        render_ui,
-    ));
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![
+            PluginId::of::<DebugInfoPlugin>(),
+            PluginId::of::<ActiveControlsPlugin>(),
+        ]
+    }
 }
@@ -1,12 +1,38 @@
-use crate::time::TimeContext;
-use ecs::{ResourceRegistry, RunSystemPhase, Service, ECS};
+use crate::modules::render_all::RenderAllPlugin;
+use crate::time::{TimeContext, DEFAULT_MAX_CATCHUP, DEFAULT_UPDATE_RATE};
+use ecs::{App, Plugin, PluginId, ResMut, Service};
 
-fn after_frame(resources: &mut ResourceRegistry, _value: &RunSystemPhase) {
-    let time_context = resources.get_mut::<TimeContext>().unwrap();
+fn after_frame(mut time_context: ResMut<TimeContext>) {
     time_context.tick();
 }
 
-pub fn load(ecs: &mut ECS) {
-    ecs.resources.set(TimeContext::new());
-    ecs.add_after_service(Service::at_render(after_frame));
+pub struct TimePlugin {
+    // Fixed updates per second and how many of them a slow frame may run
+    pub update_rate: u32,
+    pub max_catchup: u32,
+}
+
+impl Default for TimePlugin {
+    fn default() -> Self {
+        Self {
+            update_rate: DEFAULT_UPDATE_RATE,
+            max_catchup: DEFAULT_MAX_CATCHUP,
+        }
+    }
+}
+
+impl Plugin for TimePlugin {
+    fn build(&self, app: &mut App) {
+        let ecs = &mut app.ecs;
+        let mut time_context = TimeContext::new();
+        time_context.set_update_rate(self.update_rate);
+        time_context.set_max_catchup(self.max_catchup);
+
+        ecs.resources.set(time_context);
+        ecs.add_after_service(Service::at_render(after_frame).before("swap_buffers"));
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![PluginId::of::<RenderAllPlugin>()]
+    }
 }
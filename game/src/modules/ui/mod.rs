@@ -1,24 +1,29 @@
 mod vertex;
 
-use crate::modules::{debug_ui::IsDebugUITag, on_resize::ViewportSizedUITag};
-use ecs::{Entity, ECS};
+use crate::modules::{
+    debug_ui::IsDebugUITag, on_resize::ViewportSizedUITag, render_all::RenderAllPlugin,
+};
+use ecs::{App, Entity, Plugin, PluginId};
 use renderer::{
     MeshFlags, RendererDevice, Texture, TextureFiltering, TextureStorage, TextureWrapping,
 };
 use std::path::Path;
 use vertex::Vertex;
 
-pub fn load(ecs: &mut ECS) {
-    let context = ecs.resources.get_mut::<RendererDevice>().unwrap();
+pub struct UiPlugin;
+
+impl Plugin for UiPlugin {
+    fn build(&self, app: &mut App) {
+        let ecs = &mut app.ecs;
+        let mut context = ecs.resources.get_mut::<RendererDevice>().unwrap();
 
-    let text_id = context.register_texture(Texture::new_initialized(
-        TextureWrapping::ClampToEdge,
-        TextureFiltering::Pixelated,
-        TextureStorage::from_canvas(500, 500),
-    ));
+        let text_id = context.register_texture(Texture::new_initialized(
+            TextureWrapping::ClampToEdge,
+            TextureFiltering::Pixelated,
+            TextureStorage::from_canvas(500, 500),
+        ));
 
-    ecs.add_entity(
-        Entity::new()
+        let debug_ui = Entity::new()
             .with(IsDebugUITag)
             .with(ViewportSizedUITag)
             .with(context.new_mesh(
@@ -32,6 +37,13 @@ pub fn load(ecs: &mut ECS) {
                 Some(vec![0, 1, 2, 0, 3, 1]),
                 vec![text_id],
                 MeshFlags::new().no_depth().opt(),
-            )),
-    );
+            ));
+        drop(context);
+
+        ecs.add_entity(debug_ui);
+    }
+
+    fn dependencies(&self) -> Vec<PluginId> {
+        vec![PluginId::of::<RenderAllPlugin>()]
+    }
 }
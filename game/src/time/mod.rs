@@ -52,10 +52,15 @@ pub struct TimeContext {
     last_instant: time::Instant,
     frame_durations: LogBuffer<time::Duration>,
     residual_update_dt: time::Duration,
+    fixed_update_dt: time::Duration,
+    max_catchup: u32,
+    catchup: u32,
     frame_count: usize,
 }
 
 const TIME_LOG_FRAMES: usize = 200;
+pub const DEFAULT_UPDATE_RATE: u32 = 60;
+pub const DEFAULT_MAX_CATCHUP: u32 = 5;
 
 impl TimeContext {
     pub fn new() -> TimeContext {
@@ -65,6 +70,9 @@ impl TimeContext {
             last_instant: time::Instant::now(),
             frame_durations: LogBuffer::new(TIME_LOG_FRAMES, initial_dt),
             residual_update_dt: time::Duration::from_secs(0),
+            fixed_update_dt: time::Duration::from_secs(1) / DEFAULT_UPDATE_RATE,
+            max_catchup: DEFAULT_MAX_CATCHUP,
+            catchup: 0,
             frame_count: 0,
         }
     }
@@ -77,6 +85,58 @@ impl TimeContext {
         self.frame_count += 1;
 
         self.residual_update_dt += time_since_last;
+        self.catchup = 0;
+    }
+
+    // Steps per second of the fixed update phase, at least one and at most one per nanosecond
+    pub fn set_update_rate(&mut self, rate: u32) {
+        assert!(
+            rate > 0,
+            "The fixed update rate has to be at least 1 step per second"
+        );
+        let fixed_update_dt = time::Duration::from_secs(1) / rate;
+        assert!(
+            fixed_update_dt > time::Duration::from_secs(0),
+            "The fixed update rate has to be at most 1_000_000_000 steps per second"
+        );
+        self.fixed_update_dt = fixed_update_dt;
+    }
+
+    // Most fixed steps run in one frame, the time left over after them is dropped so a slow
+    // frame doesn't make the next ones slower still
+    pub fn set_max_catchup(&mut self, steps: u32) {
+        assert!(
+            steps >= 1,
+            "The fixed update has to catch up at least 1 step"
+        );
+        self.max_catchup = steps;
+    }
+
+    // Takes one fixed step out of the time accumulated by `tick`, true while there is one
+    pub fn check_update(&mut self) -> bool {
+        if self.residual_update_dt < self.fixed_update_dt {
+            return false;
+        }
+
+        if self.catchup >= self.max_catchup {
+            let residual = self.residual_update_dt.as_nanos() % self.fixed_update_dt.as_nanos();
+            self.residual_update_dt = time::Duration::from_nanos(residual as u64);
+            return false;
+        }
+
+        self.residual_update_dt -= self.fixed_update_dt;
+        self.catchup += 1;
+        true
+    }
+
+    pub fn fixed_delta(&self) -> f64 {
+        duration_to_f64(self.fixed_update_dt)
+    }
+
+    // How far the render is between the last fixed step and the next one, from 0 to 1, for
+    // interpolating state the fixed update moves
+    pub fn alpha(&self) -> f64 {
+        duration_to_f64(self.residual_update_dt) / duration_to_f64(self.fixed_update_dt)
     }
 
     pub fn delta(&self) -> time::Duration {
@@ -113,3 +173,64 @@ pub fn fps(tc: &TimeContext) -> f64 {
     let seconds_per_frame = duration_to_f64(duration_per_frame);
     1.0 / seconds_per_frame
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps(tc: &mut TimeContext) -> u32 {
+        let mut steps = 0;
+        while tc.check_update() {
+            steps += 1;
+        }
+        steps
+    }
+
+    #[test]
+    fn fixed_steps_consume_the_accumulated_time() {
+        let mut tc = TimeContext::new();
+        tc.set_update_rate(10);
+        tc.residual_update_dt = time::Duration::from_millis(250);
+
+        assert_eq!(steps(&mut tc), 2);
+        assert_eq!(tc.residual_update_dt, time::Duration::from_millis(50));
+        assert!((tc.alpha() - 0.5).abs() < 1e-9);
+        assert!((tc.fixed_delta() - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn catchup_is_bounded_and_drops_the_backlog() {
+        let mut tc = TimeContext::new();
+        tc.set_update_rate(10);
+        tc.set_max_catchup(3);
+        tc.residual_update_dt = time::Duration::from_millis(1050);
+
+        assert_eq!(steps(&mut tc), 3);
+        assert_eq!(tc.residual_update_dt, time::Duration::from_millis(50));
+
+        tc.tick();
+        assert_eq!(tc.catchup, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 1 step per second")]
+    fn zero_update_rate_is_rejected() {
+        TimeContext::new().set_update_rate(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "at most 1_000_000_000 steps per second")]
+    fn update_rate_above_a_step_per_nanosecond_is_rejected() {
+        let mut tc = TimeContext::new();
+        tc.set_update_rate(1_000_000_000);
+        assert_eq!(tc.fixed_update_dt, time::Duration::from_nanos(1));
+
+        tc.set_update_rate(1_000_000_001);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 1 step")]
+    fn zero_max_catchup_is_rejected() {
+        TimeContext::new().set_max_catchup(0);
+    }
+}
@@ -2,6 +2,7 @@
 use super::{Quat, Vec3};
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Mat4 {
     pub elements: [f32; 16],
 }
@@ -1,6 +1,8 @@
 // REF:: https://github.com/mrdoob/three.js/blob/dev/src/math/Quaternion.js
 use super::{Mat4, Vec3};
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Quat {
 	pub x: f32,
 	pub y: f32,
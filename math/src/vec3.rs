@@ -3,7 +3,8 @@
 use super::{one_when_zero, Mat4, Quat, Vec2};
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vec3 {
     pub x: f32,
     pub y: f32,
@@ -1,5 +1,6 @@
 #[repr(C)]
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vec4 {
     pub x: f32,
     pub y: f32,
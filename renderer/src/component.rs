@@ -17,16 +17,43 @@ pub enum TextureFiltering {
     Pixelated = gl::NEAREST as isize,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttachmentKind {
+    Color,
+    // Floating point color, for values above 1 until tonemapping
+    HdrColor,
+    Depth,
+}
+
+impl AttachmentKind {
+    pub fn is_color(self) -> bool {
+        self != AttachmentKind::Depth
+    }
+}
+
 pub enum TextureStorage {
     Canvas2D(SkiaCanvas),
     Image(DynamicImage),
     Zeroed,
+    // Only allocated on the GPU, filled by rendering into a `RenderTarget`
+    Attachment {
+        width: u32,
+        height: u32,
+        kind: AttachmentKind,
+    },
 }
 
 impl TextureStorage {
     pub fn from_canvas(width: u32, height: u32) -> TextureStorage {
         TextureStorage::Canvas2D(SkiaCanvas::new(500, 500))
     }
+    pub fn attachment(width: u32, height: u32, kind: AttachmentKind) -> TextureStorage {
+        TextureStorage::Attachment {
+            width,
+            height,
+            kind,
+        }
+    }
     pub fn from_image<P>(path: P) -> TextureStorage
     where
         P: AsRef<Path>,
@@ -74,8 +101,7 @@ impl Texture {
 }
 
 pub struct RenderComponent {
-    pub material: GLShader,
-    pub textures: Vec<Index<Texture>>,
+    pub material: Index<Material>,
     pub vao: GLVertexArray,
     pub ibo: Option<GLBuffer>,
     pub vbo: GLBuffer,
@@ -87,8 +113,7 @@ pub struct RenderComponent {
 
 impl RenderComponent {
     pub fn new(
-        material: GLShader,
-        textures: Vec<Index<Texture>>,
+        material: Index<Material>,
         vao: GLVertexArray,
         vbo: GLBuffer,
         ibo: Option<GLBuffer>,
@@ -99,7 +124,6 @@ impl RenderComponent {
         let is_indexed = ibo.is_some();
         let component = Self {
             material,
-            textures,
             vao,
             ibo,
             vbo,
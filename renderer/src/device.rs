@@ -1,9 +1,17 @@
 use super::*;
 use generational_arena::{Arena, Index};
-use glutin::{ContextWrapper, PossiblyCurrent};
-use image::GenericImageView;
+use glutin::dpi::PhysicalSize;
+use glutin::platform::unix::HeadlessContextExt;
+use glutin::{
+    Api, Context, ContextBuilder, ContextError, ContextWrapper, CreationError, GlProfile,
+    GlRequest, PossiblyCurrent,
+};
+use image::{imageops, GenericImageView, ImageBuffer, RgbaImage};
 use lazy_static::lazy_static;
-use std::path::Path;
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use winit::window::Window;
 
 const DEFAULT_TEXTURE_SIZE: u32 = 2;
@@ -55,9 +63,40 @@ impl Default for MeshFlags {
     }
 }
 
+#[derive(Debug)]
+pub enum HeadlessError {
+    Creation(CreationError),
+    MakeCurrent(ContextError),
+}
+
+impl fmt::Display for HeadlessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeadlessError::Creation(error) => {
+                write!(f, "Cannot create the offscreen context: {}", error)
+            }
+            HeadlessError::MakeCurrent(error) => {
+                write!(f, "Cannot make the offscreen context current: {}", error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HeadlessError {}
+
 pub struct RendererDevice {
     pub ctx: OpenGLContext,
     pub texture_cache: Arena<Texture>,
+    shaders: Arena<Shader>,
+    materials: Arena<Material>,
+    // Programs are compiled once per file and vertex format
+    shader_cache: HashMap<(PathBuf, TypeId), Index<Shader>>,
+    // Only set for `headless`, a windowed device draws through the context of the window
+    offscreen: Option<Context<PossiblyCurrent>>,
+    // Viewport of the default framebuffer while a render target is bound
+    saved_viewport: Option<(u32, u32)>,
+    // Objects passed to the `destroy_*` functions, deleted by `end_frame`
+    pending_deletions: Vec<GLObject>,
 }
 
 impl RendererDevice {
@@ -65,15 +104,77 @@ impl RendererDevice {
         let mut value = Self {
             ctx: OpenGLContext::build_initialize(window_context),
             texture_cache: Arena::new(),
+            shaders: Arena::new(),
+            materials: Arena::new(),
+            shader_cache: HashMap::new(),
+            offscreen: None,
+            saved_viewport: None,
+            pending_deletions: Vec::new(),
         };
 
         value.resize(500, 500);
         value
     }
 
+    // Draws into a `width` x `height` OSMesa buffer instead of a window, so it runs without a
+    // display or a GPU on Mesa's software rasterizer. The context is current on the calling
+    // thread, the size is fixed and `read_pixels` gets the result back
+    pub fn headless(width: u32, height: u32) -> Result<Self, HeadlessError> {
+        let context = ContextBuilder::new()
+            .with_gl(GlRequest::Specific(Api::OpenGl, (4, 5)))
+            .with_gl_profile(GlProfile::Core)
+            .build_osmesa(PhysicalSize::new(width, height))
+            .map_err(HeadlessError::Creation)?;
+        let context = unsafe { context.make_current() }
+            .map_err(|(_, error)| HeadlessError::MakeCurrent(error))?;
+
+        let mut value = Self {
+            ctx: OpenGLContext::build_from_context(&context),
+            texture_cache: Arena::new(),
+            shaders: Arena::new(),
+            materials: Arena::new(),
+            shader_cache: HashMap::new(),
+            offscreen: Some(context),
+            saved_viewport: None,
+            pending_deletions: Vec::new(),
+        };
+
+        value.resize(width, height);
+        Ok(value)
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.offscreen.is_some()
+    }
+
+    // Contents of the viewport in the bound framebuffer, the screen or a render target, with the
+    // top row first
+    pub fn read_pixels(&self) -> RgbaImage {
+        let (width, height) = self.ctx.viewport.size();
+        let mut data = vec![0; (width * height * 4) as usize];
+
+        gl::pixel_store_i(gl::PACK_ALIGNMENT, 1);
+        gl::read_pixels(0, 0, width, height, gl::RGBA, gl::UNSIGNED_BYTE, &mut data);
+
+        let image: RgbaImage = ImageBuffer::from_raw(width, height, data).unwrap();
+        imageops::flip_vertical(&image)
+    }
+
     pub fn register_texture(&mut self, texture_object: Texture) -> Index<Texture> {
         let mut texture_object = texture_object;
-        let gl_texture = self.ctx.texture.create_texture(gl::TEXTURE_2D, None);
+        let format = match texture_object.storage {
+            TextureStorage::Attachment {
+                kind: AttachmentKind::Depth,
+                ..
+            } => Some(TextureFormat::depth()),
+            TextureStorage::Attachment {
+                kind: AttachmentKind::HdrColor,
+                ..
+            } => Some(TextureFormat::hdr()),
+            _ => None,
+        };
+
+        let gl_texture = self.ctx.texture.create_texture(gl::TEXTURE_2D, format);
         self.ctx
             .texture
             .set_active_texture_unit(gl::TEXTURE0)
@@ -81,6 +182,13 @@ impl RendererDevice {
             .set_wrappings(texture_object.wrapping as u32)
             .set_mig_mag_filter(texture_object.filtering as u32);
 
+        if let TextureStorage::Attachment { width, height, .. } = texture_object.storage {
+            self.ctx
+                .texture
+                .set_raw_data(width, height, std::ptr::null());
+            texture_object.needs_update = false;
+        }
+
         texture_object.set_handle(gl_texture);
 
         self.texture_cache.insert(texture_object)
@@ -90,6 +198,24 @@ impl RendererDevice {
         self.texture_cache.get_mut(index)
     }
 
+    // Frees the GL texture too, meshes still pointing at `index` skip it when drawing
+    pub fn delete_texture(&mut self, index: Index<Texture>) -> Option<Texture> {
+        let texture = self.texture_cache.remove(index)?;
+        if let Some(handle) = texture.handle {
+            self.ctx.texture.delete_texture(handle);
+        }
+        Some(texture)
+    }
+
+    // Same as `delete_texture` but the GL texture lives until `end_frame`
+    pub fn unregister_texture(&mut self, index: Index<Texture>) -> Option<Texture> {
+        let texture = self.texture_cache.remove(index)?;
+        if let Some(handle) = texture.handle {
+            self.pending_deletions.push(GLObject::Texture(handle));
+        }
+        Some(texture)
+    }
+
     pub fn set_index_data<T>(&mut self, component: &mut RenderComponent, data: &[T]) {
         if let Some(ibo) = &component.ibo {
             self.ctx.buffer.bind_buffer(ibo).set_data(data);
@@ -106,17 +232,276 @@ impl RendererDevice {
         }
     }
 
+    // Sets the size of the screen, kept for later while a render target is bound
     pub fn resize(&mut self, width: u32, height: u32) {
-        self.ctx.viewport.resize(width, height);
+        match self.saved_viewport.as_mut() {
+            Some(size) => *size = (width, height),
+            None => self.ctx.viewport.resize(width, height),
+        }
     }
 
-    pub fn new_mesh<T: VertexFormat>(
+    fn attachment_size(
+        &self,
+        texture: Option<Index<Texture>>,
+        expected: AttachmentKind,
+    ) -> Result<Option<(u32, u32)>, RenderTargetError> {
+        let texture = match texture {
+            Some(index) => self
+                .texture_cache
+                .get(index)
+                .ok_or(RenderTargetError::MissingTexture)?,
+            None => return Ok(None),
+        };
+
+        match texture.storage {
+            TextureStorage::Attachment {
+                width,
+                height,
+                kind,
+            } if kind.is_color() == expected.is_color() => Ok(Some((width, height))),
+            TextureStorage::Attachment { .. } => Err(RenderTargetError::WrongKind { expected }),
+            _ => Err(RenderTargetError::NotAnAttachment),
+        }
+    }
+
+    // Both textures have to be registered with `TextureStorage::attachment` and be the same size
+    pub fn new_render_target(
+        &mut self,
+        color: Option<Index<Texture>>,
+        depth: Option<Index<Texture>>,
+    ) -> Result<RenderTarget, RenderTargetError> {
+        let color_size = self.attachment_size(color, AttachmentKind::Color)?;
+        let depth_size = self.attachment_size(depth, AttachmentKind::Depth)?;
+        let (width, height) = match (color_size, depth_size) {
+            (Some(color), Some(depth)) if color != depth => {
+                return Err(RenderTargetError::SizeMismatch { color, depth })
+            }
+            (Some(size), _) | (None, Some(size)) => size,
+            (None, None) => return Err(RenderTargetError::NoAttachments),
+        };
+
+        let previous = self.ctx.framebuffer.bound();
+        let framebuffer = self.ctx.framebuffer.create();
+        self.ctx.framebuffer.bind(&framebuffer);
+
+        if let Some(index) = color {
+            let handle = self.texture_cache.get(index).unwrap().handle.unwrap();
+            self.ctx
+                .framebuffer
+                .attach_texture(gl::COLOR_ATTACHMENT0, &handle);
+        }
+        if let Some(index) = depth {
+            let handle = self.texture_cache.get(index).unwrap().handle.unwrap();
+            self.ctx
+                .framebuffer
+                .attach_texture(gl::DEPTH_ATTACHMENT, &handle);
+        }
+        self.ctx.framebuffer.set_color_enabled(color.is_some());
+
+        let status = self.ctx.framebuffer.status();
+        self.ctx.framebuffer.bind(&previous);
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            self.ctx.framebuffer.delete(&framebuffer);
+            return Err(RenderTargetError::Incomplete(status));
+        }
+
+        Ok(RenderTarget {
+            framebuffer,
+            color,
+            depth,
+            width,
+            height,
+        })
+    }
+
+    // Color and depth textures of `width` x `height`, registered in the texture cache
+    pub fn create_render_target(
+        &mut self,
+        width: u32,
+        height: u32,
+        filtering: TextureFiltering,
+    ) -> Result<RenderTarget, RenderTargetError> {
+        self.create_target(width, height, filtering, AttachmentKind::Color)
+    }
+
+    // Same with a floating point color texture, for post-processing before tonemapping
+    pub fn create_hdr_render_target(
+        &mut self,
+        width: u32,
+        height: u32,
+        filtering: TextureFiltering,
+    ) -> Result<RenderTarget, RenderTargetError> {
+        self.create_target(width, height, filtering, AttachmentKind::HdrColor)
+    }
+
+    fn create_target(
+        &mut self,
+        width: u32,
+        height: u32,
+        filtering: TextureFiltering,
+        color: AttachmentKind,
+    ) -> Result<RenderTarget, RenderTargetError> {
+        let color = self.register_texture(Texture::new_initialized(
+            TextureWrapping::ClampToEdge,
+            filtering,
+            TextureStorage::attachment(width, height, color),
+        ));
+        let depth = self.register_texture(Texture::new_initialized(
+            TextureWrapping::ClampToEdge,
+            TextureFiltering::Pixelated,
+            TextureStorage::attachment(width, height, AttachmentKind::Depth),
+        ));
+
+        let target = self.new_render_target(Some(color), Some(depth));
+        if target.is_err() {
+            self.delete_texture(color);
+            self.delete_texture(depth);
+        }
+        target
+    }
+
+    // Everything drawn until `unbind_render_target` goes into `target`, the viewport covers it
+    pub fn bind_render_target(&mut self, target: &RenderTarget) {
+        if self.saved_viewport.is_none() {
+            self.saved_viewport = Some(self.ctx.viewport.size());
+        }
+
+        self.ctx.framebuffer.bind(&target.framebuffer);
+        self.ctx.viewport.resize(target.width, target.height);
+    }
+
+    // Back to drawing on the screen
+    pub fn unbind_render_target(&mut self) {
+        self.ctx.framebuffer.bind_default();
+        if let Some((width, height)) = self.saved_viewport.take() {
+            self.ctx.viewport.resize(width, height);
+        }
+    }
+
+    // Frees the framebuffer, its textures stay in the cache
+    pub fn delete_render_target(&mut self, target: &RenderTarget) {
+        if self.ctx.framebuffer.bound().0 == target.framebuffer.0 {
+            self.unbind_render_target();
+        }
+        self.ctx.framebuffer.delete(&target.framebuffer);
+    }
+
+    // Frees the framebuffer and unregisters its textures at the end of the frame
+    pub fn destroy_render_target(&mut self, target: &RenderTarget) {
+        self.pending_deletions
+            .push(GLObject::Framebuffer(GLFramebuffer(target.framebuffer.0)));
+        for index in target.color.iter().chain(target.depth.iter()) {
+            self.unregister_texture(*index);
+        }
+    }
+
+    fn load_shader<V: VertexFormat + 'static>(&mut self, path: &Path) -> Index<Shader> {
+        let key = (path.to_path_buf(), TypeId::of::<V>());
+        if let Some(index) = self.shader_cache.get(&key) {
+            return *index;
+        }
+
+        let (vertex_shader, fragment_shader) = read_shader_file(path);
+        let program = self
+            .ctx
+            .program
+            .create_from_strings::<V>(vertex_shader.clone(), fragment_shader);
+
+        let index = self.shaders.insert(Shader::new(
+            program,
+            path.to_path_buf(),
+            TypeId::of::<V>(),
+            vertex_shader,
+        ));
+        self.shader_cache.insert(key, index);
+        index
+    }
+
+    // New instance of the shader at `path`, compiling it only if no material uses it yet. It
+    // lives as long as meshes are drawn with it, or until `delete_material` if none ever is
+    pub fn new_material<V: VertexFormat + 'static>(
+        &mut self,
+        path: &Path,
+        textures: Vec<Index<Texture>>,
+    ) -> Index<Material> {
+        let shader = self.load_shader::<V>(path);
+        self.shaders.get_mut(shader).unwrap().users += 1;
+        self.materials.insert(Material::new(shader, textures))
+    }
+
+    pub fn material(&self, index: Index<Material>) -> Option<&Material> {
+        self.materials.get(index)
+    }
+
+    pub fn material_mut(&mut self, index: Index<Material>) -> Option<&mut Material> {
+        self.materials.get_mut(index)
+    }
+
+    pub fn shader(&self, index: Index<Shader>) -> Option<&Shader> {
+        self.shaders.get(index)
+    }
+
+    // Only for materials no mesh was created with, false otherwise
+    pub fn delete_material(&mut self, index: Index<Material>) -> bool {
+        match self.materials.get(index) {
+            Some(material) if material.users == 0 => {}
+            _ => return false,
+        }
+
+        let material = self.materials.remove(index).unwrap();
+        if let Some(program) = self.release_shader(material.shader) {
+            self.ctx.program.delete(&program);
+        }
+        true
+    }
+
+    // Returns the program once no material uses it anymore
+    fn release_shader(&mut self, index: Index<Shader>) -> Option<GLShader> {
+        let shader = self.shaders.get_mut(index)?;
+        shader.users -= 1;
+        if shader.users > 0 {
+            return None;
+        }
+
+        let shader = self.shaders.remove(index).unwrap();
+        self.shader_cache
+            .remove(&(shader.path.clone(), shader.vertex_format));
+        Some(shader.program)
+    }
+
+    // Returns the program once neither the material nor another one uses it anymore
+    fn release_material(&mut self, index: Index<Material>) -> Option<GLShader> {
+        let material = self.materials.get_mut(index)?;
+        material.users -= 1;
+        if material.users > 0 {
+            return None;
+        }
+
+        let material = self.materials.remove(index).unwrap();
+        self.release_shader(material.shader)
+    }
+
+    // Compiles the program at `material_path` the first time, meshes created from the same file
+    // and vertex format after that share it with their own material instance
+    pub fn new_mesh<T: VertexFormat + 'static>(
         &mut self,
         material_path: &Path,
         geometry: Vec<T>,
         index_buffer: Option<Vec<i32>>,
         textures: Vec<Index<Texture>>,
         flags: Option<MeshFlags>,
+    ) -> RenderComponent {
+        let material = self.new_material::<T>(material_path, textures);
+        self.new_mesh_with_material(material, geometry, index_buffer, flags)
+    }
+
+    // Meshes created with the same material share its parameters and textures too
+    pub fn new_mesh_with_material<T: VertexFormat>(
+        &mut self,
+        material: Index<Material>,
+        geometry: Vec<T>,
+        index_buffer: Option<Vec<i32>>,
+        flags: Option<MeshFlags>,
     ) -> RenderComponent {
         let flags = flags.unwrap_or_default();
         let vao = self.ctx.vertex_array.create();
@@ -140,27 +525,82 @@ impl RendererDevice {
             (None, geometry.len())
         };
 
-        let program = self.ctx.program.create_from_file::<T>(material_path);
+        let instance = self.materials.get_mut(material).unwrap();
+        instance.users += 1;
+        let shader = self.shaders.get(instance.shader).unwrap();
+        self.ctx.program.bind(&shader.program);
+        shader.configure_vertex_attributes::<T>();
 
-        RenderComponent::new(
-            program,
-            textures,
-            vao,
-            vb,
-            ib,
-            flags.depth_write,
-            flags.mode,
-            size,
-        )
+        RenderComponent::new(material, vao, vb, ib, flags.depth_write, flags.mode, size)
+    }
+
+    // Frees the GL objects owned by the mesh and its material if no other mesh uses it, textures
+    // live in the cache and are left alone
+    pub fn delete_mesh(&mut self, component: &RenderComponent) {
+        self.ctx.vertex_array.delete(&component.vao);
+        self.ctx.buffer.delete_buffer(&component.vbo);
+        if let Some(ibo) = &component.ibo {
+            self.ctx.buffer.delete_buffer(ibo);
+        }
+        if let Some(program) = self.release_material(component.material) {
+            self.ctx.program.delete(&program);
+        }
+    }
+
+    // Same as `delete_mesh` at the end of the frame, so systems and hooks can drop meshes while
+    // the frame is still drawing
+    pub fn destroy_mesh(&mut self, component: &RenderComponent) {
+        self.pending_deletions
+            .push(GLObject::VertexArray(GLVertexArray(component.vao.0)));
+        self.pending_deletions
+            .push(GLObject::Buffer(component.vbo.clone()));
+        if let Some(ibo) = &component.ibo {
+            self.pending_deletions.push(GLObject::Buffer(ibo.clone()));
+        }
+        if let Some(program) = self.release_material(component.material) {
+            self.destroy_shader(&program);
+        }
+    }
+
+    pub fn destroy_shader(&mut self, shader: &GLShader) {
+        self.pending_deletions
+            .push(GLObject::Program(GLShader(shader.0)));
+    }
+
+    // Deletes what was destroyed during the frame, call it once everything was drawn
+    pub fn end_frame(&mut self) {
+        for object in self.pending_deletions.drain(..) {
+            self.ctx.delete_object(object);
+        }
+    }
+
+    // Frees every texture left in the cache and the pending deletions. What is still alive
+    // afterwards was never deleted by its owner and leaked
+    pub fn shutdown(&mut self) -> LiveObjects {
+        let indices: Vec<Index<Texture>> =
+            self.texture_cache.iter().map(|(index, _)| index).collect();
+        for index in indices {
+            self.delete_texture(index);
+        }
+        self.end_frame();
+
+        self.ctx.live_objects()
     }
 
     pub fn render_component(&mut self, component: &mut RenderComponent) {
-        self.ctx.program.bind(&component.material);
+        let material = match self.materials.get_mut(component.material) {
+            Some(material) => material,
+            None => return,
+        };
+        let shader = self.shaders.get_mut(material.shader).unwrap();
+
+        self.ctx.program.bind(&shader.program);
+        material.upload(shader);
         self.ctx.vertex_array.bind(&component.vao);
         self.ctx.depth_buffer.set_mask(component.depth_write as u8);
 
         let mut index = 0;
-        for text_id in component.textures.iter() {
+        for text_id in material.textures.iter() {
             let text = if let Some(text) = self.texture_cache.get_mut(*text_id) {
                 text
             } else {
@@ -198,6 +638,7 @@ impl RendererDevice {
                             &DEFAULT_TEXTURE_DATA,
                         );
                     }
+                    TextureStorage::Attachment { .. } => {}
                 }
             }
             text.needs_update = false;
@@ -1,4 +1,5 @@
 use super::super::gl;
+use std::collections::HashSet;
 
 #[derive(Debug)]
 pub struct GLBufferBinding {
@@ -6,30 +7,39 @@ pub struct GLBufferBinding {
     usage: gl::GLenum,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct GLBuffer {
     target: gl::GLenum,
     usage: gl::GLenum,
     handle: gl::GLuint,
 }
 
+impl GLBuffer {
+    pub fn handle(&self) -> gl::GLuint {
+        self.handle
+    }
+}
+
 pub struct BufferState {
     current_bound_buffer: Option<GLBufferBinding>,
+    live: HashSet<gl::GLuint>,
 }
 
 impl BufferState {
     pub fn build_initialized() -> Self {
         Self {
             current_bound_buffer: None,
+            live: HashSet::new(),
         }
     }
 
-    pub fn create_buffer(&self, target: gl::GLenum, usage: gl::GLenum) -> GLBuffer {
+    pub fn create_buffer(&mut self, target: gl::GLenum, usage: gl::GLenum) -> GLBuffer {
         let state = GLBuffer {
             target,
             usage,
             handle: gl::gen_buffers(1),
         };
+        self.live.insert(state.handle);
 
         state
     }
@@ -51,6 +61,19 @@ impl BufferState {
         self
     }
 
+    // Deleting twice is a no-op, GL could have handed the name to a new buffer since
+    pub fn delete_buffer(&mut self, buffer: &GLBuffer) {
+        if self.live.remove(&buffer.handle) {
+            gl::delete_buffers(buffer.handle);
+        }
+    }
+
+    pub fn live(&self) -> Vec<gl::GLuint> {
+        let mut live: Vec<gl::GLuint> = self.live.iter().cloned().collect();
+        live.sort_unstable();
+        live
+    }
+
     pub fn set_data<T>(&mut self, data: &[T]) -> &mut Self {
         if let Some(buffer) = &self.current_bound_buffer {
             gl::buffer_data(buffer.target, data, buffer.usage);
@@ -0,0 +1,105 @@
+use super::super::gl;
+use super::GLTexture;
+use std::collections::HashSet;
+
+#[derive(Debug)]
+pub struct GLFramebuffer(pub gl::GLuint);
+
+pub struct FramebufferState {
+    // 0 is the default framebuffer, the window or the headless buffer
+    current_bound_framebuffer: gl::GLuint,
+    live: HashSet<gl::GLuint>,
+}
+
+impl FramebufferState {
+    pub fn build_initialized() -> Self {
+        let mut state = Self {
+            current_bound_framebuffer: 0,
+            live: HashSet::new(),
+        };
+
+        state.bind_default();
+
+        state
+    }
+
+    pub fn create(&mut self) -> GLFramebuffer {
+        let framebuffer = gl::gen_framebuffers(1);
+        self.live.insert(framebuffer);
+        GLFramebuffer(framebuffer)
+    }
+
+    pub fn bind(&mut self, framebuffer: &GLFramebuffer) -> &mut Self {
+        self.current_bound_framebuffer = framebuffer.0;
+        gl::bind_framebuffer(gl::FRAMEBUFFER, framebuffer.0);
+
+        self
+    }
+
+    pub fn bind_default(&mut self) -> &mut Self {
+        self.current_bound_framebuffer = 0;
+        gl::bind_framebuffer(gl::FRAMEBUFFER, 0);
+
+        self
+    }
+
+    pub fn bound(&self) -> GLFramebuffer {
+        GLFramebuffer(self.current_bound_framebuffer)
+    }
+
+    pub fn is_default_bound(&self) -> bool {
+        self.current_bound_framebuffer == 0
+    }
+
+    // `attachment` is `gl::COLOR_ATTACHMENT0`, `gl::DEPTH_ATTACHMENT` and the like
+    pub fn attach_texture(&mut self, attachment: gl::GLenum, texture: &GLTexture) -> &mut Self {
+        if !self.is_default_bound() {
+            gl::framebuffer_texture_2d(
+                gl::FRAMEBUFFER,
+                attachment,
+                texture.target(),
+                texture.handle(),
+                0,
+            );
+        }
+
+        self
+    }
+
+    // Framebuffers with only a depth attachment have nothing to draw colors into
+    pub fn set_color_enabled(&mut self, enabled: bool) -> &mut Self {
+        if !self.is_default_bound() {
+            let buffer = if enabled {
+                gl::COLOR_ATTACHMENT0
+            } else {
+                gl::NONE
+            };
+            gl::draw_buffer(buffer);
+            gl::read_buffer(buffer);
+        }
+
+        self
+    }
+
+    pub fn status(&self) -> gl::GLenum {
+        gl::check_framebuffer_status(gl::FRAMEBUFFER)
+    }
+
+    pub fn delete(&mut self, framebuffer: &GLFramebuffer) {
+        if !self.live.remove(&framebuffer.0) {
+            return;
+        }
+
+        if self.current_bound_framebuffer == framebuffer.0 {
+            self.bind_default();
+        }
+
+        gl::delete_framebuffers(framebuffer.0);
+    }
+
+    pub fn live(&self) -> Vec<gl::GLuint> {
+        let mut live: Vec<gl::GLuint> = self.live.iter().cloned().collect();
+        live.sort_unstable();
+        live
+    }
+}
@@ -1,11 +1,67 @@
 use super::super::gl;
 use super::{
-    BlendState, BufferState, ColorBufferState, CullFaceState, DepthBufferState, FrontFaceState,
-    ProgramState, TextureState, VertexArrayState, ViewportState,
+    BlendState, BufferState, ColorBufferState, CullFaceState, DepthBufferState, FramebufferState,
+    FrontFaceState, GLBuffer, GLFramebuffer, GLShader, GLTexture, GLVertexArray, ProgramState,
+    TextureState, VertexArrayState, ViewportState,
 };
-use glutin::{ContextWrapper, PossiblyCurrent};
+use glutin::{Context, ContextWrapper, PossiblyCurrent};
+use std::fmt;
 use winit::window::Window;
 
+// Any object created through the context, for deleting them later without knowing the kind
+#[derive(Debug)]
+pub enum GLObject {
+    Buffer(GLBuffer),
+    VertexArray(GLVertexArray),
+    Program(GLShader),
+    Texture(GLTexture),
+    Framebuffer(GLFramebuffer),
+}
+
+// Names of the objects created through the context and not deleted yet
+#[derive(Debug, Default)]
+pub struct LiveObjects {
+    pub buffers: Vec<gl::GLuint>,
+    pub vertex_arrays: Vec<gl::GLuint>,
+    pub programs: Vec<gl::GLuint>,
+    pub textures: Vec<gl::GLuint>,
+    pub framebuffers: Vec<gl::GLuint>,
+}
+
+impl LiveObjects {
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+            + self.vertex_arrays.len()
+            + self.programs.len()
+            + self.textures.len()
+            + self.framebuffers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Display for LiveObjects {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} live GL objects", self.len())?;
+
+        let kinds = [
+            ("buffers", &self.buffers),
+            ("vertex arrays", &self.vertex_arrays),
+            ("programs", &self.programs),
+            ("textures", &self.textures),
+            ("framebuffers", &self.framebuffers),
+        ];
+        for (kind, names) in kinds.iter() {
+            if !names.is_empty() {
+                write!(f, "\n  {} {}: {:?}", names.len(), kind, names)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 pub struct OpenGLContext {
     pub color_buffer: ColorBufferState,
     pub depth_buffer: DepthBufferState,
@@ -17,11 +73,17 @@ pub struct OpenGLContext {
     pub viewport: ViewportState,
     pub culling: CullFaceState,
     pub front_face: FrontFaceState,
+    pub framebuffer: FramebufferState,
 }
 
 impl OpenGLContext {
     pub fn build_initialize(window_context: &ContextWrapper<PossiblyCurrent, Window>) -> Self {
-        gl::init_from_window(window_context);
+        Self::build_from_context(window_context.context())
+    }
+
+    // Any current context, like the offscreen one of `RendererDevice::headless`
+    pub fn build_from_context(context: &Context<PossiblyCurrent>) -> Self {
+        gl::init_from_context(context);
 
         let color_buffer = ColorBufferState::build_initialized();
         let depth_buffer = DepthBufferState::build_initialized();
@@ -33,6 +95,7 @@ impl OpenGLContext {
         let viewport = ViewportState::build_initialized();
         let culling = CullFaceState::build_initialized();
         let front_face = FrontFaceState::build_initialized();
+        let framebuffer = FramebufferState::build_initialized();
 
         Self {
             color_buffer,
@@ -45,6 +108,7 @@ impl OpenGLContext {
             viewport,
             culling,
             front_face,
+            framebuffer,
         }
     }
 
@@ -64,4 +128,24 @@ impl OpenGLContext {
             gl::ONE_MINUS_SRC_ALPHA,
         );
     }
+
+    pub fn delete_object(&mut self, object: GLObject) {
+        match object {
+            GLObject::Buffer(buffer) => self.buffer.delete_buffer(&buffer),
+            GLObject::VertexArray(array) => self.vertex_array.delete(&array),
+            GLObject::Program(program) => self.program.delete(&program),
+            GLObject::Texture(texture) => self.texture.delete_texture(texture),
+            GLObject::Framebuffer(framebuffer) => self.framebuffer.delete(&framebuffer),
+        }
+    }
+
+    pub fn live_objects(&self) -> LiveObjects {
+        LiveObjects {
+            buffers: self.buffer.live(),
+            vertex_arrays: self.vertex_array.live(),
+            programs: self.program.live(),
+            textures: self.texture.live(),
+            framebuffers: self.framebuffer.live(),
+        }
+    }
 }
@@ -5,6 +5,7 @@ mod buffer_state;
 mod color_buffer_state;
 mod cull_state;
 mod depth_buffer_state;
+mod framebuffer_state;
 mod front_face_state;
 mod gl_context;
 mod program_state;
@@ -17,6 +18,7 @@ pub use buffer_state::*;
 pub use color_buffer_state::*;
 pub use cull_state::*;
 pub use depth_buffer_state::*;
+pub use framebuffer_state::*;
 pub use front_face_state::*;
 pub use gl_context::*;
 pub use program_state::*;
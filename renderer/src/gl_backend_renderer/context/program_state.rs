@@ -1,5 +1,6 @@
 use super::super::gl;
 use super::super::{configure_vertex_attributes_from_source, VertexFormat};
+use std::collections::HashSet;
 use std::fs;
 use std::path::Path;
 use math::{Mat4, Vec2, Vec3, Vec4};
@@ -55,6 +56,17 @@ fn create_shader(code: &String, xtype: gl::GLuint) -> Result<gl::GLuint, String>
     }
 }
 
+// Vertex and fragment parts of a `#pragma SHADER` file
+pub fn read_shader_file(path: &Path) -> (String, String) {
+    let contents = fs::read_to_string(path).unwrap();
+    let shaders: Vec<&str> = contents.split_terminator("#pragma SHADER").collect();
+
+    // First item is an empty string
+    assert_eq!(shaders.len(), 3);
+
+    (String::from(shaders[1]), String::from(shaders[2]))
+}
+
 pub struct GLShaderVariable<T> {
     pub value: gl::GLint,
     data: std::marker::PhantomData<T>,
@@ -191,13 +203,8 @@ impl GLShader {
     }
 
     fn from_file<V: VertexFormat>(path: &Path) -> Self {
-        let contents = fs::read_to_string(path).unwrap();
-        let shaders: Vec<&str> = contents.split_terminator("#pragma SHADER").collect();
-
-        // First item is an empty string
-        assert_eq!(shaders.len(), 3);
-
-        Self::from::<V>(String::from(shaders[1]), String::from(shaders[2]))
+        let (vertex_shader, fragment_shader) = read_shader_file(path);
+        Self::from::<V>(vertex_shader, fragment_shader)
     }
 
     pub fn get_variable<T>(&self, name: &str) -> GLShaderVariable<T> {
@@ -220,25 +227,31 @@ impl GLShader {
 
 pub struct ProgramState {
     current_bound_shader: Option<u32>,
+    live: HashSet<gl::GLuint>,
 }
 
 impl ProgramState {
     pub fn build_initialized() -> Self {
         Self {
             current_bound_shader: None,
+            live: HashSet::new(),
         }
     }
 
     pub fn create_from_strings<V: VertexFormat>(
-        &self,
+        &mut self,
         vertex_shader: String,
         fragment_shader: String,
     ) -> GLShader {
-        GLShader::from::<V>(vertex_shader, fragment_shader)
+        let shader = GLShader::from::<V>(vertex_shader, fragment_shader);
+        self.live.insert(shader.0);
+        shader
     }
 
-    pub fn create_from_file<V: VertexFormat>(&self, path: &Path) -> GLShader {
-        GLShader::from_file::<V>(path)
+    pub fn create_from_file<V: VertexFormat>(&mut self, path: &Path) -> GLShader {
+        let shader = GLShader::from_file::<V>(path);
+        self.live.insert(shader.0);
+        shader
     }
 
     pub fn bind(&mut self, shader: &GLShader) {
@@ -246,4 +259,22 @@ impl ProgramState {
 
         gl::use_program(shader.0);
     }
+
+    pub fn delete(&mut self, shader: &GLShader) {
+        if !self.live.remove(&shader.0) {
+            return;
+        }
+
+        if self.current_bound_shader == Some(shader.0) {
+            self.current_bound_shader = None;
+        }
+
+        gl::delete_program(shader.0);
+    }
+
+    pub fn live(&self) -> Vec<gl::GLuint> {
+        let mut live: Vec<gl::GLuint> = self.live.iter().cloned().collect();
+        live.sort_unstable();
+        live
+    }
 }
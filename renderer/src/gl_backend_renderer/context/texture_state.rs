@@ -1,4 +1,5 @@
 use super::super::gl;
+use std::collections::HashSet;
 use std::os::raw::c_void;
 
 #[derive(Copy, Clone, Debug)]
@@ -20,6 +21,26 @@ impl Default for TextureFormat {
     }
 }
 
+impl TextureFormat {
+    pub fn depth() -> Self {
+        Self {
+            level: 0,
+            internal_format: gl::DEPTH_COMPONENT24,
+            format: gl::DEPTH_COMPONENT,
+            type_: gl::FLOAT,
+        }
+    }
+
+    pub fn hdr() -> Self {
+        Self {
+            level: 0,
+            internal_format: gl::RGBA16F,
+            format: gl::RGBA,
+            type_: gl::FLOAT,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct GLTexture {
     texture: gl::GLuint,
@@ -30,6 +51,16 @@ pub struct GLTexture {
     type_: gl::GLenum,
 }
 
+impl GLTexture {
+    pub fn handle(&self) -> gl::GLuint {
+        self.texture
+    }
+
+    pub fn target(&self) -> gl::GLenum {
+        self.target
+    }
+}
+
 struct TextureUnit {
     handle: gl::GLuint,
     target: gl::GLenum,
@@ -40,6 +71,7 @@ pub struct TextureState {
     bound_texture: Option<GLTexture>,
     bound_texture_units: Vec<TextureUnit>,
     max_texture_units: i32,
+    live: HashSet<gl::GLuint>,
 }
 
 impl TextureState {
@@ -61,6 +93,7 @@ impl TextureState {
             bound_texture,
             bound_texture_units,
             max_texture_units,
+            live: HashSet::new(),
         };
 
         state.set_active_texture_unit(gl::TEXTURE0);
@@ -148,8 +181,13 @@ impl TextureState {
         self
     }
 
-    pub fn create_texture(&self, target: gl::GLenum, format: Option<TextureFormat>) -> GLTexture {
+    pub fn create_texture(
+        &mut self,
+        target: gl::GLenum,
+        format: Option<TextureFormat>,
+    ) -> GLTexture {
         let texture = gl::gen_textures(1);
+        self.live.insert(texture);
 
         let format = format.unwrap_or_default();
 
@@ -163,6 +201,31 @@ impl TextureState {
         }
     }
 
+    pub fn delete_texture(&mut self, texture: GLTexture) {
+        if !self.live.remove(&texture.texture) {
+            return;
+        }
+
+        if let Some(bound) = &self.bound_texture {
+            if bound.texture == texture.texture {
+                self.bound_texture = None;
+            }
+        }
+        for unit in self.bound_texture_units.iter_mut() {
+            if unit.handle == texture.texture {
+                unit.handle = 0;
+            }
+        }
+
+        gl::delete_textures(texture.texture);
+    }
+
+    pub fn live(&self) -> Vec<gl::GLuint> {
+        let mut live: Vec<gl::GLuint> = self.live.iter().cloned().collect();
+        live.sort_unstable();
+        live
+    }
+
     pub fn bind_texture(&mut self, texture: GLTexture) -> &mut Self {
         unsafe {
             let unit = self
@@ -1,21 +1,26 @@
 use super::super::gl;
+use std::collections::HashSet;
 
 #[derive(Debug)]
 pub struct GLVertexArray(pub gl::GLuint);
 
 pub struct VertexArrayState {
     current_bound_buffer: Option<GLVertexArray>,
+    live: HashSet<gl::GLuint>,
 }
 
 impl VertexArrayState {
     pub fn build_initialized() -> Self {
         Self {
             current_bound_buffer: None,
+            live: HashSet::new(),
         }
     }
 
-    pub fn create(&self) -> GLVertexArray {
-        GLVertexArray(gl::gen_vertex_arrays(1))
+    pub fn create(&mut self) -> GLVertexArray {
+        let array = gl::gen_vertex_arrays(1);
+        self.live.insert(array);
+        GLVertexArray(array)
     }
 
     pub fn bind(&mut self, buffer: &GLVertexArray) {
@@ -23,4 +28,24 @@ impl VertexArrayState {
 
         gl::bind_vertex_array(buffer.0);
     }
+
+    pub fn delete(&mut self, buffer: &GLVertexArray) {
+        if !self.live.remove(&buffer.0) {
+            return;
+        }
+
+        if let Some(GLVertexArray(current)) = self.current_bound_buffer {
+            if current == buffer.0 {
+                self.current_bound_buffer = None;
+            }
+        }
+
+        gl::delete_vertex_arrays(buffer.0);
+    }
+
+    pub fn live(&self) -> Vec<gl::GLuint> {
+        let mut live: Vec<gl::GLuint> = self.live.iter().cloned().collect();
+        live.sort_unstable();
+        live
+    }
 }
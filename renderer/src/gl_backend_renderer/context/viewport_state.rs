@@ -37,6 +37,10 @@ impl ViewportState {
         self.upload();
     }
 
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
     fn upload(&self) {
         gl::viewport(self.x, self.y, self.width, self.height);
     }
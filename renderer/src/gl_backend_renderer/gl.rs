@@ -1,4 +1,4 @@
-use glutin::{ContextWrapper, PossiblyCurrent};
+use glutin::{Context, ContextWrapper, PossiblyCurrent};
 use std::ffi::CStr;
 use std::os::raw::c_void;
 use math::{Mat4, Vec2, Vec3, Vec4};
@@ -159,6 +159,75 @@ pub fn gen_buffers(count: i32) -> GLuint {
     }
 }
 
+#[inline]
+pub fn delete_vertex_arrays(array: GLuint) {
+    unsafe {
+        DeleteVertexArrays(1, &array);
+    }
+}
+
+#[inline]
+pub fn delete_buffers(buffer: GLuint) {
+    unsafe {
+        DeleteBuffers(1, &buffer);
+    }
+}
+
+#[inline]
+pub fn gen_framebuffers(count: i32) -> GLuint {
+    unsafe {
+        let mut fbo = 0;
+        GenFramebuffers(count, &mut fbo);
+        fbo
+    }
+}
+
+#[inline]
+pub fn delete_framebuffers(framebuffer: GLuint) {
+    unsafe {
+        DeleteFramebuffers(1, &framebuffer);
+    }
+}
+
+#[inline]
+pub fn bind_framebuffer(target: GLenum, framebuffer: GLuint) {
+    unsafe {
+        BindFramebuffer(target, framebuffer);
+    }
+}
+
+#[inline]
+pub fn framebuffer_texture_2d(
+    target: GLenum,
+    attachment: GLenum,
+    tex_target: GLenum,
+    texture: GLuint,
+    level: GLint,
+) {
+    unsafe {
+        FramebufferTexture2D(target, attachment, tex_target, texture, level);
+    }
+}
+
+#[inline]
+pub fn check_framebuffer_status(target: GLenum) -> GLenum {
+    unsafe { CheckFramebufferStatus(target) }
+}
+
+#[inline]
+pub fn draw_buffer(buffer: GLenum) {
+    unsafe {
+        DrawBuffer(buffer);
+    }
+}
+
+#[inline]
+pub fn read_buffer(buffer: GLenum) {
+    unsafe {
+        ReadBuffer(buffer);
+    }
+}
+
 #[inline]
 pub fn bind_buffer(target: GLenum, buffer: GLuint) {
     unsafe {
@@ -193,6 +262,13 @@ pub fn delete_shader(shader: GLuint) {
         DeleteShader(shader);
     }
 }
+
+#[inline]
+pub fn delete_program(program: GLuint) {
+    unsafe {
+        DeleteProgram(program);
+    }
+}
 #[inline]
 pub fn get_string(name: GLenum) -> String {
     unsafe {
@@ -205,7 +281,11 @@ pub fn get_string(name: GLenum) -> String {
 
 #[inline]
 pub fn init_from_window(window_context: &ContextWrapper<PossiblyCurrent, Window>) {
-    load_with(|ptr| window_context.context().get_proc_address(ptr) as *const _);
+    init_from_context(window_context.context());
+}
+
+pub fn init_from_context(context: &Context<PossiblyCurrent>) {
+    load_with(|ptr| context.get_proc_address(ptr) as *const _);
 }
 
 #[inline]
@@ -366,6 +446,13 @@ pub fn gen_textures(count: GLint) -> GLuint {
     }
 }
 
+#[inline]
+pub fn delete_textures(texture: GLuint) {
+    unsafe {
+        DeleteTextures(1, &texture);
+    }
+}
+
 #[inline]
 pub fn bind_texture(target: GLenum, texture: GLuint) {
     unsafe {
@@ -440,6 +527,36 @@ pub fn tex_image_2d_raw(
     }
 }
 
+#[inline]
+pub fn pixel_store_i(pname: GLenum, param: GLint) {
+    unsafe {
+        PixelStorei(pname, param);
+    }
+}
+
+#[inline]
+pub fn read_pixels(
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    format: GLenum,
+    type_: GLenum,
+    pixels: &mut [u8],
+) {
+    unsafe {
+        ReadPixels(
+            x,
+            y,
+            width as i32,
+            height as i32,
+            format,
+            type_,
+            pixels.as_mut_ptr() as *mut c_void,
+        );
+    }
+}
+
 #[inline]
 pub fn active_texture(texture: GLenum) {
     unsafe {
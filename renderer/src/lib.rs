@@ -2,6 +2,9 @@ mod camera;
 pub mod canvas2d;
 pub mod component;
 pub mod device;
+pub mod material;
+pub mod post_process;
+pub mod render_target;
 #[cfg(any(target_os = "linux",))]
 #[path = "gl_backend_renderer/mod.rs"]
 pub mod renderer;
@@ -11,3 +14,6 @@ pub use camera::*;
 pub use canvas2d::*;
 pub use component::*;
 pub use device::*;
+pub use material::*;
+pub use post_process::*;
+pub use render_target::*;
@@ -0,0 +1,182 @@
+use super::*;
+use generational_arena::Index;
+use math::{Mat4, Vec2, Vec3, Vec4};
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+// Value of a material parameter, uploaded to the uniform of the same name before each draw
+#[derive(Debug)]
+pub enum MaterialValue {
+    Int(i32),
+    UInt(u32),
+    Float(f32),
+    Vec2(Vec2),
+    Vec3(Vec3),
+    Vec4(Vec4),
+    Mat4(Mat4),
+}
+
+impl MaterialValue {
+    fn upload(&self, location: gl::GLint) {
+        match self {
+            MaterialValue::Int(value) => gl::uniform_1i(location, *value),
+            MaterialValue::UInt(value) => gl::uniform_1ui(location, *value),
+            MaterialValue::Float(value) => gl::uniform_1f(location, *value),
+            MaterialValue::Vec2(value) => gl::uniform_2f(location, value),
+            MaterialValue::Vec3(value) => gl::uniform_3f(location, value),
+            MaterialValue::Vec4(value) => gl::uniform_4f(location, value),
+            MaterialValue::Mat4(value) => gl::uniform_matrix_4f(location, value),
+        }
+    }
+}
+
+impl From<i32> for MaterialValue {
+    fn from(value: i32) -> Self {
+        MaterialValue::Int(value)
+    }
+}
+
+impl From<u32> for MaterialValue {
+    fn from(value: u32) -> Self {
+        MaterialValue::UInt(value)
+    }
+}
+
+impl From<f32> for MaterialValue {
+    fn from(value: f32) -> Self {
+        MaterialValue::Float(value)
+    }
+}
+
+impl From<Vec2> for MaterialValue {
+    fn from(value: Vec2) -> Self {
+        MaterialValue::Vec2(value)
+    }
+}
+
+impl From<Vec3> for MaterialValue {
+    fn from(value: Vec3) -> Self {
+        MaterialValue::Vec3(value)
+    }
+}
+
+impl From<Vec4> for MaterialValue {
+    fn from(value: Vec4) -> Self {
+        MaterialValue::Vec4(value)
+    }
+}
+
+impl From<Mat4> for MaterialValue {
+    fn from(value: Mat4) -> Self {
+        MaterialValue::Mat4(value)
+    }
+}
+
+// Program compiled once per file and vertex format, shared by the materials created from them
+pub struct Shader {
+    pub program: GLShader,
+    pub path: PathBuf,
+    pub vertex_format: TypeId,
+    // Every new mesh points its attributes at its own buffer from the `in` declarations
+    vertex_source: String,
+    uniforms: HashMap<String, gl::GLint>,
+    // Materials using the program, it is deleted with the last one
+    pub(crate) users: usize,
+}
+
+impl Shader {
+    pub(crate) fn new(
+        program: GLShader,
+        path: PathBuf,
+        vertex_format: TypeId,
+        vertex_source: String,
+    ) -> Self {
+        Self {
+            program,
+            path,
+            vertex_format,
+            vertex_source,
+            uniforms: HashMap::new(),
+            users: 0,
+        }
+    }
+
+    // Queried from GL once per name, -1 when the program has no such uniform
+    pub fn uniform_location(&mut self, name: &str) -> gl::GLint {
+        let program = self.program.0;
+        *self
+            .uniforms
+            .entry(name.to_string())
+            .or_insert_with(|| gl::get_uniform_location(program, name))
+    }
+
+    // With the vertex array and the vertex buffer of the mesh bound
+    pub fn configure_vertex_attributes<V: VertexFormat>(&self) {
+        configure_vertex_attributes_from_source::<V>(self.program.0, self.vertex_source.clone());
+    }
+}
+
+struct MaterialParam {
+    name: String,
+    // Resolved on the first draw
+    location: Option<gl::GLint>,
+    value: MaterialValue,
+}
+
+// Instance of a shader with its own parameter values and textures, so meshes sharing a program
+// can still be drawn at different positions or with different colors
+pub struct Material {
+    pub shader: Index<Shader>,
+    // In the order of the `sampler2D` uniforms of the fragment shader
+    pub textures: Vec<Index<Texture>>,
+    params: Vec<MaterialParam>,
+    // Meshes drawn with the material, it is freed with the last one
+    pub(crate) users: usize,
+}
+
+impl Material {
+    pub(crate) fn new(shader: Index<Shader>, textures: Vec<Index<Texture>>) -> Self {
+        Self {
+            shader,
+            textures,
+            params: Vec::new(),
+            users: 0,
+        }
+    }
+
+    pub fn set<V: Into<MaterialValue>>(&mut self, name: &str, value: V) -> &mut Self {
+        let value = value.into();
+        match self.params.iter_mut().find(|param| param.name == name) {
+            Some(param) => param.value = value,
+            None => self.params.push(MaterialParam {
+                name: name.to_string(),
+                location: None,
+                value,
+            }),
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&MaterialValue> {
+        self.params
+            .iter()
+            .find(|param| param.name == name)
+            .map(|param| &param.value)
+    }
+
+    // With the program of `shader` bound
+    pub(crate) fn upload(&mut self, shader: &mut Shader) {
+        for param in self.params.iter_mut() {
+            let location = match param.location {
+                Some(location) => location,
+                None => {
+                    let location = shader.uniform_location(&param.name);
+                    param.location = Some(location);
+                    location
+                }
+            };
+            param.value.upload(location);
+        }
+    }
+}
@@ -0,0 +1,363 @@
+use super::*;
+use std::any::Any;
+use std::ffi::c_void;
+use std::mem::size_of;
+use std::path::Path;
+
+// Binding point of the `PassParams` uniform block while a pass runs, the game keeps 0 for its
+// viewport matrices
+const PARAMS_BINDING: gl::GLuint = 1;
+
+const COPY_VERTEX_SHADER: &str = "
+#version 420
+precision highp float;
+
+in vec2 position;
+
+out vec2 v_uv;
+
+void main() {
+    gl_Position = vec4(position, 0.0, 1.0);
+    v_uv = position * 0.5 + 0.5;
+}
+";
+
+const COPY_FRAGMENT_SHADER: &str = "
+#version 420
+precision highp float;
+
+in vec2 v_uv;
+
+uniform sampler2D source;
+
+out vec4 fragColor;
+
+void main() {
+    fragColor = texture(source, v_uv);
+}
+";
+
+#[repr(C)]
+struct PassVertex {
+    position: FVec2,
+}
+
+impl VertexFormat for PassVertex {
+    fn size() -> usize {
+        size_of::<Self>()
+    }
+
+    fn on_vertex_layout() -> Vec<(i32, u32, u8, *const c_void)> {
+        unsafe { vec![get_attribute_format::<FVec2>(offset_of!(Self, position))] }
+    }
+}
+
+enum PassSource<'a> {
+    File(&'a Path),
+    Code(&'static str, &'static str),
+}
+
+type UploadParams = fn(&dyn Any, &mut BufferState);
+
+struct Pass {
+    name: &'static str,
+    enabled: bool,
+    program: GLShader,
+    vao: GLVertexArray,
+    params: Box<dyn Any>,
+    // Passes with `()` as parameters have no uniform block
+    buffer: Option<GLBuffer>,
+    upload: UploadParams,
+}
+
+impl Pass {
+    fn new<P: 'static>(
+        device: &mut RendererDevice,
+        triangle: &GLBuffer,
+        name: &'static str,
+        source: PassSource,
+        params: P,
+    ) -> Self {
+        let vao = device.ctx.vertex_array.create();
+        device.ctx.vertex_array.bind(&vao);
+        device.ctx.buffer.bind_buffer(triangle);
+
+        let program = match source {
+            PassSource::File(path) => device.ctx.program.create_from_file::<PassVertex>(path),
+            PassSource::Code(vertex, fragment) => device
+                .ctx
+                .program
+                .create_from_strings::<PassVertex>(vertex.to_string(), fragment.to_string()),
+        };
+
+        let buffer = if size_of::<P>() > 0 {
+            let block = program.get_uniform_block_index::<P>("PassParams");
+            program.uniform_block_binding(&block, PARAMS_BINDING);
+            Some(
+                device
+                    .ctx
+                    .buffer
+                    .create_buffer(gl::UNIFORM_BUFFER, gl::DYNAMIC_DRAW),
+            )
+        } else {
+            None
+        };
+
+        Self {
+            name,
+            enabled: true,
+            program,
+            vao,
+            params: Box::new(params),
+            buffer,
+            upload: |params, buffer| {
+                buffer.set_data(std::slice::from_ref(params.downcast_ref::<P>().unwrap()));
+            },
+        }
+    }
+
+    fn draw(&self, device: &mut RendererDevice, source: GLTexture, scene: GLTexture) {
+        device.ctx.program.bind(&self.program);
+        device.ctx.vertex_array.bind(&self.vao);
+
+        if let Some(buffer) = &self.buffer {
+            device.ctx.buffer.bind_buffer(buffer);
+            (self.upload)(self.params.as_ref(), &mut device.ctx.buffer);
+            device.ctx.buffer.bind_buffer_base(PARAMS_BINDING, buffer);
+        }
+
+        device
+            .ctx
+            .texture
+            .set_active_texture_unit(gl::TEXTURE0)
+            .bind_texture(source)
+            .set_active_texture_unit(gl::TEXTURE1)
+            .bind_texture(scene);
+
+        gl::draw_arrays(gl::TRIANGLES, 0, 3);
+    }
+
+    fn delete(&self, device: &mut RendererDevice) {
+        device.ctx.vertex_array.delete(&self.vao);
+        device.ctx.program.delete(&self.program);
+        if let Some(buffer) = &self.buffer {
+            device.ctx.buffer.delete_buffer(buffer);
+        }
+    }
+}
+
+// Renders the scene into an HDR target and runs full-screen passes over it, in the order they
+// were added, the last one drawing to the screen.
+//
+// A pass is a `#pragma SHADER` file with `in vec2 position` as its only vertex input, covering
+// the screen, and `v_uv = position * 0.5 + 0.5`. The first `sampler2D` of its fragment shader
+// is the output of the previous pass, the second one the untouched scene. Its parameters are a
+// `#[repr(C)]` struct uploaded as `layout (std140) uniform PassParams`, so the struct has to
+// follow the std140 layout of the block, `[f32; 4]` for a `vec4` and padding after a `vec3`
+pub struct PostProcessStack {
+    scene: RenderTarget,
+    swap: [RenderTarget; 2],
+    triangle: GLBuffer,
+    copy: Pass,
+    passes: Vec<Pass>,
+}
+
+fn create_targets(
+    device: &mut RendererDevice,
+    width: u32,
+    height: u32,
+) -> Result<(RenderTarget, [RenderTarget; 2]), RenderTargetError> {
+    let scene = device.create_hdr_render_target(width, height, TextureFiltering::Smooth)?;
+    let first = device.create_hdr_render_target(width, height, TextureFiltering::Smooth)?;
+    let second = device.create_hdr_render_target(width, height, TextureFiltering::Smooth)?;
+    Ok((scene, [first, second]))
+}
+
+fn delete_target(device: &mut RendererDevice, target: &RenderTarget) {
+    device.delete_render_target(target);
+    for texture in target.color.iter().chain(target.depth.iter()) {
+        device.delete_texture(*texture);
+    }
+}
+
+fn color_texture(device: &RendererDevice, target: &RenderTarget) -> GLTexture {
+    let index = target.color.unwrap();
+    device.texture_cache.get(index).unwrap().handle.unwrap()
+}
+
+impl PostProcessStack {
+    pub fn new(
+        device: &mut RendererDevice,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, RenderTargetError> {
+        let (scene, swap) = create_targets(device, width, height)?;
+
+        let triangle = device
+            .ctx
+            .buffer
+            .create_buffer(gl::ARRAY_BUFFER, gl::STATIC_DRAW);
+        device.ctx.buffer.bind_buffer(&triangle).set_data(&[
+            PassVertex {
+                position: FVec2 { x: -1.0, y: -1.0 },
+            },
+            PassVertex {
+                position: FVec2 { x: 3.0, y: -1.0 },
+            },
+            PassVertex {
+                position: FVec2 { x: -1.0, y: 3.0 },
+            },
+        ]);
+
+        let copy = Pass::new(
+            device,
+            &triangle,
+            "copy",
+            PassSource::Code(COPY_VERTEX_SHADER, COPY_FRAGMENT_SHADER),
+            (),
+        );
+
+        Ok(Self {
+            scene,
+            swap,
+            triangle,
+            copy,
+            passes: Vec::new(),
+        })
+    }
+
+    // Adding a pass with the name of an existing one replaces it in place
+    pub fn add_pass<P: 'static>(
+        &mut self,
+        device: &mut RendererDevice,
+        name: &'static str,
+        path: &Path,
+        params: P,
+    ) -> &mut Self {
+        let pass = Pass::new(device, &self.triangle, name, PassSource::File(path), params);
+
+        match self.passes.iter().position(|other| other.name == name) {
+            Some(index) => {
+                self.passes[index].delete(device);
+                self.passes[index] = pass;
+            }
+            None => self.passes.push(pass),
+        }
+        self
+    }
+
+    pub fn remove_pass(&mut self, device: &mut RendererDevice, name: &str) -> bool {
+        match self.passes.iter().position(|pass| pass.name == name) {
+            Some(index) => {
+                self.passes.remove(index).delete(device);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn pass_names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|pass| pass.name).collect()
+    }
+
+    // `None` when there is no pass `name` or its parameters are not a `P`
+    pub fn params<P: 'static>(&self, name: &str) -> Option<&P> {
+        let pass = self.passes.iter().find(|pass| pass.name == name)?;
+        pass.params.downcast_ref()
+    }
+
+    pub fn params_mut<P: 'static>(&mut self, name: &str) -> Option<&mut P> {
+        let pass = self.passes.iter_mut().find(|pass| pass.name == name)?;
+        pass.params.downcast_mut()
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.passes.iter_mut().find(|pass| pass.name == name) {
+            Some(pass) => {
+                pass.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.passes
+            .iter()
+            .any(|pass| pass.name == name && pass.enabled)
+    }
+
+    pub fn scene(&self) -> &RenderTarget {
+        &self.scene
+    }
+
+    // Creates the targets again at the new size, usually next to `RendererDevice::resize`
+    pub fn resize(
+        &mut self,
+        device: &mut RendererDevice,
+        width: u32,
+        height: u32,
+    ) -> Result<(), RenderTargetError> {
+        if self.scene.width == width && self.scene.height == height {
+            return Ok(());
+        }
+
+        let (scene, swap) = create_targets(device, width, height)?;
+        delete_target(device, &self.scene);
+        for target in self.swap.iter() {
+            delete_target(device, target);
+        }
+
+        self.scene = scene;
+        self.swap = swap;
+        Ok(())
+    }
+
+    // Everything drawn until `present` goes into the HDR scene target
+    pub fn begin(&self, device: &mut RendererDevice) {
+        device.bind_render_target(&self.scene);
+    }
+
+    // Runs the enabled passes and draws the result to the screen, copying the scene over as is
+    // when none is enabled. Depth testing, blending and culling are left disabled
+    pub fn present(&self, device: &mut RendererDevice) {
+        device.unbind_render_target();
+        device.ctx.depth_buffer.set_enabled(false);
+        device.ctx.blend.set_enabled(false);
+        device.ctx.culling.set_enabled(false);
+
+        let scene = color_texture(device, &self.scene);
+        let enabled: Vec<&Pass> = self.passes.iter().filter(|pass| pass.enabled).collect();
+        if enabled.is_empty() {
+            self.copy.draw(device, scene, scene);
+            return;
+        }
+
+        let mut source = scene;
+        for (index, pass) in enabled.iter().enumerate() {
+            let target = &self.swap[index % 2];
+            let last = index + 1 == enabled.len();
+            if last {
+                device.unbind_render_target();
+            } else {
+                device.bind_render_target(target);
+            }
+
+            pass.draw(device, source, scene);
+            source = color_texture(device, target);
+        }
+    }
+
+    pub fn delete(self, device: &mut RendererDevice) {
+        for pass in self.passes.iter() {
+            pass.delete(device);
+        }
+        self.copy.delete(device);
+        device.ctx.buffer.delete_buffer(&self.triangle);
+
+        delete_target(device, &self.scene);
+        for target in self.swap.iter() {
+            delete_target(device, target);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use super::*;
+use generational_arena::Index;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum RenderTargetError {
+    NoAttachments,
+    MissingTexture,
+    // The texture was not created with `TextureStorage::Attachment`
+    NotAnAttachment,
+    WrongKind {
+        expected: AttachmentKind,
+    },
+    SizeMismatch {
+        color: (u32, u32),
+        depth: (u32, u32),
+    },
+    Incomplete(gl::GLenum),
+}
+
+impl fmt::Display for RenderTargetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderTargetError::NoAttachments => {
+                write!(f, "A render target needs a color or a depth attachment")
+            }
+            RenderTargetError::MissingTexture => {
+                write!(f, "Attachment is not in the texture cache")
+            }
+            RenderTargetError::NotAnAttachment => {
+                write!(f, "Texture was not created as an attachment")
+            }
+            RenderTargetError::WrongKind { expected } => {
+                write!(f, "Expected a {:?} attachment", expected)
+            }
+            RenderTargetError::SizeMismatch { color, depth } => write!(
+                f,
+                "Color attachment is {}x{} but depth attachment is {}x{}",
+                color.0, color.1, depth.0, depth.1
+            ),
+            RenderTargetError::Incomplete(status) => {
+                write!(f, "Framebuffer is incomplete, status {:#x}", status)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RenderTargetError {}
+
+// Framebuffer drawing into textures of `RendererDevice::texture_cache`, for minimaps, mirrors,
+// post-processing or screenshots. Meshes can sample its textures like any other
+pub struct RenderTarget {
+    pub framebuffer: GLFramebuffer,
+    pub color: Option<Index<Texture>>,
+    pub depth: Option<Index<Texture>>,
+    pub width: u32,
+    pub height: u32,
+}
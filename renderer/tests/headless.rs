@@ -0,0 +1,278 @@
+use image::RgbaImage;
+use renderer::gl_vertex_format::{get_attribute_format, FVec3, VertexFormat};
+use renderer::{
+    offset_of, renderer::gl, PostProcessStack, RenderComponent, RendererDevice, TextureFiltering,
+};
+use std::env;
+use std::mem::size_of;
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+
+const SIZE: u32 = 64;
+
+#[repr(C)]
+struct Vertex {
+    position: FVec3,
+    color: FVec3,
+}
+
+impl Vertex {
+    fn new(x: f32, y: f32, r: f32, g: f32, b: f32) -> Self {
+        Vertex {
+            position: FVec3 { x, y, z: 0.0 },
+            color: FVec3 { x: r, y: g, z: b },
+        }
+    }
+}
+
+impl VertexFormat for Vertex {
+    fn size() -> usize {
+        size_of::<Self>()
+    }
+
+    fn on_vertex_layout() -> Vec<(i32, u32, u8, *const c_void)> {
+        unsafe {
+            vec![
+                get_attribute_format::<FVec3>(offset_of!(Self, position)),
+                get_attribute_format::<FVec3>(offset_of!(Self, color)),
+            ]
+        }
+    }
+}
+
+fn fixture(path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join(path)
+}
+
+fn device() -> RendererDevice {
+    let mut device = RendererDevice::headless(SIZE, SIZE).expect("OSMesa is required");
+    device.ctx.reset_state();
+    device.ctx.color_buffer.set(0.0, 0.0, 1.0, 1.0);
+    device
+        .ctx
+        .clear_buffers(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+    device
+}
+
+// Compares against `tests/golden/<name>.png`, allowing off by one channels from the software
+// rasterizer. Run with `UPDATE_GOLDEN=1` to write the images again after a deliberate change
+fn assert_golden(name: &str, image: &RgbaImage) {
+    let path = fixture(&format!("golden/{}.png", name));
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        image.save(&path).unwrap();
+        return;
+    }
+
+    let golden = image::open(&path).unwrap().to_rgba();
+    assert_eq!(golden.dimensions(), image.dimensions());
+
+    for (x, y, expected) in golden.enumerate_pixels() {
+        let actual = image.get_pixel(x, y);
+        let close = expected
+            .0
+            .iter()
+            .zip(actual.0.iter())
+            .all(|(a, b)| (*a as i32 - *b as i32).abs() <= 1);
+        assert!(
+            close,
+            "{} differs at ({}, {}): expected {:?}, got {:?}",
+            name, x, y, expected.0, actual.0
+        );
+    }
+}
+
+#[test]
+fn read_pixels_returns_the_clear_color() {
+    let device = device();
+    let image = device.read_pixels();
+
+    assert_eq!(image.dimensions(), (SIZE, SIZE));
+    assert!(image.pixels().all(|pixel| pixel.0 == [0, 0, 255, 255]));
+}
+
+// Red quad over the top left quarter
+fn quad(device: &mut RendererDevice) -> RenderComponent {
+    device.new_mesh(
+        &fixture("shaders/flat-color.glsl"),
+        vec![
+            Vertex::new(-1.0, 1.0, 1.0, 0.0, 0.0),
+            Vertex::new(-1.0, 0.0, 1.0, 0.0, 0.0),
+            Vertex::new(0.0, 0.0, 1.0, 0.0, 0.0),
+            Vertex::new(0.0, 1.0, 1.0, 0.0, 0.0),
+        ],
+        Some(vec![0, 1, 2, 0, 2, 3]),
+        Vec::new(),
+        None,
+    )
+}
+
+#[test]
+fn render_component_matches_golden() {
+    let mut device = device();
+    let mut quad = quad(&mut device);
+
+    device.render_component(&mut quad);
+    assert_golden("top-left-quad", &device.read_pixels());
+    device.delete_mesh(&quad);
+}
+
+#[test]
+fn render_target_matches_golden() {
+    let mut device = device();
+    let mut quad = quad(&mut device);
+    let target = device
+        .create_render_target(SIZE / 2, SIZE / 2, TextureFiltering::Pixelated)
+        .unwrap();
+
+    device.bind_render_target(&target);
+    device
+        .ctx
+        .clear_buffers(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+    device.render_component(&mut quad);
+    let image = device.read_pixels();
+    device.unbind_render_target();
+
+    assert_golden("top-left-quad-half", &image);
+    assert_eq!(device.ctx.viewport.size(), (SIZE, SIZE));
+    assert!(device
+        .read_pixels()
+        .pixels()
+        .all(|pixel| pixel.0 == [0, 0, 255, 255]));
+
+    device.destroy_render_target(&target);
+    device.delete_mesh(&quad);
+    device.end_frame();
+}
+
+#[repr(C)]
+struct TintParams {
+    factor: [f32; 4],
+}
+
+#[test]
+fn post_process_runs_enabled_passes() {
+    let mut device = device();
+    let mut quad = quad(&mut device);
+    let mut stack = PostProcessStack::new(&mut device, SIZE, SIZE).unwrap();
+
+    let mut draw = |device: &mut RendererDevice, stack: &PostProcessStack| {
+        stack.begin(device);
+        device.ctx.color_buffer.set(0.0, 0.0, 1.0, 1.0);
+        device
+            .ctx
+            .clear_buffers(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        device.render_component(&mut quad);
+        stack.present(device);
+        device.read_pixels()
+    };
+
+    // Without passes the scene is copied over as is
+    assert_golden("top-left-quad", &draw(&mut device, &stack));
+
+    stack.add_pass(
+        &mut device,
+        "tint",
+        &fixture("shaders/tint.glsl"),
+        TintParams {
+            factor: [0.5, 1.0, 0.5, 1.0],
+        },
+    );
+    let image = draw(&mut device, &stack);
+    assert!((image.get_pixel(0, 0).0[0] as i32 - 128).abs() <= 1);
+    assert!((image.get_pixel(SIZE - 1, SIZE - 1).0[2] as i32 - 128).abs() <= 1);
+
+    stack.params_mut::<TintParams>("tint").unwrap().factor = [1.0; 4];
+    assert_golden("top-left-quad", &draw(&mut device, &stack));
+
+    stack.params_mut::<TintParams>("tint").unwrap().factor = [0.0; 4];
+    assert!(stack.set_enabled("tint", false));
+    assert_golden("top-left-quad", &draw(&mut device, &stack));
+
+    stack.delete(&mut device);
+    device.delete_mesh(&quad);
+}
+
+#[test]
+fn destroyed_objects_are_deleted_at_end_frame() {
+    let mut device = device();
+    let quad = quad(&mut device);
+    let target = device
+        .create_render_target(SIZE, SIZE, TextureFiltering::Pixelated)
+        .unwrap();
+
+    let live = device.ctx.live_objects();
+    assert_eq!(live.vertex_arrays.len(), 1);
+    assert_eq!(live.buffers.len(), 2);
+    assert_eq!(live.programs.len(), 1);
+    assert_eq!(live.textures.len(), 2);
+    assert_eq!(live.framebuffers.len(), 1);
+
+    device.destroy_mesh(&quad);
+    device.destroy_render_target(&target);
+    assert_eq!(device.texture_cache.len(), 0);
+    assert_eq!(device.ctx.live_objects().len(), live.len());
+
+    device.end_frame();
+    assert!(device.ctx.live_objects().is_empty());
+}
+
+#[test]
+fn shutdown_reports_what_was_not_deleted() {
+    let mut device = device();
+    let quad = quad(&mut device);
+    let target = device
+        .create_render_target(SIZE, SIZE, TextureFiltering::Pixelated)
+        .unwrap();
+    device.delete_render_target(&target);
+
+    let material = device.material(quad.material).unwrap();
+    let program = device.shader(material.shader).unwrap().program.0;
+
+    // Textures belong to the cache and go with it, the mesh was never deleted
+    let leaks = device.shutdown();
+    assert!(leaks.textures.is_empty());
+    assert!(leaks.framebuffers.is_empty());
+    assert_eq!(leaks.buffers.len(), 2);
+    assert_eq!(leaks.vertex_arrays, vec![quad.vao.0]);
+    assert_eq!(leaks.programs, vec![program]);
+}
+
+#[test]
+fn meshes_share_the_program_of_a_file() {
+    let mut device = device();
+    let first = quad(&mut device);
+    let second = quad(&mut device);
+
+    assert_eq!(device.ctx.live_objects().programs.len(), 1);
+
+    // Parameters belong to each mesh's own material
+    device
+        .material_mut(first.material)
+        .unwrap()
+        .set("u_unused", 1.0f32);
+    assert!(device
+        .material(first.material)
+        .unwrap()
+        .get("u_unused")
+        .is_some());
+    assert!(device
+        .material(second.material)
+        .unwrap()
+        .get("u_unused")
+        .is_none());
+
+    device.delete_mesh(&first);
+    assert_eq!(device.ctx.live_objects().programs.len(), 1);
+    assert!(device.material(first.material).is_none());
+
+    device.delete_mesh(&second);
+    assert!(device.ctx.live_objects().is_empty());
+
+    // Compiled again once nothing used it anymore
+    let mut third = quad(&mut device);
+    device.render_component(&mut third);
+    assert_golden("top-left-quad", &device.read_pixels());
+    device.delete_mesh(&third);
+}
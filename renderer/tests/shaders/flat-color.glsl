@@ -0,0 +1,25 @@
+#pragma SHADER
+#version 420
+precision highp float;
+
+in vec3 position;
+in vec3 color;
+
+out vec3 v_color;
+
+void main() {
+    gl_Position = vec4(position, 1.0);
+    v_color = color;
+}
+
+#pragma SHADER
+#version 420
+precision highp float;
+
+in vec3 v_color;
+
+out vec4 fragColor;
+
+void main() {
+    fragColor = vec4(v_color, 1.0);
+}
@@ -0,0 +1,31 @@
+#pragma SHADER
+#version 420
+precision highp float;
+
+in vec2 position;
+
+out vec2 v_uv;
+
+void main() {
+    gl_Position = vec4(position, 0.0, 1.0);
+    v_uv = position * 0.5 + 0.5;
+}
+
+#pragma SHADER
+#version 420
+precision highp float;
+
+in vec2 v_uv;
+
+uniform sampler2D source;
+
+layout (std140) uniform PassParams
+{
+    vec4 factor;
+};
+
+out vec4 fragColor;
+
+void main() {
+    fragColor = texture(source, v_uv) * factor;
+}
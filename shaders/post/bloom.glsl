@@ -0,0 +1,53 @@
+#pragma SHADER
+#version 420
+precision highp float;
+
+in vec2 position;
+
+out vec2 v_uv;
+
+void main() {
+    gl_Position = vec4(position, 0.0, 1.0);
+    v_uv = position * 0.5 + 0.5;
+}
+
+#pragma SHADER
+#version 420
+precision highp float;
+
+in vec2 v_uv;
+
+uniform sampler2D source;
+
+layout (std140) uniform PassParams
+{
+    float threshold;
+    float intensity;
+    // In pixels
+    float radius;
+};
+
+out vec4 fragColor;
+
+vec3 bright(vec2 uv) {
+    vec3 color = texture(source, uv).rgb;
+    float brightness = max(color.r, max(color.g, color.b));
+    return color * max(brightness - threshold, 0.0) / max(brightness, 0.0001);
+}
+
+void main() {
+    vec2 texel = radius / vec2(textureSize(source, 0));
+    vec3 glow = vec3(0.0);
+    float total = 0.0;
+
+    for (int x = -3; x <= 3; x++) {
+        for (int y = -3; y <= 3; y++) {
+            float weight = exp(-float(x * x + y * y) / 8.0);
+            glow += bright(v_uv + vec2(x, y) * texel) * weight;
+            total += weight;
+        }
+    }
+
+    vec4 color = texture(source, v_uv);
+    fragColor = vec4(color.rgb + glow / total * intensity, color.a);
+}
@@ -0,0 +1,44 @@
+#pragma SHADER
+#version 420
+precision highp float;
+
+in vec2 position;
+
+out vec2 v_uv;
+
+void main() {
+    gl_Position = vec4(position, 0.0, 1.0);
+    v_uv = position * 0.5 + 0.5;
+}
+
+#pragma SHADER
+#version 420
+precision highp float;
+
+in vec2 v_uv;
+
+uniform sampler2D source;
+
+layout (std140) uniform PassParams
+{
+    vec4 lift;
+    vec4 gamma;
+    vec4 gain;
+    float saturation;
+    float contrast;
+};
+
+out vec4 fragColor;
+
+void main() {
+    vec3 color = texture(source, v_uv).rgb;
+
+    color = gain.rgb * (color + lift.rgb * (1.0 - color));
+    color = pow(max(color, vec3(0.0)), 1.0 / gamma.rgb);
+
+    float luma = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    color = mix(vec3(luma), color, saturation);
+    color = (color - 0.5) * contrast + 0.5;
+
+    fragColor = vec4(clamp(color, 0.0, 1.0), 1.0);
+}
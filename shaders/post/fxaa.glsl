@@ -0,0 +1,80 @@
+#pragma SHADER
+#version 420
+precision highp float;
+
+in vec2 position;
+
+out vec2 v_uv;
+
+void main() {
+    gl_Position = vec4(position, 0.0, 1.0);
+    v_uv = position * 0.5 + 0.5;
+}
+
+#pragma SHADER
+#version 420
+precision highp float;
+
+in vec2 v_uv;
+
+uniform sampler2D source;
+
+layout (std140) uniform PassParams
+{
+    // Smallest local contrast, relative to the brightest neighbour, that gets smoothed
+    float edge_threshold;
+    // Ignores edges darker than this, mostly noise
+    float edge_threshold_min;
+    float subpixel;
+};
+
+out vec4 fragColor;
+
+float luma(vec3 color) {
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+// Simplified FXAA 3.11 quality pass, meant to run after tonemapping
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(source, 0));
+
+    vec4 center = texture(source, v_uv);
+    float luma_m = luma(center.rgb);
+    float luma_nw = luma(texture(source, v_uv + vec2(-1.0, 1.0) * texel).rgb);
+    float luma_ne = luma(texture(source, v_uv + vec2(1.0, 1.0) * texel).rgb);
+    float luma_sw = luma(texture(source, v_uv + vec2(-1.0, -1.0) * texel).rgb);
+    float luma_se = luma(texture(source, v_uv + vec2(1.0, -1.0) * texel).rgb);
+
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    if (luma_max - luma_min < max(edge_threshold_min, luma_max * edge_threshold)) {
+        fragColor = center;
+        return;
+    }
+
+    vec2 direction = vec2(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se)
+    );
+
+    float reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * subpixel * 0.125, 1.0 / 128.0);
+    float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2(-8.0), vec2(8.0)) * texel;
+
+    vec3 near = 0.5 * (
+        texture(source, v_uv + direction * (1.0 / 3.0 - 0.5)).rgb +
+        texture(source, v_uv + direction * (2.0 / 3.0 - 0.5)).rgb
+    );
+    vec3 far = near * 0.5 + 0.25 * (
+        texture(source, v_uv + direction * -0.5).rgb +
+        texture(source, v_uv + direction * 0.5).rgb
+    );
+
+    float luma_far = luma(far);
+    if (luma_far < luma_min || luma_far > luma_max) {
+        fragColor = vec4(near, center.a);
+    } else {
+        fragColor = vec4(far, center.a);
+    }
+}
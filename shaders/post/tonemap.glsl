@@ -0,0 +1,39 @@
+#pragma SHADER
+#version 420
+precision highp float;
+
+in vec2 position;
+
+out vec2 v_uv;
+
+void main() {
+    gl_Position = vec4(position, 0.0, 1.0);
+    v_uv = position * 0.5 + 0.5;
+}
+
+#pragma SHADER
+#version 420
+precision highp float;
+
+in vec2 v_uv;
+
+uniform sampler2D source;
+
+layout (std140) uniform PassParams
+{
+    float exposure;
+    float gamma;
+};
+
+out vec4 fragColor;
+
+// Narkowicz's fit of the ACES filmic curve
+vec3 aces(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+    vec3 color = texture(source, v_uv).rgb * exposure;
+    color = pow(aces(color), vec3(1.0 / gamma));
+    fragColor = vec4(color, 1.0);
+}
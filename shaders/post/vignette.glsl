@@ -0,0 +1,37 @@
+#pragma SHADER
+#version 420
+precision highp float;
+
+in vec2 position;
+
+out vec2 v_uv;
+
+void main() {
+    gl_Position = vec4(position, 0.0, 1.0);
+    v_uv = position * 0.5 + 0.5;
+}
+
+#pragma SHADER
+#version 420
+precision highp float;
+
+in vec2 v_uv;
+
+uniform sampler2D source;
+
+layout (std140) uniform PassParams
+{
+    vec4 color;
+    float intensity;
+    float smoothness;
+};
+
+out vec4 fragColor;
+
+void main() {
+    vec4 source_color = texture(source, v_uv);
+    float edge = length(v_uv - 0.5) * 1.41421356;
+    float amount = smoothstep(1.0 - smoothness, 1.0, edge) * intensity;
+
+    fragColor = vec4(mix(source_color.rgb, color.rgb, amount * color.a), source_color.a);
+}